@@ -44,7 +44,8 @@ async function fetchFromBackend(path, options) {
     const candidates = activeApiBase ? [activeApiBase] : [API_BASE, NODE_API_BASE];
     for (const base of candidates) {
         try {
-            const response = await fetch(`${base}${path}`, options);
+            // Only the Rust backend reads the session cookie
+            const response = await fetch(`${base}${path}`, base === API_BASE ? { ...options, credentials: 'include' } : options);
             activeApiBase = base;
             return response;
         } catch (error) {
@@ -64,14 +65,14 @@ async function fetchConfigEnv() {
     if (activeApiBase) {
         const path = activeApiBase === NODE_API_BASE ? '/config/env-vars' : '/config/env';
         try {
-            return await fetch(`${activeApiBase}${path}`);
+            return await fetch(`${activeApiBase}${path}`, { credentials: 'include' });
         } catch (error) {
             return null;
         }
     }
 
     try {
-        const response = await fetch(`${API_BASE}/config/env`);
+        const response = await fetch(`${API_BASE}/config/env`, { credentials: 'include' });
         activeApiBase = API_BASE;
         return response;
     } catch (error) {
@@ -503,6 +504,7 @@ async function handleCredentialResponse(response) {
         
         // Verify the credential with our backend
         const authResponse = await fetch(`${API_BASE}/google/auth/verify`, {
+            credentials: 'include',
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json'
@@ -21,7 +21,7 @@ class DatabaseAdmin {
 
     async loadEnvConfig() {
         try {
-            const response = await fetch(`${this.apiBaseUrl}/config/env`);
+            const response = await fetch(`${this.apiBaseUrl}/config/env`, { credentials: 'include' });
             if (response.ok) {
                 this.envConfig = await response.json();
                 console.log('Loaded env config:', this.envConfig);
@@ -388,7 +388,7 @@ class DatabaseAdmin {
         this.addLog(`🔍 Checking table: ${tableName} using connection: ${this.selectedConnection}`);
         
         try {
-            const response = await this.makeRequest(`/db/table/${tableName}?connection=${this.selectedConnection}`, {
+            const response = await this.makeRequest(`/db/table/${encodeURIComponent(tableName)}?connection=${this.selectedConnection}`, {
                 method: 'GET'
             });
 
@@ -456,6 +456,7 @@ class DatabaseAdmin {
         this.addLog(`📡 Making request to: ${url}`);
         
         const defaultOptions = {
+            credentials: 'include',
             headers: {
                 'Content-Type': 'application/json',
                 'Accept': 'application/json'
@@ -574,8 +575,6 @@ class DatabaseAdmin {
         this._tableDataTabulator = new Tabulator(container, {
             ajaxURL: `${apiBase}/db/table-rows`,
             ajaxRequestFunc: (url, config, params) => {
-                const sortField = params.sort?.[0]?.field ?? null;
-                const sortDir = params.sort?.[0]?.dir ?? null;
                 const total = this._tableDataTotal;
                 const size = params.size;
                 const page = params.page;
@@ -599,6 +598,7 @@ class DatabaseAdmin {
                 });
 
                 return fetch(`${apiBase}/db/table-rows`, {
+                    credentials: 'include',
                     method: 'POST',
                     headers: { 'Content-Type': 'application/json' },
                     body: JSON.stringify({
@@ -606,8 +606,8 @@ class DatabaseAdmin {
                         connection,
                         page,
                         size,
-                        sort_field: sortField,
-                        sort_dir: sortDir,
+                        sort: params.sort ?? [],
+                        filter: params.filter ?? [],
                     })
                 }).then(r => r.json()).then(resp => {
                     if (resp.error) {
@@ -629,6 +629,7 @@ class DatabaseAdmin {
             paginationSize: 200,
             paginationButtonCount: 0,
             sortMode: 'remote',
+            filterMode: 'remote',
             layout: 'fitColumns',
             responsiveLayout: 'hide',
             tooltips: true,
@@ -638,6 +639,7 @@ class DatabaseAdmin {
             autoColumnsDefinitions: (definitions) => {
                 definitions.forEach(def => {
                     def.headerSortStartingDir = 'desc';
+                    def.headerFilter = 'input';
                     def.tooltip = true;
                     const orig = def.formatter;
                     def.formatter = (cell) => {
@@ -231,33 +231,9 @@ async fn get_table_details(pool: &PgPool, table_name: &str) -> Result<HashMap<St
 
 async fn execute_safe_query(pool: &PgPool, query: &str) -> Result<serde_json::Value, sqlx::Error> {
     let rows = sqlx::query(query).fetch_all(pool).await?;
-    
-    let mut results = Vec::new();
-    for row in rows {
-        let mut row_map = serde_json::Map::new();
-        
-        // This is a simplified approach - in production you'd want to handle types properly
-        for (i, column) in row.columns().iter().enumerate() {
-            let value = match row.try_get_raw(i) {
-                Ok(raw_value) => {
-                    // Try to convert to string for simplicity
-                    if raw_value.is_null() {
-                        serde_json::Value::Null
-                    } else {
-                        // For demo purposes, convert everything to string
-                        serde_json::Value::String(format!("{:?}", raw_value))
-                    }
-                }
-                Err(_) => serde_json::Value::String("Error reading value".to_string()),
-            };
-            
-            row_map.insert(column.name().to_string(), value);
-        }
-        
-        results.push(serde_json::Value::Object(row_map));
-    }
-
-    Ok(serde_json::Value::Array(results))
+    // Same Postgres-to-JSON mapping as the main server (src/pg_json.rs): exact NUMERIC strings,
+    // arrays as JSON arrays, BYTEA as base64
+    Ok(serde_json::Value::Array(rows.iter().map(crate::pg_json::row_to_json).collect()))
 }
 
 fn get_table_description(table_name: &str) -> Option<String> {
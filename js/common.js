@@ -1456,7 +1456,7 @@ npm install -g @openai/codex</code></pre>`;
                 return;
             }
             try {
-                const response = await fetch(`${getApiBase()}/github-cli/status`, { method: 'GET' });
+                const response = await fetch(`${getApiBase()}/github-cli/status`, { credentials: 'include', method: 'GET' });
                 if (!response.ok) {
                     rustServerAvailable = false;
                     if (localStorage.getItem('github-cli-confirmed') === 'true') {
@@ -1675,6 +1675,7 @@ async function apiCall(endpoint, method = 'GET', data = null) {
     try {
         const options = {
             method: method,
+            credentials: 'include',
             headers: {
                 'Content-Type': 'application/json',
             }
@@ -2409,6 +2410,7 @@ async function updateRustApiStatusPanel(showConfigureLink = true, adminPath = 'a
     try {
         // Check if backend API is running
         const healthResponse = await fetch('http://localhost:8081/api/health', {
+            credentials: 'include',
             method: 'GET',
             timeout: 5000
         });
@@ -2578,7 +2580,7 @@ function updateStatusIndicator(indicatorId, textId, isActive, activeText, inacti
 // Function to check a single database connection
 async function checkDatabaseConnection(endpoint, indicatorId, textId, activeText, inactiveText) {
     try {
-        const response = await fetch(`http://localhost:8081/api/db/${endpoint}`);
+        const response = await fetch(`http://localhost:8081/api/db/${endpoint}`, { credentials: 'include' });
         const result = await response.json();
         updateStatusIndicator(indicatorId, textId, result.success, activeText, inactiveText);
         return result.success;
@@ -2610,7 +2612,7 @@ async function checkBackendStatus() {
         // Locations Database - only show when active
         (async () => {
             try {
-                const response = await fetch(`http://localhost:8081/api/db/test-locations-connection`);
+                const response = await fetch(`http://localhost:8081/api/db/test-locations-connection`, { credentials: 'include' });
                 const result = await response.json();
                 const container = document.getElementById('location-db-container');
                 if (result.success) {
@@ -2656,6 +2658,7 @@ async function stopRustServer() {
     try {
         // Call the Rust API restart endpoint which performs a clean shutdown
         const response = await fetch('http://localhost:8081/api/config/restart', {
+            credentials: 'include',
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json'
@@ -114,6 +114,7 @@ async function storeFallbackCSV(csvText) {
     try {
         // Try to use the Rust API endpoint to save the file (if available)
         const saveResponse = await fetch('http://localhost:8081/api/files/csv', {
+            credentials: 'include',
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json',
@@ -524,6 +525,7 @@ async function fetchListFeed(url, options = {}, forceCorsProxy = false, API_BASE
         */
         // Use Rust backend to fetch the external URL
         const proxyResponse = await fetch(`${API_BASE}/proxy/external`, {
+            credentials: 'include',
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json',
@@ -855,6 +855,7 @@ Do not include any explanation or additional text.`;
         try {
             // Try Gemini first
             const geminiResponse = await fetch('http://localhost:8081/api/gemini/analyze', {
+                credentials: 'include',
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ prompt })
@@ -873,6 +874,7 @@ Do not include any explanation or additional text.`;
 
             // Fallback to Claude
             const claudeResponse = await fetch('http://localhost:8081/api/claude/analyze', {
+                credentials: 'include',
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ prompt })
@@ -1321,6 +1323,7 @@ Do not include any explanation or additional text.`;
     async saveUpdatedDataset(data, localFilePath) {
         try {
             const response = await fetch('http://localhost:8081/api/save-dataset', {
+                credentials: 'include',
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
@@ -1429,6 +1432,7 @@ Do not include any explanation or additional text.`;
             }
             // Route through Rust proxy to avoid CORS (same mechanism as lists.csv CORS=TRUE)
             const proxyResponse = await fetch('http://localhost:8081/api/proxy/csv', {
+                credentials: 'include',
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ url })
@@ -1644,6 +1648,7 @@ Do not include any explanation or additional text.`;
 
             // Call the Rust endpoint to refresh the local file
             const response = await fetch('http://localhost:8081/api/refresh-local', {
+                credentials: 'include',
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
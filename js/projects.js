@@ -690,8 +690,8 @@ class ProjectsManager {
         // Database fields that exist
         recognized.id = project.id;
         recognized.status = project.status || 'unknown';
-        recognized.created_date = project.created_date;
-        recognized.modified_date = project.modified_date;
+        recognized.created_date = project.date_entered || project.created_date;
+        recognized.modified_date = project.date_modified || project.modified_date;
         
         // Fields that don't exist in database - provide defaults
         recognized.location = 'Location TBD';
@@ -862,8 +862,8 @@ class ProjectsManager {
                 // Filter projects that might be high priority (recent or contains urgency keywords)
                 filtered = filtered.filter(p => {
                     const text = ((p.name || '') + ' ' + (p.description || '')).toLowerCase();
-                    const isRecent = p.created_date && 
-                        new Date(p.created_date) > new Date(Date.now() - 30 * 24 * 60 * 60 * 1000); // 30 days
+                    const isRecent = (p.date_entered || p.created_date) && 
+                        new Date(p.date_entered || p.created_date) > new Date(Date.now() - 30 * 24 * 60 * 60 * 1000); // 30 days
                     return isRecent || text.includes('urgent') || text.includes('priority') || 
                            text.includes('critical') || text.includes('important');
                 });
@@ -882,15 +882,15 @@ class ProjectsManager {
                 if (!aNameMatch && bNameMatch) return 1;
                 
                 // If both match name or both match description, sort by creation date
-                const dateA = new Date(a.created_date || 0);
-                const dateB = new Date(b.created_date || 0);
+                const dateA = new Date(a.date_entered || a.created_date || 0);
+                const dateB = new Date(b.date_entered || b.created_date || 0);
                 return dateB - dateA;
             });
         } else {
             // Sort by creation date (newest first) for consistent ordering
             filtered.sort((a, b) => {
-                const dateA = new Date(a.created_date || 0);
-                const dateB = new Date(b.created_date || 0);
+                const dateA = new Date(a.date_entered || a.created_date || 0);
+                const dateB = new Date(b.date_entered || b.created_date || 0);
                 return dateB - dateA;
             });
         }
@@ -438,7 +438,7 @@ async function checkGeminiKeyStatus() {
     }
     try {
         // Call the config/current endpoint to check if Gemini key is available
-        const response = await fetch(`${API_BASE}/config/current`);
+        const response = await fetch(`${API_BASE}/config/current`, { credentials: 'include' });
         if (response.ok) {
             const config = await response.json();
             updateGeminiKeyUI(config.gemini_api_key_present);
@@ -647,7 +647,7 @@ async function testGeminiFromPanel() {
     resultDiv.innerHTML = '<div style="color: var(--text-secondary); font-size: 12px;">Testing Gemini API...</div>';
     
     try {
-        const response = await fetch(`${API_BASE}/config/gemini`);
+        const response = await fetch(`${API_BASE}/config/gemini`, { credentials: 'include' });
         const data = await response.json();
         
         if (data.success) {
@@ -713,7 +713,7 @@ async function isGeminiKeyAvailable() {
         return false;
     }
     try {
-        const response = await fetch(`${API_BASE}/config/current`);
+        const response = await fetch(`${API_BASE}/config/current`, { credentials: 'include' });
         if (response.ok) {
             const config = await response.json();
             return config.gemini_api_key_present;
@@ -1979,6 +1979,7 @@ async function stopLocalWebServer() {
     if (rustAvailable) {
         try {
             const response = await fetch('http://localhost:8081/api/config/stop-webroot', {
+                credentials: 'include',
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' }
             });
@@ -0,0 +1,26 @@
+-- Drops the CRM schema baseline. Relationship tables go first so their
+-- foreign keys never block the entity tables they reference.
+
+DROP TABLE IF EXISTS projects_accounts;
+DROP TABLE IF EXISTS projects_contacts;
+DROP TABLE IF EXISTS campaigns_leads;
+DROP TABLE IF EXISTS contacts_opportunities;
+DROP TABLE IF EXISTS accounts_opportunities;
+DROP TABLE IF EXISTS accounts_contacts;
+DROP TABLE IF EXISTS users_roles;
+DROP TABLE IF EXISTS taggables;
+DROP TABLE IF EXISTS tags;
+DROP TABLE IF EXISTS surveyquestionoptions;
+DROP TABLE IF EXISTS calls;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS products;
+DROP TABLE IF EXISTS events;
+DROP TABLE IF EXISTS documents;
+DROP TABLE IF EXISTS campaigns;
+DROP TABLE IF EXISTS leads;
+DROP TABLE IF EXISTS activities;
+DROP TABLE IF EXISTS opportunities;
+DROP TABLE IF EXISTS projects;
+DROP TABLE IF EXISTS contacts;
+DROP TABLE IF EXISTS accounts;
+DROP TABLE IF EXISTS users;
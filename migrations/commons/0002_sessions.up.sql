@@ -0,0 +1,15 @@
+-- Server-side login sessions. The browser only holds a signed cookie with the session id;
+-- deleting a row revokes that login immediately.
+
+CREATE TABLE sessions (
+    id VARCHAR(128) PRIMARY KEY,
+    state JSONB NOT NULL DEFAULT '{}'::jsonb,
+    user_id VARCHAR(255),
+    provider VARCHAR(50),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_sessions_expires_at ON sessions (expires_at);
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
@@ -0,0 +1,15 @@
+-- Named, parameterized read-only queries for /api/db/saved. `parameters` is a JSON array of
+-- { name, type, required, default, description }; entry n binds to $n in `sql`.
+
+CREATE TABLE saved_queries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(150) NOT NULL UNIQUE,
+    description TEXT,
+    sql TEXT NOT NULL,
+    connection VARCHAR(100),
+    parameters JSONB NOT NULL DEFAULT '[]'::jsonb,
+    cache_ttl_seconds INTEGER CHECK (cache_ttl_seconds > 0),
+    created_by VARCHAR(255),
+    date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,19 @@
+-- Who changed what: one row per mutating request or admin action, written by src/audit.rs.
+-- `changes` holds the changed fields as { field: { from, to } } (or a summary for bulk
+-- operations), with secret values already redacted.
+
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    occurred_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    actor VARCHAR(255),
+    provider VARCHAR(50),
+    action VARCHAR(100) NOT NULL,
+    target VARCHAR(255),
+    changes JSONB NOT NULL DEFAULT '{}'::jsonb,
+    outcome VARCHAR(20) NOT NULL,
+    status SMALLINT
+);
+
+CREATE INDEX idx_audit_log_occurred_at ON audit_log (occurred_at);
+CREATE INDEX idx_audit_log_actor ON audit_log (actor);
+CREATE INDEX idx_audit_log_action ON audit_log (action);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS projects_audit, accounts_audit, contacts_audit, opportunities_audit, activities_audit,
+    leads_audit, campaigns_audit, documents_audit, events_audit, products_audit, roles_audit, calls_audit, tags_audit;
@@ -0,0 +1,31 @@
+-- Per-entity change history in the SuiteCRM `{entity}_audit` shape: one row per changed field,
+-- written by src/history.rs in the same transaction as the API update. Values of TEXT columns go
+-- in the *_text columns, everything else in *_string. created_by is the users.id of the editor.
+
+DO $$
+DECLARE
+    entity TEXT;
+BEGIN
+    FOREACH entity IN ARRAY ARRAY[
+        'projects', 'accounts', 'contacts', 'opportunities', 'activities', 'leads', 'campaigns',
+        'documents', 'events', 'products', 'roles', 'calls', 'tags'
+    ] LOOP
+        EXECUTE format(
+            'CREATE TABLE %I (
+                id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+                parent_id UUID NOT NULL REFERENCES %I(id) ON DELETE CASCADE,
+                date_created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                created_by VARCHAR(36),
+                field_name VARCHAR(100) NOT NULL,
+                data_type VARCHAR(100),
+                before_value_string VARCHAR(255),
+                after_value_string VARCHAR(255),
+                before_value_text TEXT,
+                after_value_text TEXT
+            )',
+            entity || '_audit', entity
+        );
+        EXECUTE format('CREATE INDEX %I ON %I (parent_id, date_created)', 'idx_' || entity || '_audit_parent', entity || '_audit');
+    END LOOP;
+END
+$$;
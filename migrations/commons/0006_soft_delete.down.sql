@@ -0,0 +1,12 @@
+DO $$
+DECLARE
+    entity TEXT;
+BEGIN
+    FOREACH entity IN ARRAY ARRAY[
+        'projects', 'accounts', 'contacts', 'opportunities', 'activities', 'leads', 'campaigns',
+        'documents', 'events', 'products', 'roles', 'calls', 'tags'
+    ] LOOP
+        EXECUTE format('ALTER TABLE %I DROP COLUMN deleted, DROP COLUMN deleted_at, DROP COLUMN deleted_by', entity);
+    END LOOP;
+END
+$$;
@@ -0,0 +1,23 @@
+-- SuiteCRM-style soft delete for the CRM entity tables: DELETE through the API sets `deleted`,
+-- list endpoints skip those rows, and `partner_tools purge` removes them for good.
+-- deleted_by is the users.id of whoever deleted the row, as in created_by / modified_user_id.
+
+DO $$
+DECLARE
+    entity TEXT;
+BEGIN
+    FOREACH entity IN ARRAY ARRAY[
+        'projects', 'accounts', 'contacts', 'opportunities', 'activities', 'leads', 'campaigns',
+        'documents', 'events', 'products', 'roles', 'calls', 'tags'
+    ] LOOP
+        EXECUTE format(
+            'ALTER TABLE %I
+                ADD COLUMN deleted BOOLEAN NOT NULL DEFAULT FALSE,
+                ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE,
+                ADD COLUMN deleted_by VARCHAR(36)',
+            entity
+        );
+        EXECUTE format('CREATE INDEX %I ON %I (deleted_at) WHERE deleted', 'idx_' || entity || '_deleted', entity);
+    END LOOP;
+END
+$$;
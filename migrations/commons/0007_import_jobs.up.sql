@@ -0,0 +1,29 @@
+-- Background imports started with POST /api/import/jobs, written by src/import_jobs.rs.
+-- `request` is the import request as sent, replayed to resume a failed job; each committed
+-- batch adds to `rows_done` and the counters in the same transaction as its rows, so
+-- `rows_done` is always where a resume starts. `errors` holds up to 1000 row errors as
+-- { row, column, message }.
+
+CREATE TABLE import_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind VARCHAR(50) NOT NULL,
+    table_name VARCHAR(100) NOT NULL,
+    request JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'completed', 'failed')),
+    batch_size INTEGER NOT NULL CHECK (batch_size > 0),
+    total_rows INTEGER NOT NULL,
+    rows_done INTEGER NOT NULL DEFAULT 0,
+    inserted INTEGER NOT NULL DEFAULT 0,
+    updated INTEGER NOT NULL DEFAULT 0,
+    skipped INTEGER NOT NULL DEFAULT 0,
+    rejected INTEGER NOT NULL DEFAULT 0,
+    errors JSONB NOT NULL DEFAULT '[]'::jsonb,
+    error TEXT,
+    created_by VARCHAR(255),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_import_jobs_created_at ON import_jobs (created_at);
+CREATE INDEX idx_import_jobs_status ON import_jobs (status);
@@ -0,0 +1,12 @@
+ALTER TABLE projects
+    DROP COLUMN fiscal_year,
+    DROP COLUMN project_number,
+    DROP COLUMN project_type,
+    DROP COLUMN department,
+    DROP COLUMN region,
+    DROP COLUMN country,
+    DROP COLUMN framework,
+    DROP COLUMN naics_sector,
+    DROP COLUMN naics_code,
+    DROP COLUMN profile_url,
+    DROP COLUMN committed;
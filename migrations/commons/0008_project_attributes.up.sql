@@ -0,0 +1,23 @@
+-- Structured project attributes. The Excel import used to fold these into `description` as
+-- "Label: value" lines and keep only a High/Medium/Low `priority` for the committed amount;
+-- `partner_tools backfill-project-attributes` moves those lines of existing rows into the columns.
+-- naics_code is the two-digit NAICS sector code for naics_sector (e.g. '52' for Finance and Insurance).
+
+ALTER TABLE projects
+    ADD COLUMN fiscal_year INTEGER,
+    ADD COLUMN project_number VARCHAR(50),
+    ADD COLUMN project_type VARCHAR(50),
+    ADD COLUMN department VARCHAR(100),
+    ADD COLUMN region VARCHAR(100),
+    ADD COLUMN country VARCHAR(100),
+    ADD COLUMN framework VARCHAR(100),
+    ADD COLUMN naics_sector VARCHAR(255),
+    ADD COLUMN naics_code VARCHAR(6),
+    ADD COLUMN profile_url TEXT,
+    ADD COLUMN committed NUMERIC(26, 6);
+
+CREATE INDEX idx_projects_region ON projects (region);
+CREATE INDEX idx_projects_department ON projects (department);
+CREATE INDEX idx_projects_country ON projects (country);
+CREATE INDEX idx_projects_fiscal_year ON projects (fiscal_year);
+CREATE INDEX idx_projects_naics_code ON projects (naics_code);
@@ -0,0 +1,8 @@
+ALTER TABLE projects
+    DROP COLUMN external_source,
+    DROP COLUMN external_id,
+    DROP COLUMN last_synced_at,
+    DROP COLUMN skills,
+    DROP COLUMN issue_areas,
+    DROP COLUMN location,
+    DROP COLUMN organization;
@@ -0,0 +1,16 @@
+-- Projects synced from DemocracyLab by `partner_tools sync-democracylab` (src/democracylab_sync.rs).
+-- A synced project is found again by (external_source, external_id); last_synced_at is when the
+-- sync last saw it upstream. skills and issue_areas are JSON arrays of DemocracyLab tag names.
+
+ALTER TABLE projects
+    ADD COLUMN external_source VARCHAR(50),
+    ADD COLUMN external_id VARCHAR(100),
+    ADD COLUMN last_synced_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN skills JSONB,
+    ADD COLUMN issue_areas JSONB,
+    ADD COLUMN location TEXT,
+    ADD COLUMN organization TEXT;
+
+CREATE UNIQUE INDEX idx_projects_external_id ON projects (external_source, external_id);
+CREATE INDEX idx_projects_skills ON projects USING GIN (skills);
+CREATE INDEX idx_projects_issue_areas ON projects USING GIN (issue_areas);
@@ -0,0 +1,3 @@
+-- pg_trgm stays installed; other schemas in the database may use it
+DROP INDEX IF EXISTS idx_accounts_name_trgm;
+DROP INDEX IF EXISTS idx_projects_name_trgm;
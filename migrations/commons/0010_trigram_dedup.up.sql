@@ -0,0 +1,7 @@
+-- Fuzzy duplicate checks (`dedup.similarity`, src/dedup.rs) match the first key with pg_trgm's `%`
+-- and similarity(), so the lookup is an index scan instead of reading every live record.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_projects_name_trgm ON projects USING GIN (name gin_trgm_ops);
+CREATE INDEX idx_accounts_name_trgm ON accounts USING GIN (name gin_trgm_ops);
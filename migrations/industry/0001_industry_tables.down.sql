@@ -0,0 +1,9 @@
+-- Drops the Industry Database baseline. Factor and flow tables go first so
+-- their foreign keys never block industry / factor.
+
+DROP TABLE IF EXISTS interstate_factor;
+DROP TABLE IF EXISTS interstate;
+DROP TABLE IF EXISTS trade_factor;
+DROP TABLE IF EXISTS trade;
+DROP TABLE IF EXISTS factor;
+DROP TABLE IF EXISTS industry;
@@ -0,0 +1,151 @@
+-- Industry Database (EXIOBASE) baseline: trade flows, factors and interstate tables.
+-- Older databases may have been created without primary keys or with duplicate
+-- rows, so key, constraint and column patches are wrapped in exception blocks
+-- and degrade to a NOTICE instead of failing the migration.
+
+-- industry — columns only; PK added separately so existing tables are handled
+CREATE TABLE IF NOT EXISTS industry (
+    industry_id VARCHAR(10),
+    name        TEXT        NOT NULL,
+    category    VARCHAR(100)
+);
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'industry_pkey') THEN
+        ALTER TABLE industry ADD PRIMARY KEY (industry_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped industry_pkey: %', SQLERRM;
+END $$;
+
+-- factor
+CREATE TABLE IF NOT EXISTS factor (
+    factor_id   INTEGER,
+    unit        VARCHAR(50),
+    stressor    TEXT,
+    extension   VARCHAR(100)
+);
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'factor_pkey') THEN
+        ALTER TABLE factor ADD PRIMARY KEY (factor_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped factor_pkey: %', SQLERRM;
+END $$;
+
+-- trade
+CREATE TABLE IF NOT EXISTS trade (
+    id        BIGSERIAL     PRIMARY KEY,
+    trade_id  INTEGER       NOT NULL,
+    year      SMALLINT      NOT NULL,
+    region1   VARCHAR(10)   NOT NULL,
+    region2   VARCHAR(10)   NOT NULL,
+    industry1 VARCHAR(10),
+    industry2 VARCHAR(10),
+    amount    NUMERIC(18,4),
+    flow_type VARCHAR(10)   NOT NULL DEFAULT 'unknown',
+    country   VARCHAR(10)   NOT NULL DEFAULT 'unknown'
+);
+ALTER TABLE trade ADD COLUMN IF NOT EXISTS flow_type VARCHAR(10) NOT NULL DEFAULT 'unknown';
+ALTER TABLE trade ADD COLUMN IF NOT EXISTS country   VARCHAR(10) NOT NULL DEFAULT 'unknown';
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'trade_dedup') THEN
+        ALTER TABLE trade ADD CONSTRAINT trade_dedup UNIQUE (trade_id, year, country, flow_type);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped trade_dedup: %', SQLERRM;
+END $$;
+
+-- trade_factor
+CREATE TABLE IF NOT EXISTS trade_factor (
+    id           BIGSERIAL      PRIMARY KEY,
+    trade_id     INTEGER        NOT NULL,
+    year         SMALLINT       NOT NULL,
+    country      VARCHAR(10)    NOT NULL,
+    flow_type    VARCHAR(10)    NOT NULL,
+    factor_id    INTEGER        NOT NULL,
+    coefficient  NUMERIC(20,10),
+    level        NUMERIC(20,6)
+);
+
+-- interstate
+CREATE TABLE IF NOT EXISTS interstate (
+    id                  BIGSERIAL     PRIMARY KEY,
+    trade_id            INTEGER       NOT NULL,
+    year                SMALLINT      NOT NULL,
+    region1             VARCHAR(10)   NOT NULL,
+    region2             VARCHAR(10)   NOT NULL,
+    industry1           VARCHAR(10),
+    industry2           VARCHAR(10),
+    amount              NUMERIC(18,4),
+    commodity_code      VARCHAR(30),
+    industry_code       VARCHAR(30),
+    economic_multiplier NUMERIC(10,6)
+);
+
+-- interstate_factor
+CREATE TABLE IF NOT EXISTS interstate_factor (
+    id                  BIGSERIAL     PRIMARY KEY,
+    interstate_id       VARCHAR(80)   NOT NULL,
+    trade_id            INTEGER       NOT NULL,
+    factor_id           INTEGER,
+    coefficient         NUMERIC(20,10),
+    state_industry_code VARCHAR(30),
+    level               NUMERIC(20,6),
+    flow_type           VARCHAR(20),
+    employment_impact   NUMERIC(20,10)
+);
+-- Add factor_id / coefficient columns if table already existed without them
+ALTER TABLE interstate_factor ADD COLUMN IF NOT EXISTS factor_id INTEGER;
+ALTER TABLE interstate_factor ADD COLUMN IF NOT EXISTS coefficient NUMERIC(20,10);
+
+-- FK constraints (skipped if the referenced PKs are unavailable)
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'fk_trade_industry1') THEN
+        ALTER TABLE trade ADD CONSTRAINT fk_trade_industry1 FOREIGN KEY (industry1) REFERENCES industry(industry_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped fk_trade_industry1: %', SQLERRM;
+END $$;
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'fk_trade_industry2') THEN
+        ALTER TABLE trade ADD CONSTRAINT fk_trade_industry2 FOREIGN KEY (industry2) REFERENCES industry(industry_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped fk_trade_industry2: %', SQLERRM;
+END $$;
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'fk_tf_factor') THEN
+        ALTER TABLE trade_factor ADD CONSTRAINT fk_tf_factor FOREIGN KEY (factor_id) REFERENCES factor(factor_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped fk_tf_factor: %', SQLERRM;
+END $$;
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'fk_istate_industry1') THEN
+        ALTER TABLE interstate ADD CONSTRAINT fk_istate_industry1 FOREIGN KEY (industry1) REFERENCES industry(industry_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped fk_istate_industry1: %', SQLERRM;
+END $$;
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'fk_istate_industry2') THEN
+        ALTER TABLE interstate ADD CONSTRAINT fk_istate_industry2 FOREIGN KEY (industry2) REFERENCES industry(industry_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped fk_istate_industry2: %', SQLERRM;
+END $$;
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'fk_isf_factor') THEN
+        ALTER TABLE interstate_factor ADD CONSTRAINT fk_isf_factor FOREIGN KEY (factor_id) REFERENCES factor(factor_id);
+    END IF;
+EXCEPTION WHEN others THEN RAISE NOTICE 'skipped fk_isf_factor: %', SQLERRM;
+END $$;
+
+-- Indexes
+CREATE INDEX IF NOT EXISTS idx_trade_lookup       ON trade (trade_id, year, country, flow_type);
+CREATE INDEX IF NOT EXISTS idx_trade_year_country ON trade (year, country);
+CREATE INDEX IF NOT EXISTS idx_trade_region1      ON trade (region1);
+CREATE INDEX IF NOT EXISTS idx_trade_region2      ON trade (region2);
+CREATE INDEX IF NOT EXISTS idx_trade_flow_type    ON trade (flow_type);
+CREATE INDEX IF NOT EXISTS idx_tf_lookup          ON trade_factor (trade_id, year, country, flow_type);
+CREATE INDEX IF NOT EXISTS idx_tf_factor_id       ON trade_factor (factor_id);
+CREATE INDEX IF NOT EXISTS idx_tf_year_country    ON trade_factor (year, country);
+CREATE INDEX IF NOT EXISTS idx_istate_trade_id    ON interstate (trade_id);
+CREATE INDEX IF NOT EXISTS idx_istate_year        ON interstate (year);
+CREATE INDEX IF NOT EXISTS idx_istate_region1     ON interstate (region1);
+CREATE INDEX IF NOT EXISTS idx_istate_region2     ON interstate (region2);
+CREATE INDEX IF NOT EXISTS idx_isf_trade_id       ON interstate_factor (trade_id);
+CREATE INDEX IF NOT EXISTS idx_isf_interstate_id  ON interstate_factor (interstate_id);
+CREATE INDEX IF NOT EXISTS idx_isf_factor_id      ON interstate_factor (factor_id);
@@ -0,0 +1,247 @@
+// src/audit.rs
+//! Audit trail of write operations and admin actions, stored in `audit_log`.
+//!
+//! Mutating handlers call [`record`] with the response they are about to return, so the
+//! entry carries the outcome as well as the actor from the session. Values under secret-looking
+//! keys are redacted before anything is written. Recording never fails the request; a lost
+//! entry is logged instead.
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{Pool, Postgres, QueryBuilder};
+use std::sync::Arc;
+
+use crate::crm::{db_error_response, db_pool};
+use crate::ApiState;
+
+pub const REDACTED: &str = "[redacted]";
+
+/// Key fragments whose values are never written to the log
+const SECRET_MARKERS: &[&str] = &["password", "secret", "token", "credential", "authorization", "private"];
+
+/// Whether a field or .env key holds a secret, e.g. `GEMINI_API_KEY` or `client_secret`
+pub fn is_secret(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker)) || key.ends_with("key")
+}
+
+/// Copy of `value` with the values of secret keys replaced, at any depth
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_secret(key) && !value.is_null() { json!(REDACTED) } else { redact(value) };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// `{ field: { from, to } }` for every top-level field that differs, redacted.
+/// Fields missing from `after` are left out, so a partial update only shows what it touched.
+pub fn diff(before: &Value, after: &Value) -> Value {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let mut changes = Map::new();
+    for (key, new) in after.as_object().unwrap_or(&empty) {
+        let old = before.get(key).unwrap_or(&Value::Null);
+        if old == new {
+            continue;
+        }
+        let (from, to) = if is_secret(key) {
+            (if old.is_null() { Value::Null } else { json!(REDACTED) }, if new.is_null() { Value::Null } else { json!(REDACTED) })
+        } else {
+            (redact(old), redact(new))
+        };
+        changes.insert(key.clone(), json!({ "from": from, "to": to }));
+    }
+    Value::Object(changes)
+}
+
+/// "success", "denied" or "failure", from the response status
+fn outcome(status: u16) -> &'static str {
+    match status {
+        200..=399 => "success",
+        401 | 403 => "denied",
+        _ => "failure",
+    }
+}
+
+/// Write one entry. Failures are logged, never returned.
+pub async fn write(
+    db: Option<&Pool<Postgres>>,
+    user: Option<&crate::oauth::UserSession>,
+    action: &str,
+    target: &str,
+    changes: &Value,
+    status: u16,
+) {
+    let actor = user.map(|u| u.email.as_str());
+    let outcome = outcome(status);
+    let Some(db) = db else {
+        log::info!("audit (not stored, no database): {} {action} {target} -> {outcome}", actor.unwrap_or("anonymous"));
+        return;
+    };
+    let result = sqlx::query(
+        r#"
+        INSERT INTO audit_log (actor, provider, action, target, changes, outcome, status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#
+    )
+    .bind(actor)
+    .bind(user.map(|u| u.provider.as_str()))
+    .bind(action)
+    .bind(target)
+    .bind(redact(changes))
+    .bind(outcome)
+    .bind(status as i16)
+    .execute(db)
+    .await;
+    if let Err(e) = result {
+        log::error!("Failed to write audit entry {action} on {target}: {e}");
+    }
+}
+
+/// Record a handler's action, taking the actor from the session and the outcome from `response`
+pub async fn record(data: &ApiState, session: &Session, action: &str, target: &str, changes: Value, response: &HttpResponse) {
+    let user = crate::sessions::current_user(session);
+    write(data.db.as_ref(), user.as_ref(), action, target, &changes, response.status().as_u16()).await;
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    /// Exact action, or a prefix ending in `.` / `*` (e.g. `config.` or `saved_query*`)
+    pub action: Option<String>,
+    /// Substring of the target, case-insensitive
+    pub target: Option<String>,
+    pub outcome: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub page: Option<i64>,
+    pub size: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: Option<String>,
+    pub provider: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+    pub changes: Value,
+    pub outcome: String,
+    pub status: Option<i16>,
+}
+
+fn push_filters(qb: &mut QueryBuilder<'_, Postgres>, query: &AuditQuery) {
+    qb.push(" WHERE TRUE");
+    if let Some(actor) = &query.actor {
+        qb.push(" AND lower(actor) = lower(").push_bind(actor.clone()).push(")");
+    }
+    if let Some(action) = &query.action {
+        match action.strip_suffix('*') {
+            Some(prefix) => qb.push(" AND starts_with(action, ").push_bind(prefix.to_string()).push(")"),
+            None if action.ends_with('.') => qb.push(" AND starts_with(action, ").push_bind(action.clone()).push(")"),
+            None => qb.push(" AND action = ").push_bind(action.clone()),
+        };
+    }
+    if let Some(target) = &query.target {
+        qb.push(" AND strpos(lower(target), lower(").push_bind(target.clone()).push(")) > 0");
+    }
+    if let Some(outcome) = &query.outcome {
+        qb.push(" AND outcome = ").push_bind(outcome.clone());
+    }
+    if let Some(since) = query.since {
+        qb.push(" AND occurred_at >= ").push_bind(since);
+    }
+    if let Some(until) = query.until {
+        qb.push(" AND occurred_at < ").push_bind(until);
+    }
+}
+
+// GET /api/audit
+pub async fn list_audit_entries(
+    data: web::Data<Arc<ApiState>>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let page = query.page.unwrap_or(1).max(1);
+    let size = query.size.unwrap_or(50).clamp(1, 500);
+
+    let mut count_qb = QueryBuilder::new("SELECT COUNT(*) FROM audit_log");
+    push_filters(&mut count_qb, &query);
+    let total: i64 = match count_qb.build_query_scalar().fetch_one(db).await {
+        Ok(total) => total,
+        Err(e) => return Ok(db_error_response(e, "count audit entries")),
+    };
+
+    let mut qb = QueryBuilder::new(
+        "SELECT id, occurred_at, actor, provider, action, target, changes, outcome, status FROM audit_log",
+    );
+    push_filters(&mut qb, &query);
+    qb.push(" ORDER BY occurred_at DESC, id DESC LIMIT ")
+        .push_bind(size)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * size);
+
+    match qb.build_query_as::<AuditEntry>().fetch_all(db).await {
+        Ok(entries) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "data": entries,
+            "total": total,
+            "page": page,
+            "size": size,
+            "last_page": ((total + size - 1) / size).max(1),
+        }))),
+        Err(e) => Ok(db_error_response(e, "list audit entries")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_secret_keys_at_any_depth() {
+        let value = json!({
+            "name": "Solar",
+            "GEMINI_API_KEY": "abc",
+            "nested": { "client_secret": "s", "password": null, "rows": [{ "token": "t", "city": "Oslo" }] }
+        });
+        assert_eq!(redact(&value), json!({
+            "name": "Solar",
+            "GEMINI_API_KEY": REDACTED,
+            "nested": { "client_secret": REDACTED, "password": null, "rows": [{ "token": REDACTED, "city": "Oslo" }] }
+        }));
+    }
+
+    #[test]
+    fn diff_lists_only_changed_fields() {
+        let before = json!({ "name": "Old", "status": "active", "google_service_key": "k1", "budget": 5 });
+        let after = json!({ "name": "New", "status": "active", "google_service_key": "k2" });
+        assert_eq!(diff(&before, &after), json!({
+            "name": { "from": "Old", "to": "New" },
+            "google_service_key": { "from": REDACTED, "to": REDACTED }
+        }));
+        assert_eq!(diff(&Value::Null, &json!({ "name": "A" })), json!({ "name": { "from": null, "to": "A" } }));
+    }
+
+    #[test]
+    fn outcome_follows_status() {
+        assert_eq!(outcome(201), "success");
+        assert_eq!(outcome(403), "denied");
+        assert_eq!(outcome(400), "failure");
+        assert_eq!(outcome(500), "failure");
+    }
+}
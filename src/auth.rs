@@ -0,0 +1,279 @@
+// src/auth.rs
+//! Role-based authorization for admin and write endpoints.
+//!
+//! A route opts in with `.wrap(auth::require(Permission::Admin))`. The middleware resolves the
+//! caller's `UserSession` from the session cookie, matches it to a `users` row by email, and checks
+//! the permissions granted by that user's `roles` (through `users_roles`).
+use actix_session::SessionExt;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error, HttpResponse};
+use anyhow::Context;
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::ApiState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Change data: imports, file saves, bulk inserts
+    Write,
+    /// Server configuration, process control, credentials and role management
+    Admin,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Write => "write",
+            Permission::Admin => "admin",
+        }
+    }
+
+    /// Whether a role (by `roles.name`, case-insensitive) carries this permission
+    pub fn granted_by(&self, role: &str) -> bool {
+        match role.to_lowercase().as_str() {
+            "admin" => true,
+            "editor" => *self == Permission::Write,
+            _ => false,
+        }
+    }
+}
+
+/// Role names held by the user with this email
+pub async fn user_roles(db: &Pool<Postgres>, email: &str) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT r.name
+        FROM users u
+        JOIN users_roles ur ON ur.user_id = u.id
+        JOIN roles r ON r.id = ur.role_id
+        WHERE lower(u.email) = lower($1) AND r.name IS NOT NULL AND NOT r.deleted
+        "#
+    )
+    .bind(email)
+    .fetch_all(db)
+    .await
+}
+
+/// `users.id` of the user with this email, as stored in `created_by`-style VARCHAR(36) columns
+pub async fn user_id(conn: &mut PgConnection, email: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id::text FROM users WHERE lower(email) = lower($1) LIMIT 1")
+        .bind(email)
+        .fetch_optional(conn)
+        .await
+}
+
+/// Give `email` the named role, creating the user and role rows if needed.
+/// Returns false if the user already had the role. A soft-deleted role is refused; restore it first.
+pub async fn grant_role(db: &Pool<Postgres>, email: &str, role: &str) -> anyhow::Result<bool> {
+    // Sign-ins without a verified email match no user; a blank one must not collect their roles
+    anyhow::ensure!(!email.trim().is_empty(), "An email is required to grant a role");
+    let mut tx = db.begin().await?;
+
+    let user_id: Option<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE lower(email) = lower($1) LIMIT 1")
+        .bind(email)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => sqlx::query_scalar("INSERT INTO users (user_name, email, status) VALUES ($1, $1, 'Active') RETURNING id")
+            .bind(email)
+            .fetch_one(&mut *tx)
+            .await
+            .context("Failed to create user")?,
+    };
+
+    // A live role wins over a deleted one of the same name
+    let existing: Option<(Uuid, bool)> =
+        sqlx::query_as("SELECT id, deleted FROM roles WHERE lower(name) = lower($1) ORDER BY deleted, date_entered LIMIT 1")
+            .bind(role)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let role_id = match existing {
+        Some((id, false)) => id,
+        Some((id, true)) => anyhow::bail!("Role '{role}' has been deleted; restore it with POST /api/roles/{id}/restore first"),
+        None => sqlx::query_scalar("INSERT INTO roles (name) VALUES ($1) RETURNING id")
+            .bind(role)
+            .fetch_one(&mut *tx)
+            .await
+            .context("Failed to create role")?,
+    };
+
+    let linked = sqlx::query("INSERT INTO users_roles (user_id, role_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(user_id)
+        .bind(role_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(linked > 0)
+}
+
+/// Middleware factory: reject callers whose roles don't grant `permission`
+pub fn require(permission: Permission) -> RequirePermission {
+    RequirePermission { permission }
+}
+
+pub struct RequirePermission {
+    permission: Permission,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = RequirePermissionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionMiddleware {
+            service: Rc::new(service),
+            permission: self.permission,
+        }))
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: Rc<S>,
+    permission: Permission,
+}
+
+fn deny(req: ServiceRequest, response: HttpResponse) -> ServiceResponse<BoxBody> {
+    req.into_response(response)
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let permission = self.permission;
+
+        Box::pin(async move {
+            let db = req
+                .app_data::<web::Data<Arc<ApiState>>>()
+                .and_then(|data| data.db.clone());
+            let target = format!("{} {}", req.method(), req.path());
+
+            let Some(user) = crate::sessions::current_user(&req.get_session()) else {
+                crate::audit::write(db.as_ref(), None, "access.denied", &target, &json!({ "requires": permission.as_str() }), 401).await;
+                return Ok(deny(req, HttpResponse::Unauthorized().json(json!({
+                    "success": false,
+                    "error": "Authentication required",
+                    "auth_url": "/api/auth/{provider}/url"
+                }))));
+            };
+
+            // Roles are matched by email, which LinkedIn and Microsoft sign-ins (and GitHub ones without
+            // a verified email) don't have; never match them to a users row with a blank email
+            if user.email.trim().is_empty() {
+                log::warn!("{} user {} has no email; denied {} on {}", user.provider, user.user_id, permission.as_str(), req.path());
+                let changes = json!({ "requires": permission.as_str(), "reason": "no verified email" });
+                crate::audit::write(db.as_ref(), Some(&user), "access.denied", &target, &changes, 403).await;
+                return Ok(deny(req, HttpResponse::Forbidden().json(json!({
+                    "success": false,
+                    "error": format!("Requires {} permission, which is granted by email; sign in with a provider that shares a verified email", permission.as_str())
+                }))));
+            }
+
+            let Some(db) = db else {
+                return Ok(deny(req, HttpResponse::ServiceUnavailable().json(json!({
+                    "success": false,
+                    "error": "Roles cannot be checked without a database connection"
+                }))));
+            };
+
+            let roles = match user_roles(&db, &user.email).await {
+                Ok(roles) => roles,
+                Err(e) => {
+                    log::error!("Failed to load roles for {}: {e}", user.email);
+                    return Ok(deny(req, HttpResponse::InternalServerError().json(json!({
+                        "success": false,
+                        "error": "Failed to check permissions"
+                    }))));
+                }
+            };
+            if !roles.iter().any(|role| permission.granted_by(role)) {
+                log::warn!("{} ({}) denied {} on {}", user.email, user.provider, permission.as_str(), req.path());
+                let changes = json!({ "requires": permission.as_str(), "roles": roles });
+                crate::audit::write(Some(&db), Some(&user), "access.denied", &target, &changes, 403).await;
+                return Ok(deny(req, HttpResponse::Forbidden().json(json!({
+                    "success": false,
+                    "error": format!("Requires {} permission", permission.as_str()),
+                    "roles": roles
+                }))));
+            }
+
+            service.call(req).await.map(ServiceResponse::map_into_boxed_body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test::{call_service, init_service, TestRequest}, App};
+
+    #[test]
+    fn roles_grant_permissions() {
+        assert!(Permission::Admin.granted_by("Admin"));
+        assert!(Permission::Write.granted_by("admin"));
+        assert!(Permission::Write.granted_by("editor"));
+        assert!(!Permission::Admin.granted_by("editor"));
+        assert!(!Permission::Write.granted_by("viewer"));
+    }
+
+    #[actix_web::test]
+    async fn anonymous_callers_are_rejected() {
+        let app = init_service(
+            App::new()
+                .wrap(crate::sessions::middleware(None, actix_web::cookie::Key::generate(), 24))
+                .route("/restart", web::post().to(HttpResponse::Ok).wrap(require(Permission::Admin))),
+        )
+        .await;
+        let resp = call_service(&app, TestRequest::post().uri("/restart").to_request()).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn users_without_an_email_are_forbidden() {
+        let user = crate::oauth::UserSession::new("li-123".into(), " ".into(), "No Email".into(), None, "linkedin".into());
+        let app = init_service(
+            App::new()
+                .route("/restart", web::post().to(HttpResponse::Ok).wrap(require(Permission::Admin)))
+                // Signs the request in, then purges the session so the store (no database here) isn't written
+                .wrap_fn(move |req, srv| {
+                    let session = req.get_session();
+                    session.insert(crate::sessions::USER_KEY, &user).unwrap();
+                    let response = srv.call(req);
+                    async move {
+                        let response = response.await;
+                        session.purge();
+                        response
+                    }
+                })
+                .wrap(crate::sessions::middleware(None, actix_web::cookie::Key::generate(), 24)),
+        )
+        .await;
+        let resp = call_service(&app, TestRequest::post().uri("/restart").to_request()).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,199 @@
+// src/connections.rs
+//! Named database connections with one cached, bounded pool per name.
+//!
+//! A name resolves to a URL either directly (`NAME=postgres://...`) or from components
+//! (`NAME_HOST`, `NAME_PORT`, `NAME_NAME`, `NAME_USER`, `NAME_PASSWORD`, `NAME_SSL_MODE`).
+//! Pools are opened on first use and reused afterwards; when the resolved URL changes
+//! (e.g. after the .env watcher reloads) the old pool is closed and a new one is built.
+use actix_web::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Resolve a connection name to a Postgres URL from the environment
+pub fn database_url(name: &str) -> Option<String> {
+    if let Ok(url) = std::env::var(name) {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Some(url);
+        }
+    }
+
+    let var = |suffix: &str| std::env::var(format!("{name}_{suffix}")).ok().filter(|v| !v.is_empty());
+    let (host, db_name, user, password) = (var("HOST")?, var("NAME")?, var("USER")?, var("PASSWORD")?);
+    let port = var("PORT").unwrap_or_else(|| "5432".to_string());
+    let ssl_mode = var("SSL_MODE").unwrap_or_else(|| "require".to_string());
+    Some(format!("postgres://{user}:{password}@{host}:{port}/{db_name}?sslmode={ssl_mode}"))
+}
+
+#[derive(Debug)]
+pub enum ConnectionError {
+    NotConfigured(String),
+    Connect(String, sqlx::Error),
+}
+
+impl ConnectionError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ConnectionError::NotConfigured(_) => StatusCode::BAD_REQUEST,
+            ConnectionError::Connect(..) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionError::NotConfigured(name) => write!(f, "Connection '{name}' not found in environment variables"),
+            ConnectionError::Connect(name, e) => write!(f, "Failed to connect to {name}: {e}"),
+        }
+    }
+}
+
+struct PoolEntry {
+    url: String,
+    pool: Pool<Postgres>,
+    opened_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PoolStats {
+    pub name: String,
+    pub max_connections: u32,
+    /// Open connections, idle or in use
+    pub size: u32,
+    pub idle: usize,
+    pub opened_at: Option<DateTime<Utc>>,
+}
+
+impl PoolStats {
+    pub fn of(name: &str, pool: &Pool<Postgres>, opened_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            name: name.to_string(),
+            max_connections: pool.options().get_max_connections(),
+            size: pool.size(),
+            idle: pool.num_idle(),
+            opened_at,
+        }
+    }
+}
+
+pub struct ConnectionRegistry {
+    pools: Mutex<HashMap<String, PoolEntry>>,
+    max_connections: u32,
+}
+
+impl ConnectionRegistry {
+    /// Pool size comes from DB_POOL_MAX_CONNECTIONS (default 5) and applies to each named pool
+    pub fn from_env() -> Self {
+        let max_connections = std::env::var("DB_POOL_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(5);
+        Self {
+            pools: Mutex::new(HashMap::new()),
+            max_connections,
+        }
+    }
+
+    fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .acquire_timeout(Duration::from_secs(10))
+            // Hand idle slots back to the server; Azure caps connections per database
+            .idle_timeout(Duration::from_secs(300))
+    }
+
+    /// The pool for `name`, opening it on first use
+    pub async fn get(&self, name: &str) -> Result<Pool<Postgres>, ConnectionError> {
+        let url = database_url(name).ok_or_else(|| ConnectionError::NotConfigured(name.to_string()))?;
+
+        if let Some(entry) = self.pools.lock().unwrap().get(name) {
+            if entry.url == url {
+                return Ok(entry.pool.clone());
+            }
+        }
+
+        // Connect outside the lock; if another request raced us, keep whichever landed first
+        let pool = self
+            .pool_options()
+            .connect(&url)
+            .await
+            .map_err(|e| ConnectionError::Connect(name.to_string(), e))?;
+
+        let mut pools = self.pools.lock().unwrap();
+        if let Some(existing) = pools.get(name) {
+            if existing.url == url {
+                close_in_background(pool);
+                return Ok(existing.pool.clone());
+            }
+        }
+        log::info!("Opened connection pool '{name}' (max {} connections)", self.max_connections);
+        if let Some(stale) = pools.insert(name.to_string(), PoolEntry { url, pool: pool.clone(), opened_at: Utc::now() }) {
+            close_in_background(stale.pool);
+        }
+        Ok(pool)
+    }
+
+    /// Close pools whose configuration changed or disappeared. Called after the .env reload.
+    pub fn refresh(&self) {
+        let mut pools = self.pools.lock().unwrap();
+        let stale: Vec<String> = pools
+            .iter()
+            .filter(|(name, entry)| database_url(name).as_deref() != Some(entry.url.as_str()))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in stale {
+            if let Some(entry) = pools.remove(&name) {
+                log::info!("Connection '{name}' changed; closing its pool");
+                close_in_background(entry.pool);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Vec<PoolStats> {
+        let pools = self.pools.lock().unwrap();
+        let mut stats: Vec<PoolStats> = pools
+            .iter()
+            .map(|(name, entry)| PoolStats::of(name, &entry.pool, Some(entry.opened_at)))
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+}
+
+fn close_in_background(pool: Pool<Postgres>) {
+    tokio::spawn(async move { pool.close().await });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_direct_urls_and_components() {
+        std::env::set_var("REGISTRY_TEST_DIRECT", "postgres://u:p@db:5432/x");
+        assert_eq!(database_url("REGISTRY_TEST_DIRECT").as_deref(), Some("postgres://u:p@db:5432/x"));
+
+        for (k, v) in [("HOST", "db.example"), ("NAME", "exio"), ("USER", "me"), ("PASSWORD", "pw")] {
+            std::env::set_var(format!("REGISTRY_TEST_PARTS_{k}"), v);
+        }
+        assert_eq!(
+            database_url("REGISTRY_TEST_PARTS").as_deref(),
+            Some("postgres://me:pw@db.example:5432/exio?sslmode=require")
+        );
+        assert_eq!(database_url("REGISTRY_TEST_MISSING"), None);
+    }
+
+    #[tokio::test]
+    async fn unknown_names_are_not_configured() {
+        let registry = ConnectionRegistry::from_env();
+        let err = registry.get("REGISTRY_TEST_UNSET").await.unwrap_err();
+        assert!(matches!(err, ConnectionError::NotConfigured(_)));
+        assert!(registry.stats().is_empty());
+    }
+}
@@ -0,0 +1,1021 @@
+// src/crm.rs
+//! REST CRUD for the SuiteCRM-style entity tables created by the `commons` migrations.
+//!
+//! Each entity gets a typed input struct (used for both POST and PATCH) generated by
+//! `crm_entity!`, which also records the column limits from the schema so values are
+//! rejected with a clear message before Postgres would truncate or refuse them.
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{de, Deserialize, Deserializer};
+use serde_json::{json, Value};
+use std::fmt;
+use sqlx::{Pool, Postgres, QueryBuilder};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::{require, Permission};
+use crate::ApiState;
+
+/// Column constraint checked before a value is written
+#[derive(Debug, Clone, Copy)]
+pub enum Limit {
+    None,
+    /// VARCHAR(n)
+    Chars(usize),
+    /// NUMERIC(precision, scale)
+    Numeric(u32, u32),
+}
+
+/// A NUMERIC value kept as the decimal text it was sent as, so it reaches Postgres unrounded.
+///
+/// Requests may send a string such as `"12345678901234567890.123456"`, or a JSON number when
+/// that number has at most 15 significant digits and so survives parsing as `f64`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decimal(String);
+
+impl Decimal {
+    /// Digits before and after the point, without sign, leading or trailing zeros, or None when
+    /// the text is not a plain decimal number
+    fn digits(&self) -> Option<(&str, &str)> {
+        let unsigned = self.0.strip_prefix(['-', '+']).unwrap_or(&self.0);
+        let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let plain = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if int.len() + frac.len() == 0 || !plain(int) || !plain(frac) {
+            return None;
+        }
+        Some((int.trim_start_matches('0'), frac.trim_end_matches('0')))
+    }
+}
+
+impl From<f64> for Decimal {
+    /// The shortest text that reads back as `value`, which is what a spreadsheet cell showed
+    fn from(value: f64) -> Self {
+        Decimal(value.to_string())
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct DecimalVisitor;
+
+        impl de::Visitor<'_> for DecimalVisitor {
+            type Value = Decimal;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a decimal number or a string holding one")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Decimal, E> {
+                Ok(Decimal(v.trim().to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Decimal, E> {
+                Ok(Decimal(v.to_string()))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Decimal, E> {
+                Ok(Decimal(v.to_string()))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<Decimal, E> {
+                let decimal = Decimal::from(v);
+                match decimal.digits() {
+                    Some((int, frac)) if format!("{int}{frac}").trim_matches('0').len() <= 15 => Ok(decimal),
+                    _ => Err(E::custom("a JSON number with more than 15 significant digits loses precision; send it as a string")),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Text(Option<String>),
+    Uuid(Option<Uuid>),
+    Decimal(Option<Decimal>),
+    Int(Option<i32>),
+    Bool(Option<bool>),
+    Date(Option<NaiveDate>),
+    Timestamp(Option<DateTime<Utc>>),
+}
+
+impl FieldValue {
+    fn is_null(&self) -> bool {
+        match self {
+            FieldValue::Text(v) => v.as_deref().is_none_or(|s| s.trim().is_empty()),
+            FieldValue::Uuid(v) => v.is_none(),
+            FieldValue::Decimal(v) => v.is_none(),
+            FieldValue::Int(v) => v.is_none(),
+            FieldValue::Bool(v) => v.is_none(),
+            FieldValue::Date(v) => v.is_none(),
+            FieldValue::Timestamp(v) => v.is_none(),
+        }
+    }
+
+    fn push_bind(self, qb: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            FieldValue::Text(v) => qb.push_bind(v),
+            FieldValue::Uuid(v) => qb.push_bind(v),
+            FieldValue::Decimal(v) => qb.push_bind(v.map(|d| d.0)).push("::numeric"),
+            FieldValue::Int(v) => qb.push_bind(v),
+            FieldValue::Bool(v) => qb.push_bind(v),
+            FieldValue::Date(v) => qb.push_bind(v),
+            FieldValue::Timestamp(v) => qb.push_bind(v),
+        };
+    }
+}
+
+macro_rules! impl_field_value {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(impl From<Option<$ty>> for FieldValue {
+            fn from(v: Option<$ty>) -> Self { FieldValue::$variant(v) }
+        })*
+    };
+}
+
+impl_field_value! {
+    String => Text,
+    Uuid => Uuid,
+    Decimal => Decimal,
+    i32 => Int,
+    bool => Bool,
+    NaiveDate => Date,
+    DateTime<Utc> => Timestamp,
+}
+
+/// A column value supplied in a request body
+#[derive(Debug)]
+pub struct Field {
+    pub column: &'static str,
+    pub value: FieldValue,
+    pub limit: Limit,
+}
+
+impl Field {
+    /// Type name recorded in the `{entity}_audit` history, as SuiteCRM names it
+    pub fn data_type(&self) -> &'static str {
+        match (&self.value, self.limit) {
+            (FieldValue::Text(_), Limit::None) => "text",
+            (FieldValue::Text(_), _) => "varchar",
+            (FieldValue::Uuid(_), _) => "id",
+            (FieldValue::Decimal(_), _) => "decimal",
+            (FieldValue::Int(_), _) => "int",
+            (FieldValue::Bool(_), _) => "bool",
+            (FieldValue::Date(_), _) => "date",
+            (FieldValue::Timestamp(_), _) => "datetime",
+        }
+    }
+}
+
+/// A table whose rows are addressed as `/api/{entity}/{id}`
+pub trait CrmTable: 'static {
+    /// Table the entity is stored in
+    const TABLE: &'static str;
+    /// Singular label used in messages
+    const LABEL: &'static str;
+}
+
+pub trait EntityInput: CrmTable + serde::de::DeserializeOwned {
+    /// Columns that must be present and non-empty on create, and may not be cleared by PATCH
+    const REQUIRED: &'static [&'static str];
+    /// Writable columns, in schema order
+    const COLUMNS: &'static [&'static str];
+
+    /// Fields present in the request body; an explicit `null` is kept as a NULL value
+    fn into_fields(self) -> Vec<Field>;
+}
+
+macro_rules! field_limit {
+    () => { Limit::None };
+    ($limit:expr) => { $limit };
+}
+
+/// Declares a typed input struct for an entity table.
+///
+/// Every field is `Option<Option<T>>` so PATCH can tell an omitted field from an explicit `null`.
+macro_rules! crm_entity {
+    (
+        $(#[$meta:meta])*
+        $name:ident => $table:literal, $label:literal, required: [$($req:literal),*] {
+            $($field:ident: $ty:ty $(= $limit:expr)?),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Default, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        pub struct $name {
+            $(
+                #[serde(default, with = "::serde_with::rust::double_option")]
+                pub $field: Option<Option<$ty>>,
+            )*
+        }
+
+        impl CrmTable for $name {
+            const TABLE: &'static str = $table;
+            const LABEL: &'static str = $label;
+        }
+
+        impl EntityInput for $name {
+            const REQUIRED: &'static [&'static str] = &[$($req),*];
+            const COLUMNS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+            fn into_fields(self) -> Vec<Field> {
+                let mut fields = Vec::new();
+                $(
+                    if let Some(value) = self.$field {
+                        fields.push(Field {
+                            column: stringify!($field),
+                            value: FieldValue::from(value),
+                            limit: field_limit!($($limit)?),
+                        });
+                    }
+                )*
+                fields
+            }
+        }
+    };
+}
+
+use Limit::{Chars, Numeric};
+
+crm_entity! {
+    ProjectInput => "projects", "Project", required: ["name"] {
+        name: String = Chars(50),
+        description: String,
+        status: String = Chars(50),
+        priority: String = Chars(255),
+        estimated_start_date: NaiveDate,
+        estimated_end_date: NaiveDate,
+        fiscal_year: i32,
+        project_number: String = Chars(50),
+        project_type: String = Chars(50),
+        department: String = Chars(100),
+        region: String = Chars(100),
+        country: String = Chars(100),
+        framework: String = Chars(100),
+        naics_sector: String = Chars(255),
+        naics_code: String = Chars(6),
+        profile_url: String,
+        committed: Decimal = Numeric(26, 6),
+        location: String,
+        organization: String,
+    }
+}
+
+crm_entity! {
+    AccountInput => "accounts", "Account", required: ["name"] {
+        name: String = Chars(150),
+        account_type: String = Chars(50),
+        industry: String = Chars(50),
+        phone_office: String = Chars(100),
+        website: String = Chars(255),
+    }
+}
+
+crm_entity! {
+    ContactInput => "contacts", "Contact", required: ["last_name"] {
+        salutation: String = Chars(255),
+        first_name: String = Chars(100),
+        last_name: String = Chars(100),
+        title: String = Chars(100),
+        department: String = Chars(255),
+        account_id: Uuid,
+        phone_work: String = Chars(100),
+        phone_mobile: String = Chars(100),
+        email: String = Chars(100),
+        primary_address_street: String = Chars(150),
+        primary_address_city: String = Chars(100),
+        primary_address_state: String = Chars(100),
+        primary_address_postalcode: String = Chars(20),
+        primary_address_country: String = Chars(255),
+        description: String,
+    }
+}
+
+crm_entity! {
+    OpportunityInput => "opportunities", "Opportunity", required: ["name"] {
+        name: String = Chars(50),
+        account_id: Uuid,
+        opportunity_type: String = Chars(255),
+        lead_source: String = Chars(50),
+        amount: Decimal = Numeric(26, 6),
+        currency_id: String = Chars(36),
+        date_closed: NaiveDate,
+        sales_stage: String = Chars(255),
+        probability: Decimal = Numeric(3, 0),
+        description: String,
+    }
+}
+
+crm_entity! {
+    ActivityInput => "activities", "Activity", required: ["name"] {
+        name: String = Chars(255),
+        date_due: DateTime<Utc>,
+        date_start: DateTime<Utc>,
+        parent_type: String = Chars(255),
+        parent_id: Uuid,
+        status: String = Chars(100),
+        priority: String = Chars(255),
+        description: String,
+        contact_id: Uuid,
+        account_id: Uuid,
+    }
+}
+
+crm_entity! {
+    LeadInput => "leads", "Lead", required: ["last_name"] {
+        salutation: String = Chars(255),
+        first_name: String = Chars(100),
+        last_name: String = Chars(100),
+        title: String = Chars(100),
+        company: String = Chars(100),
+        phone_work: String = Chars(100),
+        phone_mobile: String = Chars(100),
+        email: String = Chars(100),
+        status: String = Chars(100),
+        lead_source: String = Chars(100),
+        description: String,
+        converted: bool,
+    }
+}
+
+crm_entity! {
+    CampaignInput => "campaigns", "Campaign", required: ["name"] {
+        name: String = Chars(50),
+        campaign_type: String = Chars(100),
+        status: String = Chars(100),
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+        budget: Decimal = Numeric(26, 6),
+        expected_cost: Decimal = Numeric(26, 6),
+        actual_cost: Decimal = Numeric(26, 6),
+        expected_revenue: Decimal = Numeric(26, 6),
+        objective: String,
+        content: String,
+    }
+}
+
+crm_entity! {
+    DocumentInput => "documents", "Document", required: ["document_name"] {
+        document_name: String = Chars(255),
+        filename: String = Chars(255),
+        file_ext: String = Chars(100),
+        file_mime_type: String = Chars(100),
+        revision: String = Chars(100),
+        category_id: String = Chars(100),
+        subcategory_id: String = Chars(100),
+        status: String = Chars(100),
+        description: String,
+    }
+}
+
+crm_entity! {
+    EventInput => "events", "Event", required: ["name"] {
+        name: String = Chars(255),
+        date_start: DateTime<Utc>,
+        date_end: DateTime<Utc>,
+        duration_hours: i32,
+        duration_minutes: i32,
+        location: String = Chars(255),
+        description: String,
+    }
+}
+
+crm_entity! {
+    ProductInput => "products", "Product", required: ["name"] {
+        name: String = Chars(50),
+        product_code: String = Chars(50),
+        category: String = Chars(100),
+        manufacturer: String = Chars(50),
+        cost: Decimal = Numeric(26, 6),
+        price: Decimal = Numeric(26, 6),
+        description: String,
+    }
+}
+
+crm_entity! {
+    RoleInput => "roles", "Role", required: ["name"] {
+        name: String = Chars(150),
+        description: String,
+    }
+}
+
+crm_entity! {
+    CallInput => "calls", "Call", required: ["name"] {
+        name: String = Chars(50),
+        date_start: DateTime<Utc>,
+        date_end: DateTime<Utc>,
+        duration_hours: i32,
+        duration_minutes: i32,
+        status: String = Chars(100),
+        direction: String = Chars(100),
+        parent_type: String = Chars(255),
+        parent_id: Uuid,
+        contact_id: Uuid,
+        account_id: Uuid,
+        description: String,
+    }
+}
+
+crm_entity! {
+    TagInput => "tags", "Tag", required: ["name"] {
+        name: String = Chars(255),
+    }
+}
+
+/// Columns every entity table has in addition to its writable ones
+const SYSTEM_COLUMNS: &[&str] = &["id", "date_entered", "date_modified", "deleted_at"];
+
+/// Entity tables with `{table}_audit` history and the `deleted` / `deleted_at` / `deleted_by`
+/// soft-delete columns. Tables that reference others come first, so a purge can run in this order.
+pub const ENTITY_TABLES: &[&str] = &[
+    "activities", "calls", "opportunities", "contacts", "leads", "campaigns", "documents",
+    "events", "products", "projects", "accounts", "roles", "tags",
+];
+
+/// Columns the API requires on create, by table, for writers that don't go through `EntityInput`
+const REQUIRED_COLUMNS: &[(&str, &[&str])] = &[
+    (ProjectInput::TABLE, ProjectInput::REQUIRED),
+    (AccountInput::TABLE, AccountInput::REQUIRED),
+    (ContactInput::TABLE, ContactInput::REQUIRED),
+    (OpportunityInput::TABLE, OpportunityInput::REQUIRED),
+    (ActivityInput::TABLE, ActivityInput::REQUIRED),
+    (LeadInput::TABLE, LeadInput::REQUIRED),
+    (CampaignInput::TABLE, CampaignInput::REQUIRED),
+    (DocumentInput::TABLE, DocumentInput::REQUIRED),
+    (EventInput::TABLE, EventInput::REQUIRED),
+    (ProductInput::TABLE, ProductInput::REQUIRED),
+    (RoleInput::TABLE, RoleInput::REQUIRED),
+    (CallInput::TABLE, CallInput::REQUIRED),
+    (TagInput::TABLE, TagInput::REQUIRED),
+];
+
+pub fn required_columns(table: &str) -> &'static [&'static str] {
+    REQUIRED_COLUMNS.iter().find(|(t, _)| *t == table).map_or(&[], |(_, required)| required)
+}
+
+/// Whether rows of `table` are soft-deleted, and so hidden from lists while `deleted` is set
+pub fn soft_deletes(table: &str) -> bool {
+    ENTITY_TABLES.contains(&table)
+}
+
+/// Check required columns and column limits. On PATCH (`creating == false`) only the
+/// supplied fields are checked, but required columns still may not be cleared.
+pub fn validate_fields(fields: &[Field], required: &[&str], creating: bool) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for column in required {
+        match fields.iter().find(|f| f.column == *column) {
+            Some(f) if f.value.is_null() => errors.push(format!("{column} is required and cannot be empty")),
+            None if creating => errors.push(format!("{column} is required")),
+            _ => {}
+        }
+    }
+
+    for field in fields {
+        match (&field.value, field.limit) {
+            (FieldValue::Text(Some(text)), Limit::Chars(max)) if text.chars().count() > max => {
+                errors.push(format!("{} exceeds {max} characters (got {})", field.column, text.chars().count()));
+            }
+            (FieldValue::Decimal(Some(v)), Limit::Numeric(precision, scale)) => match v.digits() {
+                None => errors.push(format!("{} is not a decimal number (got '{v}')", field.column)),
+                Some((int, _)) if int.len() > (precision - scale) as usize => {
+                    errors.push(format!("{} is out of range for NUMERIC({precision},{scale})", field.column));
+                }
+                Some((_, frac)) if frac.len() > scale as usize => {
+                    errors.push(format!("{} has more than {scale} decimal places for NUMERIC({precision},{scale})", field.column));
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    errors
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    pub page: Option<i64>,
+    pub size: Option<i64>,
+    pub sort_field: Option<String>,
+    pub sort_dir: Option<String>,
+    /// Also list soft-deleted records
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordQuery {
+    /// Return the record even if it has been soft-deleted
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+pub(crate) fn db_pool(data: &ApiState) -> std::result::Result<&Pool<Postgres>, HttpResponse> {
+    data.db.as_ref().ok_or_else(|| {
+        HttpResponse::ServiceUnavailable().json(json!({
+            "success": false,
+            "error": "Database not available. Server started without database connection."
+        }))
+    })
+}
+
+pub(crate) fn not_found<T: CrmTable>(id: Uuid) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "success": false,
+        "error": format!("{} {id} not found", T::LABEL)
+    }))
+}
+
+/// Map constraint violations to client errors; anything else is a server error
+pub(crate) fn db_error_response(e: sqlx::Error, action: &str) -> HttpResponse {
+    if let sqlx::Error::Database(db_err) = &e {
+        let detail = db_err.message().to_string();
+        match db_err.code().as_deref() {
+            Some("23503") => {
+                let reason = if detail.starts_with("update or delete") {
+                    "record is still referenced by other records"
+                } else {
+                    "references a record that does not exist"
+                };
+                return HttpResponse::Conflict().json(json!({
+                    "success": false,
+                    "error": format!("Failed to {action}: {reason}"),
+                    "details": detail
+                }));
+            }
+            Some("23505") => return HttpResponse::Conflict().json(json!({
+                "success": false,
+                "error": format!("Failed to {action}: duplicate value"),
+                "details": detail
+            })),
+            Some("22001") | Some("22003") | Some("23502") | Some("23514") => return HttpResponse::BadRequest().json(json!({
+                "success": false,
+                "error": format!("Failed to {action}: {detail}")
+            })),
+            _ => {}
+        }
+    }
+    HttpResponse::InternalServerError().json(json!({
+        "success": false,
+        "error": format!("Failed to {action}: {e}")
+    }))
+}
+
+// GET /api/{entity}
+pub async fn list_records<T: EntityInput>(
+    data: web::Data<Arc<ApiState>>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+
+    let page = query.page.unwrap_or(1).max(1);
+    let size = query.size.unwrap_or(50).clamp(1, 1000);
+    let sort_field = query.sort_field.as_deref().unwrap_or("date_modified");
+    if !T::COLUMNS.contains(&sort_field) && !SYSTEM_COLUMNS.contains(&sort_field) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "error": format!("Unknown sort field '{sort_field}' for {}", T::TABLE)
+        })));
+    }
+    let direction = match query.sort_dir.as_deref() {
+        Some(dir) if dir.eq_ignore_ascii_case("asc") => "ASC",
+        _ => "DESC",
+    };
+
+    let visible = if query.include_deleted { "TRUE" } else { "NOT deleted" };
+    let total: i64 = match sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE {visible}", T::TABLE))
+        .fetch_one(db)
+        .await
+    {
+        Ok(total) => total,
+        Err(e) => return Ok(db_error_response(e, &format!("count {}", T::TABLE))),
+    };
+
+    let sql = format!(
+        "SELECT * FROM {} WHERE {visible} ORDER BY \"{sort_field}\" {direction} NULLS LAST, id LIMIT $1 OFFSET $2",
+        T::TABLE
+    );
+    match sqlx::query(&sql).bind(size).bind((page - 1) * size).fetch_all(db).await {
+        Ok(rows) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "data": rows.iter().map(crate::row_to_json).collect::<Vec<_>>(),
+            "total": total,
+            "page": page,
+            "size": size,
+            "last_page": ((total + size - 1) / size).max(1),
+        }))),
+        Err(e) => Ok(db_error_response(e, &format!("list {}", T::TABLE))),
+    }
+}
+
+// GET /api/{entity}/{id}
+pub async fn get_record<T: EntityInput>(
+    data: web::Data<Arc<ApiState>>,
+    path: web::Path<Uuid>,
+    query: web::Query<RecordQuery>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let visible = if query.include_deleted { "TRUE" } else { "NOT deleted" };
+
+    match sqlx::query(&format!("SELECT * FROM {} WHERE id = $1 AND {visible}", T::TABLE))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+    {
+        Ok(Some(row)) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "data": crate::row_to_json(&row)
+        }))),
+        Ok(None) => Ok(not_found::<T>(id)),
+        Err(e) => Ok(db_error_response(e, &format!("fetch {}", T::LABEL.to_lowercase()))),
+    }
+}
+
+// POST /api/{entity}
+pub async fn create_record<T: EntityInput>(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: web::Json<T>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+
+    let fields = req.into_inner().into_fields();
+    let errors = validate_fields(&fields, T::REQUIRED, true);
+    if !errors.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "error": format!("Invalid {}", T::LABEL.to_lowercase()),
+            "errors": errors
+        })));
+    }
+
+    let mut qb = QueryBuilder::<Postgres>::new(format!("INSERT INTO {} (", T::TABLE));
+    let mut columns = qb.separated(", ");
+    for field in &fields {
+        columns.push(field.column);
+    }
+    qb.push(") VALUES (");
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            qb.push(", ");
+        }
+        field.value.push_bind(&mut qb);
+    }
+    qb.push(") RETURNING *");
+
+    let (response, target, changes) = match qb.build().fetch_one(db).await {
+        Ok(row) => {
+            let record = crate::row_to_json(&row);
+            let target = format!("{}/{}", T::TABLE, record["id"].as_str().unwrap_or_default());
+            let changes = crate::audit::diff(&Value::Null, &record);
+            let response = HttpResponse::Created().json(json!({
+                "success": true,
+                "id": record["id"],
+                "message": format!("{} created successfully", T::LABEL),
+                "data": record
+            }));
+            (response, target, changes)
+        }
+        Err(e) => (db_error_response(e, &format!("create {}", T::LABEL.to_lowercase())), T::TABLE.to_string(), json!({})),
+    };
+    crate::audit::record(&data, &session, &format!("{}.create", T::TABLE), &target, changes, &response).await;
+    Ok(response)
+}
+
+// PATCH /api/{entity}/{id}
+pub async fn update_record<T: EntityInput>(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    path: web::Path<Uuid>,
+    req: web::Json<T>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+
+    let fields = req.into_inner().into_fields();
+    if fields.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "error": "No fields to update"
+        })));
+    }
+    let errors = validate_fields(&fields, T::REQUIRED, false);
+    if !errors.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "error": format!("Invalid {}", T::LABEL.to_lowercase()),
+            "errors": errors
+        })));
+    }
+
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return Ok(db_error_response(e, &format!("update {}", T::LABEL.to_lowercase()))),
+    };
+
+    // Lock and read the current row first so the history and audit entries can show what changed.
+    // Deleted records must be restored before they can be edited.
+    let before = match sqlx::query(&format!("SELECT * FROM {} WHERE id = $1 AND NOT deleted FOR UPDATE", T::TABLE))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+    {
+        Ok(Some(row)) => crate::row_to_json(&row),
+        Ok(None) => return Ok(not_found::<T>(id)),
+        Err(e) => return Ok(db_error_response(e, &format!("fetch {}", T::LABEL.to_lowercase()))),
+    };
+
+    let tracked: Vec<_> = fields.iter().map(|f| (f.column, f.data_type())).collect();
+    let mut qb = QueryBuilder::<Postgres>::new(format!("UPDATE {} SET ", T::TABLE));
+    for field in fields {
+        qb.push(field.column).push(" = ");
+        field.value.push_bind(&mut qb);
+        qb.push(", ");
+    }
+    qb.push("date_modified = CURRENT_TIMESTAMP WHERE id = ").push_bind(id).push(" RETURNING *");
+
+    // The update and its history rows commit together
+    let user = crate::sessions::current_user(&session);
+    let saved: std::result::Result<Option<Value>, sqlx::Error> = async {
+        let Some(row) = qb.build().fetch_optional(&mut *tx).await? else {
+            return Ok(None);
+        };
+        let record = crate::row_to_json(&row);
+        let history = crate::history::changes(&tracked, &before, &record);
+        let editor = match &user {
+            Some(user) => crate::auth::user_id(&mut tx, &user.email).await?,
+            None => None,
+        };
+        crate::history::write(&mut tx, T::TABLE, id, editor.as_deref(), &history).await?;
+        tx.commit().await?;
+        Ok(Some(record))
+    }
+    .await;
+
+    let (response, changes) = match saved {
+        Ok(Some(record)) => {
+            let changes = crate::audit::diff(&before, &record);
+            let response = HttpResponse::Ok().json(json!({
+                "success": true,
+                "message": format!("{} updated successfully", T::LABEL),
+                "data": record
+            }));
+            (response, changes)
+        }
+        Ok(None) => return Ok(not_found::<T>(id)),
+        Err(e) => (db_error_response(e, &format!("update {}", T::LABEL.to_lowercase())), json!({})),
+    };
+    crate::audit::record(&data, &session, &format!("{}.update", T::TABLE), &format!("{}/{id}", T::TABLE), changes, &response).await;
+    Ok(response)
+}
+
+/// Set or clear the soft-delete flag and record it in the history, all in one transaction.
+/// Returns None if the record doesn't exist or already has that state.
+async fn set_deleted(
+    db: &Pool<Postgres>,
+    table: &str,
+    id: Uuid,
+    deleted: bool,
+    user: Option<&crate::oauth::UserSession>,
+) -> std::result::Result<Option<Value>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let editor = match user {
+        Some(user) => crate::auth::user_id(&mut tx, &user.email).await?,
+        None => None,
+    };
+    let row = if deleted {
+        sqlx::query(&format!(
+            "UPDATE {table} SET deleted = TRUE, deleted_at = CURRENT_TIMESTAMP, deleted_by = $2, \
+             date_modified = CURRENT_TIMESTAMP WHERE id = $1 AND NOT deleted RETURNING *"
+        ))
+        .bind(id)
+        .bind(editor.as_deref())
+        .fetch_optional(&mut *tx)
+        .await?
+    } else {
+        sqlx::query(&format!(
+            "UPDATE {table} SET deleted = FALSE, deleted_at = NULL, deleted_by = NULL, \
+             date_modified = CURRENT_TIMESTAMP WHERE id = $1 AND deleted RETURNING *"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+    };
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let change = crate::history::FieldChange {
+        field_name: "deleted".into(),
+        data_type: "bool",
+        before: Some((!deleted).to_string()),
+        after: Some(deleted.to_string()),
+    };
+    crate::history::write(&mut tx, table, id, editor.as_deref(), &[change]).await?;
+    tx.commit().await?;
+    Ok(Some(crate::row_to_json(&row)))
+}
+
+// DELETE /api/{entity}/{id}
+pub async fn delete_record<T: EntityInput>(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+
+    // Soft delete: the row stays, hidden from lists, until restored or purged from the CLI
+    let user = crate::sessions::current_user(&session);
+    let (response, changes) = match set_deleted(db, T::TABLE, id, true, user.as_ref()).await {
+        Ok(None) => return Ok(not_found::<T>(id)),
+        Ok(Some(record)) => {
+            let response = HttpResponse::Ok().json(json!({
+                "success": true,
+                "message": format!("{} {id} deleted", T::LABEL)
+            }));
+            (response, json!({ "deleted": crate::audit::redact(&record) }))
+        }
+        Err(e) => (db_error_response(e, &format!("delete {}", T::LABEL.to_lowercase())), json!({})),
+    };
+    crate::audit::record(&data, &session, &format!("{}.delete", T::TABLE), &format!("{}/{id}", T::TABLE), changes, &response).await;
+    Ok(response)
+}
+
+// POST /api/{entity}/{id}/restore
+pub async fn restore_record<T: EntityInput>(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+
+    let user = crate::sessions::current_user(&session);
+    let response = match set_deleted(db, T::TABLE, id, false, user.as_ref()).await {
+        Ok(Some(record)) => HttpResponse::Ok().json(json!({
+            "success": true,
+            "message": format!("{} {id} restored", T::LABEL),
+            "data": record
+        })),
+        Ok(None) => match sqlx::query_scalar::<_, bool>(&format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = $1)", T::TABLE))
+            .bind(id)
+            .fetch_one(db)
+            .await
+        {
+            Ok(true) => HttpResponse::Conflict().json(json!({
+                "success": false,
+                "error": format!("{} {id} is not deleted", T::LABEL)
+            })),
+            Ok(false) => return Ok(not_found::<T>(id)),
+            Err(e) => db_error_response(e, &format!("fetch {}", T::LABEL.to_lowercase())),
+        },
+        Err(e) => db_error_response(e, &format!("restore {}", T::LABEL.to_lowercase())),
+    };
+    crate::audit::record(&data, &session, &format!("{}.restore", T::TABLE), &format!("{}/{id}", T::TABLE), json!({}), &response).await;
+    Ok(response)
+}
+
+/// Register GET/PATCH/DELETE on `/{id}`, its history and restore, plus relationship routes, for an entity scope
+fn configure_record_routes<T: EntityInput>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/{id}", web::get().to(get_record::<T>))
+        .route("/{id}", web::patch().to(update_record::<T>).wrap(require(Permission::Write)))
+        .route("/{id}", web::delete().to(delete_record::<T>).wrap(require(Permission::Write)))
+        // Before the relationship routes, which would otherwise take `history` as a related entity
+        .route("/{id}/history", web::get().to(crate::history::record_history::<T>))
+        .route("/{id}/restore", web::post().to(restore_record::<T>).wrap(require(Permission::Write)))
+        .configure(crate::relationships::configure_routes::<T>);
+}
+
+fn entity_scope<T: EntityInput>(path: &str) -> actix_web::Scope {
+    web::scope(path)
+        .route("", web::get().to(list_records::<T>))
+        .route("", web::post().to(create_record::<T>).wrap(require(Permission::Write)))
+        .configure(configure_record_routes::<T>)
+}
+
+/// Full CRUD for every CRM entity
+pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(entity_scope::<ProjectInput>("/projects"))
+        .service(entity_scope::<AccountInput>("/accounts"))
+        .service(entity_scope::<ContactInput>("/contacts"))
+        .service(entity_scope::<OpportunityInput>("/opportunities"))
+        .service(entity_scope::<ActivityInput>("/activities"))
+        .service(entity_scope::<LeadInput>("/leads"))
+        .service(entity_scope::<CampaignInput>("/campaigns"))
+        .service(entity_scope::<DocumentInput>("/documents"))
+        .service(entity_scope::<EventInput>("/events"))
+        .service(entity_scope::<ProductInput>("/products"))
+        // Role management decides who passes authorization, so it is admin-only
+        .service(entity_scope::<RoleInput>("/roles").wrap(require(Permission::Admin)))
+        .service(entity_scope::<CallInput>("/calls"))
+        .service(entity_scope::<TagInput>("/tags"))
+        .service(
+            web::scope("/users")
+                .configure(crate::relationships::configure_routes::<crate::relationships::Users>)
+                .wrap(require(Permission::Admin)),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_and_null_fields_are_distinguished() {
+        let input: ProjectInput = serde_json::from_value(json!({ "name": "Solar", "status": null })).unwrap();
+        let fields = input.into_fields();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].value, FieldValue::Text(Some("Solar".to_string())));
+        assert_eq!(fields[1].value, FieldValue::Text(None));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(serde_json::from_value::<AccountInput>(json!({ "name": "A", "bogus": 1 })).is_err());
+    }
+
+    #[test]
+    fn varchar_limits_are_enforced() {
+        let input: ProjectInput = serde_json::from_value(json!({ "name": "x".repeat(51) })).unwrap();
+        let errors = validate_fields(&input.into_fields(), ProjectInput::REQUIRED, true);
+        assert_eq!(errors, vec!["name exceeds 50 characters (got 51)"]);
+    }
+
+    #[test]
+    fn required_fields_on_create_and_patch() {
+        let missing = validate_fields(&[], ContactInput::REQUIRED, true);
+        assert_eq!(missing, vec!["last_name is required"]);
+        assert!(validate_fields(&[], ContactInput::REQUIRED, false).is_empty());
+
+        let cleared: ContactInput = serde_json::from_value(json!({ "last_name": "  " })).unwrap();
+        let errors = validate_fields(&cleared.into_fields(), ContactInput::REQUIRED, false);
+        assert_eq!(errors, vec!["last_name is required and cannot be empty"]);
+    }
+
+    #[test]
+    fn numeric_precision_is_enforced() {
+        let input: OpportunityInput = serde_json::from_value(json!({ "name": "Deal", "probability": 1000.0 })).unwrap();
+        let errors = validate_fields(&input.into_fields(), OpportunityInput::REQUIRED, true);
+        assert_eq!(errors, vec!["probability is out of range for NUMERIC(3,0)"]);
+    }
+
+    #[test]
+    fn decimals_keep_every_digit() {
+        let input: ProjectInput =
+            serde_json::from_value(json!({ "name": "Solar", "committed": "12345678901234567890.123456" })).unwrap();
+        let fields = input.into_fields();
+        assert_eq!(fields[1].value, FieldValue::Decimal(Some(Decimal("12345678901234567890.123456".to_string()))));
+        assert!(validate_fields(&fields, ProjectInput::REQUIRED, true).is_empty());
+
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT ");
+        fields.into_iter().nth(1).unwrap().value.push_bind(&mut qb);
+        assert_eq!(qb.sql(), "SELECT $1::numeric");
+
+        let number: ProjectInput = serde_json::from_value(json!({ "committed": 2500000.75 })).unwrap();
+        assert_eq!(number.committed, Some(Some(Decimal("2500000.75".to_string()))));
+        assert!(serde_json::from_value::<ProjectInput>(json!({ "committed": 12345678901234567890.123456 })).is_err());
+    }
+
+    #[test]
+    fn decimal_scale_and_syntax_are_enforced() {
+        let errors = |committed: &str| {
+            let input: ProjectInput = serde_json::from_value(json!({ "committed": committed })).unwrap();
+            validate_fields(&input.into_fields(), &[], false)
+        };
+        assert!(errors("-0001.500000").is_empty());
+        assert_eq!(errors("123456789012345678901"), vec!["committed is out of range for NUMERIC(26,6)"]);
+        assert_eq!(errors("1.1234567"), vec!["committed has more than 6 decimal places for NUMERIC(26,6)"]);
+        assert_eq!(errors("1e6"), vec!["committed is not a decimal number (got '1e6')"]);
+        assert_eq!(errors("NaN"), vec!["committed is not a decimal number (got 'NaN')"]);
+    }
+}
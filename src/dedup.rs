@@ -0,0 +1,366 @@
+// src/dedup.rs
+//! Duplicate detection for imports.
+//!
+//! Each import path has a default `DedupPolicy` matching what it always did (projects from
+//! Excel on name + region + department, accounts on name + industry, JSON and DemocracyLab
+//! projects on name), and any import request can pass its own as `dedup`. Keys the incoming row
+//! has no value for are left out of the comparison, so an account without an industry is
+//! matched on name alone.
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::history::{self, FieldChange};
+use crate::schema::quote_ident;
+
+/// What to do with a row that matches an existing record
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupAction {
+    /// Leave the existing record alone and don't import the row
+    #[default]
+    Skip,
+    /// Overwrite the existing record with the row's non-empty values
+    #[serde(alias = "update_existing")]
+    Update,
+    /// Fill only the existing record's empty fields from the row
+    #[serde(alias = "merge_fields")]
+    Merge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupPolicy {
+    /// Columns compared against existing records
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// Trim and collapse runs of whitespace before comparing
+    #[serde(default)]
+    pub ignore_whitespace: bool,
+    /// Match the first key (usually the name) by trigram similarity at or above this, from 0 to 1,
+    /// instead of exactly
+    #[serde(default)]
+    pub similarity: Option<f64>,
+    #[serde(default)]
+    pub action: DedupAction,
+}
+
+/// A record the incoming row duplicates
+#[derive(Debug)]
+pub struct Match {
+    pub id: Uuid,
+    pub existing: Value,
+    /// Keys that were compared, which excludes those the row had no value for
+    pub keys: Vec<String>,
+    pub similarity: Option<f64>,
+}
+
+impl DedupPolicy {
+    /// Exact match on `keys`, skipping duplicates
+    pub fn on(keys: &[&str]) -> Self {
+        DedupPolicy {
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+            ignore_case: false,
+            ignore_whitespace: false,
+            similarity: None,
+            action: DedupAction::Skip,
+        }
+    }
+
+    /// Check the policy against the keys an import can compare
+    pub fn validate(&self, allowed: &[&str]) -> Result<(), Vec<String>> {
+        let mut errors: Vec<String> = self
+            .keys
+            .iter()
+            .filter(|k| !allowed.contains(&k.as_str()))
+            .map(|k| format!("Unknown dedup key '{k}'; expected one of: {}", allowed.join(", ")))
+            .collect();
+        if self.keys.is_empty() {
+            errors.push("dedup.keys must name at least one column".to_string());
+        }
+        if self.similarity.is_some_and(|s| !(0.0..=1.0).contains(&s)) {
+            errors.push("dedup.similarity must be between 0 and 1".to_string());
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Reported as the import's `duplicate_check_columns`
+    pub fn describe(&self) -> String {
+        let mut options = Vec::new();
+        if self.ignore_case {
+            options.push("ignore case".to_string());
+        }
+        if self.ignore_whitespace {
+            options.push("ignore whitespace".to_string());
+        }
+        if let (Some(s), Some(first)) = (self.similarity, self.keys.first()) {
+            options.push(format!("{first} similarity >= {s}"));
+        }
+        let options = if options.is_empty() { String::new() } else { format!(" ({})", options.join(", ")) };
+        let action = match self.action {
+            DedupAction::Skip => "skip",
+            DedupAction::Update => "update",
+            DedupAction::Merge => "merge",
+        };
+        format!("{}{options}; {action}", self.keys.join(" + "))
+    }
+
+    fn normalize(&self, value: &str) -> String {
+        let value = if self.ignore_whitespace { value.split_whitespace().collect::<Vec<_>>().join(" ") } else { value.to_string() };
+        if self.ignore_case { value.to_lowercase() } else { value }
+    }
+
+    /// SQL for a column normalized the way `normalize` treats the incoming value
+    fn normalized_column(&self, column: &str) -> String {
+        let mut sql = format!("t.{}::text", quote_ident(column));
+        if self.ignore_whitespace {
+            sql = format!("regexp_replace(btrim({sql}), '\\s+', ' ', 'g')");
+        }
+        if self.ignore_case {
+            sql = format!("lower({sql})");
+        }
+        sql
+    }
+
+    /// Whether `row` duplicates `earlier`, another row of the same import that isn't in the
+    /// database yet; compared the way `find` compares a row with existing records
+    pub fn matches(&self, earlier: &Value, row: &Value) -> bool {
+        let mut compared = false;
+        for (i, key) in self.keys.iter().enumerate() {
+            let Some(ours) = text(row, key) else { continue };
+            let Some(theirs) = text(earlier, key) else { return false };
+            let (ours, theirs) = (self.normalize(&ours), self.normalize(&theirs));
+            let same = match self.similarity {
+                Some(threshold) if i == 0 => similarity(&ours, &theirs) >= threshold,
+                _ => ours == theirs,
+            };
+            if !same {
+                return false;
+            }
+            compared = true;
+        }
+        compared
+    }
+}
+
+/// `key` of a row as text, if it has a value
+fn text(row: &Value, key: &str) -> Option<String> {
+    match row.get(key)? {
+        Value::Null => None,
+        Value::String(s) if s.trim().is_empty() => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Trigrams of each word, padded as pg_trgm pads them
+fn trigrams(value: &str) -> HashSet<String> {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .flat_map(|word| {
+            let padded: Vec<char> = format!("  {} ", word.to_lowercase()).chars().collect();
+            padded.windows(3).map(|w| w.iter().collect::<String>()).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Share of trigrams two strings have in common, as pg_trgm's `similarity()`
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Find the oldest live record in `table` that `row` duplicates under `policy`
+pub async fn find(
+    conn: &mut PgConnection,
+    table: &str,
+    policy: &DedupPolicy,
+    row: &Value,
+) -> Result<Option<Match>, sqlx::Error> {
+    let keys: Vec<(&str, String)> = policy
+        .keys
+        .iter()
+        .filter_map(|k| text(row, k).map(|v| (k.as_str(), policy.normalize(&v))))
+        .collect();
+    if keys.is_empty() {
+        return Ok(None);
+    }
+    let fuzzy = policy.similarity.filter(|_| policy.keys.first().map(String::as_str) == Some(keys[0].0));
+
+    // `%` can use the trigram index, but compares against pg_trgm.similarity_threshold; imports
+    // run in a transaction, so set it to the policy's threshold for the rest of that transaction
+    if let Some(threshold) = fuzzy {
+        sqlx::query("SELECT set_config('pg_trgm.similarity_threshold', $1, true)")
+            .bind(threshold.to_string())
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let mut qb = QueryBuilder::<Postgres>::new("SELECT to_jsonb(t), ");
+    match (fuzzy, keys.first()) {
+        // Trigrams ignore case and punctuation, so the fuzzy key is compared as stored
+        (Some(_), Some((key, value))) => {
+            qb.push(format!("similarity(t.{}::text, ", quote_ident(key))).push_bind(value.clone()).push(")::float8");
+        }
+        _ => {
+            qb.push("NULL::float8");
+        }
+    }
+    qb.push(format!(" FROM {table} t WHERE NOT t.deleted"));
+    for (i, (key, value)) in keys.iter().enumerate() {
+        match fuzzy {
+            Some(threshold) if i == 0 => {
+                let column = format!("t.{}::text", quote_ident(key));
+                qb.push(format!(" AND {column} % ")).push_bind(value.clone());
+                qb.push(format!(" AND similarity({column}, ")).push_bind(value.clone()).push(") >= ").push_bind(threshold as f32);
+            }
+            _ => {
+                qb.push(format!(" AND {} = ", policy.normalized_column(key))).push_bind(value.clone());
+            }
+        }
+    }
+    // Best match first; the oldest record wins ties, as in the exact case
+    qb.push(" ORDER BY 2 DESC NULLS LAST, t.date_entered NULLS LAST, t.id LIMIT 1");
+    let best: Option<(Value, Option<f64>)> = qb.build_query_as().fetch_optional(&mut *conn).await?;
+
+    let compared = keys.iter().map(|(k, _)| k.to_string()).collect();
+    Ok(best.and_then(|(existing, similarity)| {
+        let id = existing.get("id")?.as_str()?.parse().ok()?;
+        Some(Match { id, existing, keys: compared, similarity })
+    }))
+}
+
+/// History entries for the written `columns` whose value changed, typed from the table's
+/// information_schema `(column, data_type)` pairs
+fn field_changes(columns: &[&String], types: &[(String, String)], before: &Value, after: &Value) -> Vec<FieldChange> {
+    columns
+        .iter()
+        .filter(|column| before.get(column.as_str()) != after.get(column.as_str()))
+        .map(|column| FieldChange {
+            field_name: column.to_string().into(),
+            data_type: history::data_type(types.iter().find(|(name, _)| name == *column).map_or("", |(_, t)| t.as_str())),
+            before: text(before, column),
+            after: text(after, column),
+        })
+        .collect()
+}
+
+/// Apply an update or merge of `values` onto the matched record, recording the changed fields in
+/// its history. Returns the columns written, which is empty when there was nothing to change.
+pub async fn apply(
+    conn: &mut PgConnection,
+    table: &str,
+    action: DedupAction,
+    found: &Match,
+    values: &Map<String, Value>,
+    modified_by: Option<&str>,
+) -> Result<Vec<String>, sqlx::Error> {
+    let is_empty = |v: Option<&Value>| match v {
+        None | Some(Value::Null) => true,
+        Some(Value::String(s)) => s.trim().is_empty(),
+        _ => false,
+    };
+    let columns: Vec<&String> = values
+        .iter()
+        .filter(|(column, value)| {
+            !is_empty(Some(value))
+                && match action {
+                    DedupAction::Skip => false,
+                    DedupAction::Update => found.existing.get(column.as_str()) != Some(value),
+                    DedupAction::Merge => is_empty(found.existing.get(column.as_str())),
+                }
+        })
+        .map(|(column, _)| column)
+        .collect();
+    if columns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let list = columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+    let mut qb = QueryBuilder::<Postgres>::new(format!("UPDATE {table} t SET ({list}) = (SELECT {list} FROM jsonb_populate_record(NULL::{table}, "));
+    let patch: Map<String, Value> = columns.iter().map(|c| (c.to_string(), values[c.as_str()].clone())).collect();
+    qb.push_bind(Value::Object(patch)).push(")), date_modified = now()");
+    if let Some(user) = modified_by {
+        qb.push(", modified_user_id = ").push_bind(user.to_string());
+    }
+    qb.push(" WHERE t.id = ").push_bind(found.id).push(" RETURNING to_jsonb(t)");
+    let after: Value = qb.build_query_scalar().fetch_one(&mut *conn).await?;
+
+    let types: Vec<(String, String)> = sqlx::query_as(
+        "SELECT column_name::text, data_type::text FROM information_schema.columns \
+         WHERE table_schema = current_schema() AND table_name = $1",
+    )
+    .bind(table)
+    .fetch_all(&mut *conn)
+    .await?;
+    let changes = field_changes(&columns, &types, &found.existing, &after);
+    history::write(conn, table, found.id, modified_by, &changes).await?;
+    Ok(columns.into_iter().cloned().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn updated_json_columns_are_recorded_as_text() {
+        let types = [("name".to_string(), "character varying".to_string()), ("skills".to_string(), "jsonb".to_string())];
+        let skills: Vec<String> = (0..40).map(|i| format!("Skill number {i}")).collect();
+        let before = json!({ "name": "Open Ballot", "skills": ["React"] });
+        let after = json!({ "name": "Open Ballot", "skills": skills });
+        let (name, skills) = ("name".to_string(), "skills".to_string());
+
+        let changes = field_changes(&[&name, &skills], &types, &before, &after);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].field_name.as_ref(), changes[0].data_type), ("skills", "text"));
+        assert_eq!(changes[0].before.as_deref(), Some(r#"["React"]"#));
+        assert!(changes[0].after.as_ref().unwrap().len() > 255);
+    }
+
+    #[test]
+    fn similarity_follows_pg_trgm() {
+        assert_eq!(similarity("Solar Farm", "solar farm"), 1.0);
+        assert!(similarity("Solar Farm Project", "Solar Farm Projects") > 0.8);
+        assert!(similarity("Solar Farm", "Wind Park") < 0.1);
+        assert_eq!(similarity("", "anything"), 0.0);
+    }
+
+    #[test]
+    fn policies_normalize_validate_and_describe() {
+        let policy: DedupPolicy = serde_json::from_value(serde_json::json!({
+            "keys": ["name", "industry"],
+            "ignore_case": true,
+            "ignore_whitespace": true,
+            "action": "merge_fields"
+        }))
+        .unwrap();
+        assert_eq!(policy.normalize("  Acme   Corp "), "acme corp");
+        assert_eq!(policy.describe(), "name + industry (ignore case, ignore whitespace); merge");
+        assert!(policy.validate(&["name", "industry"]).is_ok());
+        assert_eq!(policy.validate(&["name"]).unwrap_err().len(), 1);
+        assert_eq!(DedupPolicy::on(&["name"]).describe(), "name; skip");
+    }
+
+    #[test]
+    fn rows_of_one_import_match_as_records_would() {
+        let policy = DedupPolicy { ignore_case: true, ..DedupPolicy::on(&["name", "industry"]) };
+        let earlier = json!({ "name": "Acme", "industry": "Energy" });
+        assert!(policy.matches(&earlier, &json!({ "name": "ACME", "industry": "energy" })));
+        // A key the row has no value for is left out, one the earlier row lacks can't match
+        assert!(policy.matches(&earlier, &json!({ "name": "acme" })));
+        assert!(!policy.matches(&json!({ "name": "Acme" }), &json!({ "name": "Acme", "industry": "Energy" })));
+        assert!(!policy.matches(&earlier, &json!({ "name": "Acme", "industry": "Retail" })));
+        assert!(!policy.matches(&earlier, &json!({})));
+
+        let fuzzy = DedupPolicy { similarity: Some(0.6), ..DedupPolicy::on(&["name"]) };
+        assert!(fuzzy.matches(&json!({ "name": "Solar Farm Project" }), &json!({ "name": "Solar Farm Projects" })));
+    }
+}
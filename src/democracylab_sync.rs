@@ -0,0 +1,310 @@
+// src/democracylab_sync.rs
+//! `partner_tools sync-democracylab`: page through the DemocracyLab projects API and upsert each
+//! project by its DemocracyLab id.
+//!
+//! Projects are mapped as `POST /api/import/democracylab` maps them, skills, issue areas, location
+//! and organization included, and written through the import pipeline with a dedup policy on
+//! `(external_source, external_id)` that updates the matched project. Each page commits on its own
+//! with `last_synced_at` set on every project it contained. A project deleted here is left deleted.
+//! Projects the DemocracyLab import created before they had ids are adopted by name the first time.
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use std::time::Duration;
+
+use crate::dedup::{DedupAction, DedupPolicy};
+use crate::import::{DemocracyLabProject, ImportTally, InsertResult, Target};
+
+/// Recorded as `created_by` and `modified_user_id` on synced projects
+const SOURCE: &str = "democracylab-sync";
+/// `external_source` of DemocracyLab projects
+const EXTERNAL_SOURCE: &str = "democracylab";
+
+/// Where the sync reads from, how far, and how often the server runs it
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub api_url: String,
+    /// Stop after this many pages even if DemocracyLab reports more
+    pub max_pages: u32,
+    /// Run in the server this often; `None` leaves it to the CLI
+    pub interval: Option<Duration>,
+}
+
+impl SyncConfig {
+    /// DEMOCRACYLAB_API_URL (default https://www.democracylab.org/api), DEMOCRACYLAB_SYNC_MAX_PAGES
+    /// (default 100) and DEMOCRACYLAB_SYNC_INTERVAL_MINUTES (unset or 0: not scheduled)
+    pub fn from_env() -> Self {
+        let env_parse = |key: &str| std::env::var(key).ok().and_then(|v| v.trim().parse::<u64>().ok()).filter(|n| *n > 0);
+        Self {
+            api_url: std::env::var("DEMOCRACYLAB_API_URL")
+                .ok()
+                .filter(|u| !u.trim().is_empty())
+                .unwrap_or_else(|| "https://www.democracylab.org/api".to_string()),
+            max_pages: env_parse("DEMOCRACYLAB_SYNC_MAX_PAGES").map_or(100, |n| n.min(u32::MAX as u64) as u32),
+            interval: env_parse("DEMOCRACYLAB_SYNC_INTERVAL_MINUTES").map(|m| Duration::from_secs(m * 60)),
+        }
+    }
+}
+
+/// One page of `GET /projects/`
+#[derive(Debug, Deserialize)]
+pub struct ProjectPage {
+    #[serde(default)]
+    pub projects: Vec<DemocracyLabProject>,
+    #[serde(rename = "numPages", default)]
+    pub num_pages: Option<u32>,
+}
+
+pub async fn fetch_page(client: &reqwest::Client, api_url: &str, page: u32) -> anyhow::Result<ProjectPage> {
+    let url = format!("{}/projects/", api_url.trim_end_matches('/'));
+    let response = client.get(&url).query(&[("page", page)]).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("DemocracyLab returned {status} for page {page} of {url}");
+    }
+    Ok(response.json().await?)
+}
+
+/// Whether there is a page after page `number`. Without `numPages`, paging goes on until a page is empty.
+fn has_more(page: &ProjectPage, number: u32, max_pages: u32) -> bool {
+    !page.projects.is_empty() && number < max_pages && page.num_pages.is_none_or(|n| number < n)
+}
+
+/// Totals of one sync run
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub pages: u32,
+    pub fetched: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    /// Seen again with nothing to change
+    pub unchanged: usize,
+    /// Deleted here, so left alone
+    pub deleted: usize,
+    /// "Page N, row M: reason" for each project that couldn't be written
+    pub errors: Vec<String>,
+}
+
+impl SyncSummary {
+    pub fn message(&self, dry_run: bool) -> String {
+        let verb = if dry_run { "would insert" } else { "inserted" };
+        format!(
+            "DemocracyLab: {} project(s) on {} page(s); {verb} {}, updated {}, unchanged {}, deleted here {}, rejected {}",
+            self.fetched,
+            self.pages,
+            self.inserted,
+            self.updated,
+            self.unchanged,
+            self.deleted,
+            self.errors.len()
+        )
+    }
+
+    pub fn changes(&self) -> Value {
+        json!({
+            "pages": self.pages,
+            "fetched": self.fetched,
+            "inserted": self.inserted,
+            "updated": self.updated,
+            "unchanged": self.unchanged,
+            "deleted": self.deleted,
+            "rejected": self.errors.len(),
+        })
+    }
+}
+
+/// Write one page in a transaction: adopt matching unsynced imports, upsert, stamp `last_synced_at`
+async fn write_page(
+    pool: &Pool<Postgres>,
+    target: &Target,
+    number: u32,
+    projects: &[DemocracyLabProject],
+    dry_run: bool,
+    summary: &mut SyncSummary,
+) -> anyhow::Result<()> {
+    let mut rows = Vec::new();
+    for (i, project) in projects.iter().enumerate() {
+        let row = crate::import::prepare_democracylab_project(i + 1, project);
+        if row.values.get("external_id").is_none_or(Value::is_null) {
+            summary.errors.push(format!("Page {number}, row {}: project '{}' has no project_id", i + 1, project.name));
+        } else {
+            rows.push(row);
+        }
+    }
+    let ids: Vec<String> = rows.iter().filter_map(|r| r.values["external_id"].as_str().map(str::to_string)).collect();
+    let names: Vec<String> = rows.iter().map(|r| r.values["name"].as_str().unwrap_or_default().to_string()).collect();
+
+    let mut tx = pool.begin().await?;
+    // The DemocracyLab import stamps what it creates; adopt a project it created under the same
+    // name, unless several share the name
+    sqlx::query(
+        r#"
+        UPDATE projects p SET external_source = $1, external_id = s.external_id
+        FROM unnest($2::text[], $3::text[]) AS s(external_id, name)
+        WHERE p.external_id IS NULL AND NOT p.deleted AND p.created_by = 'democracylab-import' AND p.name = s.name
+          AND NOT EXISTS (SELECT 1 FROM projects e WHERE e.external_source = $1 AND e.external_id = s.external_id)
+          AND (SELECT count(*) FROM projects q
+               WHERE q.external_id IS NULL AND NOT q.deleted AND q.created_by = 'democracylab-import' AND q.name = s.name) = 1
+        "#,
+    )
+    .bind(EXTERNAL_SOURCE)
+    .bind(&ids)
+    .bind(&names)
+    .execute(&mut *tx)
+    .await?;
+
+    let deleted: Vec<String> = sqlx::query_scalar("SELECT external_id FROM projects WHERE external_source = $1 AND external_id = ANY($2) AND deleted")
+        .bind(EXTERNAL_SOURCE)
+        .bind(&ids)
+        .fetch_all(&mut *tx)
+        .await?;
+    rows.retain(|row| !deleted.iter().any(|id| row.values["external_id"] == id.as_str()));
+    summary.deleted += deleted.len();
+
+    let results = crate::import::write_batch(&mut tx, target, &rows).await?;
+    let mut tally = ImportTally::default();
+    let mut synced = Vec::new();
+    for (row, result) in rows.iter().zip(results) {
+        if matches!(result, Ok(InsertResult::Inserted(..) | InsertResult::Updated(..) | InsertResult::Skipped(..))) {
+            synced.extend(row.values["external_id"].as_str().map(str::to_string));
+        }
+        tally.add(row.row, result);
+    }
+    sqlx::query("UPDATE projects SET last_synced_at = now() WHERE external_source = $1 AND external_id = ANY($2)")
+        .bind(EXTERNAL_SOURCE)
+        .bind(&synced)
+        .execute(&mut *tx)
+        .await?;
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    summary.inserted += tally.inserted;
+    summary.updated += tally.updated;
+    summary.unchanged += tally.skipped;
+    summary.errors.extend(tally.errors.into_iter().map(|e| format!("Page {number}, {}", e.replacen("Row", "row", 1))));
+    Ok(())
+}
+
+/// Fetch every page and write it. With `dry_run`, each page is rolled back after writing.
+pub async fn sync(pool: &Pool<Postgres>, client: &reqwest::Client, config: &SyncConfig, dry_run: bool) -> anyhow::Result<SyncSummary> {
+    let policy = DedupPolicy { action: DedupAction::Update, ..DedupPolicy::on(&["external_source", "external_id"]) };
+    let target = Target::load(pool, "projects", Some(policy), Some(SOURCE)).await?;
+    let mut summary = SyncSummary::default();
+    for number in 1.. {
+        let page = fetch_page(client, &config.api_url, number).await?;
+        summary.pages = number;
+        summary.fetched += page.projects.len();
+        write_page(pool, &target, number, &page.projects, dry_run, &mut summary).await?;
+        if !has_more(&page, number, config.max_pages) {
+            break;
+        }
+    }
+    Ok(summary)
+}
+
+pub fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .user_agent(concat!("partner_tools/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_default()
+}
+
+/// Run the sync every `config.interval` in the background, auditing each run
+pub fn schedule(pool: Pool<Postgres>, config: SyncConfig) {
+    let Some(interval) = config.interval else { return };
+    log::info!("Syncing DemocracyLab projects every {} minute(s)", interval.as_secs() / 60);
+    actix_web::rt::spawn(async move {
+        let client = client();
+        loop {
+            actix_web::rt::time::sleep(interval).await;
+            match sync(&pool, &client, &config, false).await {
+                Ok(summary) => {
+                    log::info!("{}", summary.message(false));
+                    let changes = json!({ "summary": summary.changes(), "via": "schedule" });
+                    crate::audit::write(Some(&pool), None, "import.democracylab.sync", "projects", &changes, 200).await;
+                }
+                Err(e) => {
+                    log::error!("DemocracyLab sync failed: {e:#}");
+                    let changes = json!({ "error": format!("{e:#}"), "via": "schedule" });
+                    crate::audit::write(Some(&pool), None, "import.democracylab.sync", "projects", &changes, 502).await;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_ONE: &str = r#"{
+        "projects": [{
+            "project_id": 17,
+            "project_name": "Open Ballot",
+            "project_description": "",
+            "project_short_description": "Sample ballots for every address",
+            "project_url": "https://openballot.example",
+            "project_location": "Seattle, WA",
+            "project_country": "US",
+            "project_issue_area": [{ "name": "elections", "display_name": "Elections" }],
+            "project_technologies": [{ "name": "react", "display_name": "React" }],
+            "project_positions": [
+                { "roleTag": { "name": "front-end-developer", "display_name": "Front End Developer" } },
+                { "roleTag": { "display_name": "Front End Developer" } }
+            ],
+            "project_organization": [{ "display_name": "Civic Tech Seattle" }, { "display_name": "Code for All" }]
+        }],
+        "numPages": 2
+    }"#;
+
+    #[tokio::test]
+    async fn pages_through_a_mock_api_and_maps_richer_fields() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("GET", "/api/projects/")
+            .match_query(mockito::Matcher::UrlEncoded("page".into(), "1".into()))
+            .with_header("content-type", "application/json")
+            .with_body(PAGE_ONE)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/projects/")
+            .match_query(mockito::Matcher::UrlEncoded("page".into(), "2".into()))
+            .with_body(r#"{ "projects": [{ "project_id": "18", "project_name": "Second" }], "numPages": 2 }"#)
+            .create_async()
+            .await;
+
+        let client = reqwest::Client::new();
+        let api_url = format!("{}/api/", server.url());
+        let page = fetch_page(&client, &api_url, 1).await.unwrap();
+        assert!(has_more(&page, 1, 100));
+        assert!(!has_more(&page, 1, 1));
+        first.assert_async().await;
+
+        let row = crate::import::prepare_democracylab_project(1, &page.projects[0]);
+        assert!(row.errors.is_empty());
+        assert_eq!(row.values["external_source"], "democracylab");
+        assert_eq!(row.values["external_id"], "17");
+        assert_eq!(row.values["description"], "Sample ballots for every address");
+        assert_eq!(row.values["skills"], json!(["Front End Developer", "React"]));
+        assert_eq!(row.values["issue_areas"], json!(["Elections"]));
+        assert_eq!(row.values["location"], "Seattle, WA");
+        assert_eq!(row.values["organization"], "Civic Tech Seattle, Code for All");
+
+        let second = fetch_page(&client, &api_url, 2).await.unwrap();
+        assert_eq!(second.projects[0].external_id().as_deref(), Some("18"));
+        assert!(!has_more(&second, 2, 100));
+    }
+
+    #[tokio::test]
+    async fn server_errors_stop_the_sync() {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/projects/").match_query(mockito::Matcher::Any).with_status(503).create_async().await;
+        let err = fetch_page(&reqwest::Client::new(), &server.url(), 1).await.unwrap_err();
+        assert!(err.to_string().contains("503"));
+    }
+}
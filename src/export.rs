@@ -0,0 +1,543 @@
+// src/export.rs
+//! Streaming exports of a table (`GET /api/db/export`) or a read-only query
+//! (`POST /api/db/export/query`) as CSV, JSON Lines or Parquet.
+//!
+//! Rows are encoded as they arrive from sqlx and sent in chunks through a bounded channel,
+//! so memory stays flat however large the result is and a slow client slows the query down
+//! rather than piling up rows on the server. Values go through the same `column_to_json`
+//! mapping the JSON endpoints use.
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Result};
+use arrow_array::builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use futures_util::{Stream, StreamExt};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::postgres::PgRow;
+use sqlx::{Column, Executor, Pool, Postgres, Row, Transaction, TypeInfo};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::sql_sandbox::{self, SandboxConfig};
+use crate::table_rows::{self, Filter, SortSpec, TableRowsRequest};
+use crate::ApiState;
+
+/// Bytes buffered before a chunk is handed to the response
+const CHUNK_BYTES: usize = 64 * 1024;
+/// Rows per Parquet row group
+const PARQUET_BATCH_ROWS: usize = 8192;
+/// Chunks in flight between the query task and the response
+const CHANNEL_CHUNKS: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// Query string for `GET /api/db/export`
+#[derive(Deserialize)]
+pub struct TableExportParams {
+    /// `table` or `schema.table`
+    pub table: String,
+    pub schema: Option<String>,
+    pub connection: Option<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Same filter tree as table-rows, JSON-encoded
+    pub filter: Option<String>,
+    /// Full-text search across the text columns, as in table-rows
+    pub q: Option<String>,
+    /// Comma-separated `field` or `field:desc`
+    pub sort: Option<String>,
+    /// Also export soft-deleted rows
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Deserialize)]
+pub struct QueryExportRequest {
+    pub query: String,
+    pub connection: Option<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Output column: name and Postgres type name (lowercase, as in `column_to_json`)
+#[derive(Debug, Clone)]
+pub struct ExportColumn {
+    pub name: String,
+    pub type_name: String,
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn csv_line<I: IntoIterator<Item = String>>(fields: I) -> String {
+    let mut line = fields.into_iter().collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// Arrow builder for one Parquet column; types without a natural Arrow match are written as text
+enum ColumnBuilder {
+    Int(Int64Builder),
+    Float(Float64Builder),
+    Bool(BooleanBuilder),
+    Text(StringBuilder),
+}
+
+impl ColumnBuilder {
+    fn for_type(type_name: &str) -> Self {
+        match type_name {
+            "int2" | "int4" | "int8" | "smallint" | "integer" | "bigint" => ColumnBuilder::Int(Int64Builder::new()),
+            "float4" | "float8" | "real" | "double precision" => ColumnBuilder::Float(Float64Builder::new()),
+            "bool" | "boolean" => ColumnBuilder::Bool(BooleanBuilder::new()),
+            _ => ColumnBuilder::Text(StringBuilder::new()),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnBuilder::Int(_) => DataType::Int64,
+            ColumnBuilder::Float(_) => DataType::Float64,
+            ColumnBuilder::Bool(_) => DataType::Boolean,
+            ColumnBuilder::Text(_) => DataType::Utf8,
+        }
+    }
+
+    fn append(&mut self, value: &Value) {
+        match self {
+            ColumnBuilder::Int(b) => b.append_option(value.as_i64()),
+            ColumnBuilder::Float(b) => b.append_option(value.as_f64()),
+            ColumnBuilder::Bool(b) => b.append_option(value.as_bool()),
+            ColumnBuilder::Text(b) => match value {
+                Value::Null => b.append_null(),
+                Value::String(s) => b.append_value(s),
+                other => b.append_value(other.to_string()),
+            },
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Int(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float(b) => Arc::new(b.finish()),
+            ColumnBuilder::Bool(b) => Arc::new(b.finish()),
+            ColumnBuilder::Text(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+struct ParquetSink {
+    writer: ArrowWriter<Vec<u8>>,
+    schema: Arc<Schema>,
+    builders: Vec<ColumnBuilder>,
+    buffered: usize,
+}
+
+impl ParquetSink {
+    fn new(columns: &[ExportColumn]) -> anyhow::Result<Self> {
+        let builders: Vec<ColumnBuilder> = columns.iter().map(|c| ColumnBuilder::for_type(&c.type_name)).collect();
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .zip(&builders)
+                .map(|(c, b)| Field::new(&c.name, b.data_type(), true))
+                .collect::<Vec<_>>(),
+        ));
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(PARQUET_BATCH_ROWS)
+            .build();
+        let writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(props))?;
+        Ok(Self { writer, schema, builders, buffered: 0 })
+    }
+
+    /// Write buffered rows out as one row group
+    fn flush_batch(&mut self) -> anyhow::Result<()> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+        let arrays: Vec<ArrayRef> = self.builders.iter_mut().map(ColumnBuilder::finish).collect();
+        self.writer.write(&RecordBatch::try_new(self.schema.clone(), arrays)?)?;
+        self.writer.flush()?;
+        self.buffered = 0;
+        Ok(())
+    }
+}
+
+/// Turns rows into bytes for one export format
+pub struct Encoder {
+    format: ExportFormat,
+    columns: Vec<ExportColumn>,
+    buf: Vec<u8>,
+    parquet: Option<ParquetSink>,
+}
+
+impl Encoder {
+    pub fn new(format: ExportFormat, columns: Vec<ExportColumn>) -> anyhow::Result<Self> {
+        let mut buf = Vec::new();
+        let parquet = match format {
+            ExportFormat::Parquet => Some(ParquetSink::new(&columns)?),
+            ExportFormat::Csv => {
+                buf.extend(csv_line(columns.iter().map(|c| csv_field(&json!(c.name)))).into_bytes());
+                None
+            }
+            ExportFormat::Jsonl => None,
+        };
+        Ok(Self { format, columns, buf, parquet })
+    }
+
+    /// Encode one row, given its values in column order
+    pub fn push(&mut self, values: &[Value]) -> anyhow::Result<()> {
+        match self.format {
+            ExportFormat::Csv => self.buf.extend(csv_line(values.iter().map(csv_field)).into_bytes()),
+            ExportFormat::Jsonl => {
+                // Written by hand so keys keep the column order
+                self.buf.push(b'{');
+                for (i, (column, value)) in self.columns.iter().zip(values).enumerate() {
+                    if i > 0 {
+                        self.buf.push(b',');
+                    }
+                    serde_json::to_writer(&mut self.buf, &column.name)?;
+                    self.buf.push(b':');
+                    serde_json::to_writer(&mut self.buf, value)?;
+                }
+                self.buf.extend_from_slice(b"}\n");
+            }
+            ExportFormat::Parquet => {
+                let sink = self.parquet.as_mut().expect("parquet sink");
+                for (builder, value) in sink.builders.iter_mut().zip(values) {
+                    builder.append(value);
+                }
+                sink.buffered += 1;
+                if sink.buffered >= PARQUET_BATCH_ROWS {
+                    sink.flush_batch()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encoded bytes ready to send, once enough have built up
+    pub fn take_chunk(&mut self) -> Option<Bytes> {
+        let buf = match &mut self.parquet {
+            Some(sink) => sink.writer.inner_mut(),
+            None => &mut self.buf,
+        };
+        (buf.len() >= CHUNK_BYTES).then(|| Bytes::from(std::mem::take(buf)))
+    }
+
+    /// Everything still buffered, including the Parquet footer
+    pub fn finish(mut self) -> anyhow::Result<Bytes> {
+        match self.parquet.take() {
+            Some(mut sink) => {
+                sink.flush_batch()?;
+                Ok(Bytes::from(sink.writer.into_inner()?))
+            }
+            None => Ok(Bytes::from(self.buf)),
+        }
+    }
+}
+
+fn export_columns<'a>(columns: impl Iterator<Item = &'a sqlx::postgres::PgColumn>) -> Vec<ExportColumn> {
+    columns
+        .map(|c| ExportColumn {
+            name: c.name().to_string(),
+            type_name: c.type_info().name().to_lowercase(),
+        })
+        .collect()
+}
+
+fn row_values(row: &PgRow) -> Vec<Value> {
+    (0..row.len()).map(|i| crate::column_to_json(row, i)).collect()
+}
+
+type Chunk = std::result::Result<Bytes, std::io::Error>;
+
+/// Drain `rows` through `encoder` into `tx`. Stops early if the client goes away.
+async fn pump<S>(mut rows: S, mut encoder: Encoder, tx: &mpsc::Sender<Chunk>, label: &str)
+where
+    S: Stream<Item = std::result::Result<PgRow, sqlx::Error>> + Unpin,
+{
+    let mut count = 0usize;
+    while let Some(row) = rows.next().await {
+        let pushed = row
+            .map_err(anyhow::Error::from)
+            .and_then(|row| encoder.push(&row_values(&row)));
+        if let Err(e) = pushed {
+            log::error!("Export of {label} failed after {count} rows: {e}");
+            // Cut the response off so the client doesn't mistake a partial file for a complete one
+            let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+            return;
+        }
+        count += 1;
+        if let Some(chunk) = encoder.take_chunk() {
+            if tx.send(Ok(chunk)).await.is_err() {
+                log::info!("Export of {label} cancelled by the client after {count} rows");
+                return;
+            }
+        }
+    }
+    match encoder.finish() {
+        Ok(rest) => {
+            let _ = tx.send(Ok(rest)).await;
+            log::info!("Exported {count} rows from {label}");
+        }
+        Err(e) => {
+            log::error!("Export of {label} failed while finishing: {e}");
+            let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+        }
+    }
+}
+
+fn streaming_response(format: ExportFormat, file_stem: &str, rx: mpsc::Receiver<Chunk>) -> HttpResponse {
+    let body = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+    let file_stem: String = file_stem
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{file_stem}.{}\"", format.extension()),
+        ))
+        .streaming(body)
+}
+
+fn bad_request(error: impl Into<String>) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "error": error.into() }))
+}
+
+async fn resolve_pool(data: &ApiState, connection: Option<&str>) -> std::result::Result<Pool<Postgres>, HttpResponse> {
+    match connection {
+        Some(name) => data.connections.get(name).await.map_err(|e| {
+            HttpResponse::build(e.status_code()).json(json!({ "error": e.to_string() }))
+        }),
+        None => data.db.clone().ok_or_else(|| {
+            HttpResponse::ServiceUnavailable().json(json!({ "error": "Database not available" }))
+        }),
+    }
+}
+
+/// `field,other:desc` into sort specs
+fn parse_sort(sort: &str) -> Vec<SortSpec> {
+    sort.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.rsplit_once(':') {
+            Some((field, dir)) => SortSpec { field: field.to_string(), dir: Some(dir.to_string()) },
+            None => SortSpec { field: s.to_string(), dir: None },
+        })
+        .collect()
+}
+
+// GET /api/db/export?table=...&format=csv|jsonl|parquet
+pub async fn export_table(
+    data: web::Data<Arc<ApiState>>,
+    params: web::Query<TableExportParams>,
+) -> Result<HttpResponse> {
+    let params = params.into_inner();
+    let pool = match resolve_pool(&data, params.connection.as_deref()).await {
+        Ok(pool) => pool,
+        Err(response) => return Ok(response),
+    };
+
+    let filter: Option<Filter> = match params.filter.as_deref().map(serde_json::from_str).transpose() {
+        Ok(filter) => filter,
+        Err(e) => return Ok(bad_request(format!("Invalid filter: {e}"))),
+    };
+    let req = TableRowsRequest {
+        table: params.table.clone(),
+        schema: params.schema.clone(),
+        connection: params.connection.clone(),
+        page: None,
+        size: None,
+        sort_field: None,
+        sort_dir: None,
+        sort: params.sort.as_deref().map(parse_sort).unwrap_or_default(),
+        filter,
+        q: params.q.clone(),
+        cursor: None,
+        count: None,
+        include_deleted: params.include_deleted,
+    };
+
+    let table = match req.table_ref() {
+        Ok(table) => table,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    let columns = match table_rows::table_columns(&pool, &table).await {
+        Ok(columns) if columns.is_empty() => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "error": format!("Table '{table}' not found")
+            })));
+        }
+        Ok(columns) => columns,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to read columns of {table}: {e}")
+        }))),
+    };
+    let sql = match table_rows::sort_keys(&req, &columns).and_then(|keys| {
+        let qb = table_rows::filtered("SELECT *", &table, &req, &columns)?;
+        Ok(format!("{}{}", qb.sql(), table_rows::order_by(&keys)))
+    }) {
+        Ok(sql) => sql,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    let described = match (&pool).describe(&sql).await {
+        Ok(described) => described,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to prepare export of {table}: {e}")
+        }))),
+    };
+    let encoder = match Encoder::new(params.format, export_columns(described.columns().iter())) {
+        Ok(encoder) => encoder,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({ "error": e.to_string() }))),
+    };
+
+    let (tx, rx) = mpsc::channel(CHANNEL_CHUNKS);
+    actix_web::rt::spawn(async move {
+        // Rebuilt here so the bound values live as long as the streaming query
+        let Ok(mut qb) = table_rows::filtered("SELECT *", &table, &req, &columns) else { return };
+        let keys = table_rows::sort_keys(&req, &columns).unwrap_or_default();
+        qb.push(table_rows::order_by(&keys));
+        pump(qb.build().fetch(&pool), encoder, &tx, &table.to_string()).await;
+    });
+
+    Ok(streaming_response(params.format, &params.table, rx))
+}
+
+// POST /api/db/export/query
+pub async fn export_query(
+    data: web::Data<Arc<ApiState>>,
+    req: web::Json<QueryExportRequest>,
+) -> Result<HttpResponse> {
+    let req = req.into_inner();
+    let statement = match sql_sandbox::validate(&req.query) {
+        Ok(statement) => statement.to_string(),
+        Err(e) => return Ok(bad_request(e.to_string())),
+    };
+    let pool = match resolve_pool(&data, req.connection.as_deref()).await {
+        Ok(pool) => pool,
+        Err(response) => return Ok(response),
+    };
+
+    let config = SandboxConfig::for_export(req.connection.as_deref());
+    let mut tx: Transaction<'static, Postgres> = match sql_sandbox::begin(&pool, &config).await {
+        Ok(tx) => tx,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({ "error": e.to_string() }))),
+    };
+    // Describing first turns syntax and permission errors into a 400 instead of a cut-off download
+    let columns = match (&mut *tx).describe(&statement).await {
+        Ok(described) => export_columns(described.columns().iter()),
+        Err(e) => {
+            let e = sql_sandbox::SandboxError::from(e);
+            return Ok(if e.is_client_error() {
+                bad_request(e.to_string())
+            } else {
+                HttpResponse::InternalServerError().json(json!({ "error": e.to_string() }))
+            });
+        }
+    };
+    let encoder = match Encoder::new(req.format, columns) {
+        Ok(encoder) => encoder,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({ "error": e.to_string() }))),
+    };
+
+    let (chunks, rx) = mpsc::channel(CHANNEL_CHUNKS);
+    actix_web::rt::spawn(async move {
+        pump(sqlx::query(&statement).fetch(&mut *tx), encoder, &chunks, "query").await;
+        tx.rollback().await.ok();
+    });
+
+    Ok(streaming_response(req.format, "query", rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<ExportColumn> {
+        [("id", "int4"), ("name", "text"), ("score", "float8")]
+            .into_iter()
+            .map(|(name, type_name)| ExportColumn { name: name.into(), type_name: type_name.into() })
+            .collect()
+    }
+
+    fn encode(format: ExportFormat) -> Bytes {
+        let mut encoder = Encoder::new(format, columns()).unwrap();
+        encoder.push(&[json!(1), json!("Smith, \"Jo\""), json!(2.5)]).unwrap();
+        encoder.push(&[json!(2), Value::Null, Value::Null]).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn encodes_csv_and_json_lines() {
+        assert_eq!(
+            encode(ExportFormat::Csv),
+            "id,name,score\r\n1,\"Smith, \"\"Jo\"\"\",2.5\r\n2,,\r\n"
+        );
+        assert_eq!(
+            encode(ExportFormat::Jsonl),
+            "{\"id\":1,\"name\":\"Smith, \\\"Jo\\\"\",\"score\":2.5}\n{\"id\":2,\"name\":null,\"score\":null}\n"
+        );
+    }
+
+    #[test]
+    fn encodes_typed_parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let reader = SerializedFileReader::new(encode(ExportFormat::Parquet)).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        let schema = metadata.file_metadata().schema_descr();
+        assert_eq!(schema.column(0).physical_type(), parquet::basic::Type::INT64);
+        assert_eq!(schema.column(1).physical_type(), parquet::basic::Type::BYTE_ARRAY);
+        assert_eq!(schema.column(2).physical_type(), parquet::basic::Type::DOUBLE);
+    }
+
+    #[test]
+    fn parses_sort_lists() {
+        let sort = parse_sort("year:desc, country");
+        assert_eq!((sort[0].field.as_str(), sort[0].dir.as_deref()), ("year", Some("desc")));
+        assert_eq!((sort[1].field.as_str(), sort[1].dir.as_deref()), ("country", None));
+    }
+}
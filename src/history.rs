@@ -0,0 +1,226 @@
+// src/history.rs
+//! Field-level change history for CRM records, kept in the SuiteCRM-style `{entity}_audit` tables.
+//!
+//! `crm::update_record` writes one row per changed field in the same transaction as the update,
+//! and `GET /api/{entity}/{id}/history` reads them back. Unlike `audit_log`, which records every
+//! request, these rows only exist for values that actually changed.
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use std::borrow::Cow;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::crm::{db_error_response, db_pool, not_found, CrmTable};
+use crate::ApiState;
+
+/// One field whose value changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field_name: Cow<'static, str>,
+    pub data_type: &'static str,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Scalar JSON as the text stored in the history table
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Changes to the `(column, data_type)` pairs that were written, comparing the row before and after
+pub fn changes(columns: &[(&'static str, &'static str)], before: &Value, after: &Value) -> Vec<FieldChange> {
+    columns
+        .iter()
+        .filter_map(|&(field_name, data_type)| {
+            let old = before.get(field_name).unwrap_or(&Value::Null);
+            let new = after.get(field_name).unwrap_or(&Value::Null);
+            (old != new).then(|| FieldChange { field_name: field_name.into(), data_type, before: as_text(old), after: as_text(new) })
+        })
+        .collect()
+}
+
+/// History type name for an information_schema `data_type`, matching `crm::Field::data_type`.
+/// JSON, arrays and any other type without a short text form are kept as "text".
+pub fn data_type(information_schema_type: &str) -> &'static str {
+    match information_schema_type {
+        "character varying" | "character" => "varchar",
+        "uuid" => "id",
+        "numeric" | "double precision" | "real" => "decimal",
+        "integer" | "bigint" | "smallint" => "int",
+        "boolean" => "bool",
+        "date" => "date",
+        t if t.starts_with("timestamp") => "datetime",
+        _ => "text",
+    }
+}
+
+/// Longest value the `*_value_string` columns hold
+const STRING_COLUMN_LENGTH: usize = 255;
+
+type BeforeAfter = (Option<String>, Option<String>);
+
+/// `(before, after)` for the `*_value_string` and `*_value_text` columns. Text values, and any
+/// value too long for VARCHAR(255), go in the text columns.
+fn value_columns(change: &FieldChange) -> (BeforeAfter, BeforeAfter) {
+    let fits = |v: &Option<String>| v.as_ref().is_none_or(|v| v.chars().count() <= STRING_COLUMN_LENGTH);
+    if change.data_type == "text" || !fits(&change.before) || !fits(&change.after) {
+        ((None, None), (change.before.clone(), change.after.clone()))
+    } else {
+        ((change.before.clone(), change.after.clone()), (None, None))
+    }
+}
+
+/// Insert `changes` into `{table}_audit`; `created_by` is the editor's `users.id`
+pub async fn write(
+    conn: &mut PgConnection,
+    table: &str,
+    parent_id: Uuid,
+    created_by: Option<&str>,
+    changes: &[FieldChange],
+) -> Result<(), sqlx::Error> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        "INSERT INTO {table}_audit (parent_id, created_by, field_name, data_type, \
+         before_value_string, after_value_string, before_value_text, after_value_text) "
+    ));
+    qb.push_values(changes, |mut row, change| {
+        let (strings, texts) = value_columns(change);
+        row.push_bind(parent_id)
+            .push_bind(created_by)
+            .push_bind(change.field_name.as_ref())
+            .push_bind(change.data_type)
+            .push_bind(strings.0)
+            .push_bind(strings.1)
+            .push_bind(texts.0)
+            .push_bind(texts.1);
+    });
+    qb.build().execute(&mut *conn).await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// Only changes to this field
+    pub field: Option<String>,
+    pub page: Option<i64>,
+    pub size: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct HistoryEntry {
+    pub id: Uuid,
+    pub date_created: DateTime<Utc>,
+    pub created_by: Option<String>,
+    pub created_by_email: Option<String>,
+    pub field_name: String,
+    pub data_type: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+// GET /api/{entity}/{id}/history
+pub async fn record_history<T: CrmTable>(
+    data: web::Data<Arc<ApiState>>,
+    path: web::Path<Uuid>,
+    query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let page = query.page.unwrap_or(1).max(1);
+    let size = query.size.unwrap_or(50).clamp(1, 500);
+
+    match sqlx::query_scalar::<_, bool>(&format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = $1)", T::TABLE))
+        .bind(id)
+        .fetch_one(db)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Ok(not_found::<T>(id)),
+        Err(e) => return Ok(db_error_response(e, &format!("fetch {}", T::LABEL.to_lowercase()))),
+    }
+
+    let total: i64 = match sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM {}_audit WHERE parent_id = $1 AND ($2::text IS NULL OR field_name = $2)",
+        T::TABLE
+    ))
+    .bind(id)
+    .bind(&query.field)
+    .fetch_one(db)
+    .await
+    {
+        Ok(total) => total,
+        Err(e) => return Ok(db_error_response(e, "count history")),
+    };
+
+    let sql = format!(
+        r#"
+        SELECT a.id, a.date_created, a.created_by, u.email AS created_by_email, a.field_name, a.data_type,
+               COALESCE(a.before_value_text, a.before_value_string) AS before,
+               COALESCE(a.after_value_text, a.after_value_string) AS after
+        FROM {}_audit a
+        LEFT JOIN users u ON u.id::text = a.created_by
+        WHERE a.parent_id = $1 AND ($2::text IS NULL OR a.field_name = $2)
+        ORDER BY a.date_created DESC, a.field_name
+        LIMIT $3 OFFSET $4
+        "#,
+        T::TABLE
+    );
+    match sqlx::query_as::<_, HistoryEntry>(&sql)
+        .bind(id)
+        .bind(&query.field)
+        .bind(size)
+        .bind((page - 1) * size)
+        .fetch_all(db)
+        .await
+    {
+        Ok(entries) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "data": entries,
+            "total": total,
+            "page": page,
+            "size": size,
+            "last_page": ((total + size - 1) / size).max(1),
+        }))),
+        Err(e) => Ok(db_error_response(e, "fetch history")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_written_fields_that_changed_are_recorded() {
+        let before = json!({ "name": "Solar", "status": "draft", "description": "Old", "budget": "5.00" });
+        let after = json!({ "name": "Solar", "status": "active", "description": null, "budget": "9.00" });
+        let columns = [("name", "varchar"), ("status", "varchar"), ("description", "text")];
+        assert_eq!(changes(&columns, &before, &after), vec![
+            FieldChange { field_name: "status".into(), data_type: "varchar", before: Some("draft".into()), after: Some("active".into()) },
+            FieldChange { field_name: "description".into(), data_type: "text", before: Some("Old".into()), after: None },
+        ]);
+    }
+
+    #[test]
+    fn long_and_json_values_go_to_the_text_columns() {
+        assert_eq!(data_type("jsonb"), "text");
+        assert_eq!(data_type("ARRAY"), "text");
+        assert_eq!(data_type("character varying"), "varchar");
+
+        let change = |data_type, after: String| FieldChange { field_name: "name".into(), data_type, before: None, after: Some(after) };
+        assert_eq!(value_columns(&change("varchar", "Solar".into())), ((None, Some("Solar".into())), (None, None)));
+        let long = "x".repeat(300);
+        assert_eq!(value_columns(&change("varchar", long.clone())), ((None, None), (None, Some(long))));
+    }
+}
@@ -1,18 +1,51 @@
 // src/import.rs
-use calamine::{Reader, Xlsx, open_workbook, Data};
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use serde_json::{json, Map, Value};
+use sqlx::{Connection, PgConnection, Pool, Postgres, QueryBuilder};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use actix_session::Session;
 use actix_web::{web, HttpResponse, Result};
 use uuid::Uuid;
-use chrono::Utc;
+
+use crate::crm::{EntityInput, ProjectInput};
+use crate::dedup::{self, DedupAction, DedupPolicy};
+use crate::schema::{quote_ident, TableRef};
+use crate::spreadsheet::{RowError, TargetColumn};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImportRequest {
-    pub file_path: String,
+    /// A file under the server's working directory; send this or `upload_id`
+    #[serde(default)]
+    pub file_path: Option<String>,
+    /// A file sent to `POST /api/import/uploads`
+    #[serde(default)]
+    pub upload_id: Option<Uuid>,
     pub sheet_name: Option<String>,
     pub table_name: String,
     pub column_mappings: Option<HashMap<String, String>>,
+    /// Validate and report per row without committing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Overrides the import's default duplicate check
+    #[serde(default)]
+    pub dedup: Option<DedupPolicy>,
+}
+
+impl ImportRequest {
+    /// The file to read
+    pub fn source(&self) -> std::result::Result<PathBuf, String> {
+        crate::uploads::resolve(self.file_path.as_deref(), self.upload_id)
+    }
+
+    /// The file as named in messages
+    pub fn source_name(&self) -> String {
+        match (&self.file_path, self.upload_id) {
+            (Some(path), _) => format!("'{path}'"),
+            (None, Some(id)) => format!("upload {id}"),
+            (None, None) => "the file".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -22,11 +55,42 @@ pub struct ImportResponse {
     pub records_processed: Option<usize>,
     pub records_inserted: Option<usize>,
     pub records_skipped: Option<usize>,
+    /// Existing records updated or merged into by the dedup policy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub records_updated: Option<usize>,
     pub duplicate_check_columns: Option<String>,
     pub errors: Vec<String>,
+    pub dry_run: bool,
+    /// Per-row report, returned for dry runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<Vec<RowReport>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// What an import did with a row, or would do on a dry run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowOutcome {
+    Insert,
+    /// Written onto an existing record by a dedup policy's update or merge
+    Update,
+    Skip,
+    Reject,
+}
+
+/// One row of an import report; `row` counts from 1
+#[derive(Debug, Serialize)]
+pub struct RowReport {
+    pub row: usize,
+    pub outcome: RowOutcome,
+    /// Column values as they are written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub values: Option<Value>,
+    /// Why the row was skipped or rejected, or what was changed to make it fit
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ProjectRecord {
     pub fiscal_year: Option<String>,
     pub project_number: Option<String>,
@@ -38,6 +102,7 @@ pub struct ProjectRecord {
     pub project_name: Option<String>,
     pub committed: Option<f64>,
     pub naics_sector: Option<String>,
+    pub naics_code: Option<String>,
     pub project_description: Option<String>,
     pub project_profile_url: Option<String>,
 }
@@ -49,6 +114,10 @@ pub struct DataImportRequest {
     pub table_name: String,
     pub source: String,
     pub file_source: String,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub dedup: Option<DedupPolicy>,
 }
 
 #[derive(Debug, Serialize)]
@@ -57,25 +126,124 @@ pub struct DataImportResponse {
     pub message: String,
     pub imported_count: Option<usize>,
     pub skipped_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_count: Option<usize>,
     pub duplicate_check_columns: Option<String>,
     pub errors: Vec<String>,
+    pub dry_run: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<Vec<RowReport>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct DemocracyLabProject {
+    /// DemocracyLab's id for the project, a number in its API
+    #[serde(rename = "project_id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
     #[serde(rename = "project_name")]
     pub name: String,
     #[serde(rename = "project_description")]
     pub description: Option<String>,
+    #[serde(rename = "project_short_description", default, skip_serializing_if = "Option::is_none")]
+    pub short_description: Option<String>,
     #[serde(rename = "project_url")]
     pub url: Option<String>,
+    #[serde(rename = "project_location", default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(rename = "project_country", default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(rename = "project_issue_area", default, skip_serializing_if = "Vec::is_empty")]
+    pub issue_areas: Vec<DemocracyLabTag>,
+    #[serde(rename = "project_technologies", default, skip_serializing_if = "Vec::is_empty")]
+    pub technologies: Vec<DemocracyLabTag>,
+    #[serde(rename = "project_positions", default, skip_serializing_if = "Vec::is_empty")]
+    pub positions: Vec<DemocracyLabPosition>,
+    #[serde(rename = "project_organization", default, skip_serializing_if = "Vec::is_empty")]
+    pub organizations: Vec<DemocracyLabTag>,
+}
+
+/// An issue area, technology, role or organization as DemocracyLab tags them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DemocracyLabTag {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+impl DemocracyLabTag {
+    fn label(&self) -> Option<&str> {
+        self.display_name.as_deref().or(self.name.as_deref()).map(str::trim).filter(|l| !l.is_empty())
+    }
+}
+
+/// An open volunteer position; its role is the skill it asks for
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DemocracyLabPosition {
+    #[serde(rename = "roleTag", default)]
+    pub role: Option<DemocracyLabTag>,
+}
+
+impl DemocracyLabProject {
+    /// The id as text, as stored in `external_id`
+    pub fn external_id(&self) -> Option<String> {
+        match self.id.as_ref()? {
+            Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Roles of the open positions, then technologies, without repeats
+    fn skills(&self) -> Vec<&str> {
+        let mut skills: Vec<&str> = Vec::new();
+        let roles = self.positions.iter().filter_map(|p| p.role.as_ref()?.label());
+        for skill in roles.chain(self.technologies.iter().filter_map(DemocracyLabTag::label)) {
+            if !skills.contains(&skill) {
+                skills.push(skill);
+            }
+        }
+        skills
+    }
 }
 
 /// Import Excel data into the projects table
 pub async fn import_excel_data(
     pool: web::Data<std::sync::Arc<crate::ApiState>>,
+    session: Session,
     req: web::Json<ImportRequest>,
 ) -> Result<HttpResponse> {
+    let response = import_excel_file(&pool, &req).await?;
+    let changes = serde_json::json!({ "file_path": req.file_path, "upload_id": req.upload_id, "sheet_name": req.sheet_name });
+    // Dry runs write nothing, so they aren't audited
+    if !req.dry_run {
+        crate::audit::record(&pool, &session, "import.excel", &req.table_name, changes, &response).await;
+    }
+    Ok(response)
+}
+
+/// An `ImportResponse` for a request that couldn't start
+fn import_failure(status: actix_web::http::StatusCode, message: String, errors: Vec<String>, dry_run: bool) -> HttpResponse {
+    HttpResponse::build(status).json(ImportResponse {
+        success: false,
+        message,
+        records_processed: None,
+        records_inserted: None,
+        records_skipped: None,
+        records_updated: None,
+        duplicate_check_columns: None,
+        errors,
+        dry_run,
+        rows: None,
+    })
+}
+
+async fn import_excel_file(pool: &crate::ApiState, req: &ImportRequest) -> Result<HttpResponse> {
+    // With a mapping this is a generic import into any CRM table
+    if req.column_mappings.is_some() {
+        return Ok(crate::spreadsheet::import(pool, req).await);
+    }
+
     let db = match &pool.db {
         Some(db) => db,
         None => {
@@ -85,64 +253,58 @@ pub async fn import_excel_data(
                 records_processed: Some(0),
                 records_inserted: Some(0),
                 records_skipped: Some(0),
+                records_updated: None,
                 duplicate_check_columns: None,
                 errors: vec!["Database connection not available".to_string()],
+                dry_run: req.dry_run,
+                rows: None,
             }));
         }
     };
-    
-    let mut errors = Vec::new();
-    
-    // Read Excel file
-    let records = match read_excel_file(&req.file_path, req.sheet_name.as_deref()) {
-        Ok(data) => data,
-        Err(e) => {
-            return Ok(HttpResponse::BadRequest().json(ImportResponse {
-                success: false,
-                message: format!("Failed to read Excel file at '{}': {}", req.file_path, e),
-                records_processed: None,
-                records_inserted: None,
-                records_skipped: None,
-                duplicate_check_columns: None,
-                errors: vec![format!("File path: {} - {}", req.file_path, e.to_string())],
-            }));
+
+    let plan = match excel_plan(db, req).await {
+        Ok(plan) => plan,
+        Err(PlanError::Invalid(message, errors)) => {
+            return Ok(import_failure(actix_web::http::StatusCode::BAD_REQUEST, message, errors, req.dry_run));
         }
+        Err(PlanError::Database(e)) => return Ok(crate::crm::db_error_response(e, "import projects")),
     };
 
     // Process and insert records
-    let mut inserted_count = 0;
-    let mut skipped_count = 0;
-    let total_records = records.len();
-
-    for (index, record) in records.iter().enumerate() {
-        match insert_project_record(db, record).await {
-            Ok(InsertResult::Inserted) => inserted_count += 1,
-            Ok(InsertResult::Skipped) => skipped_count += 1,
-            Err(e) => {
-                errors.push(format!("Row {}: {}", index + 1, e));
-            }
-        }
-    }
+    let total_records = plan.rows.len();
+    let tally = match run_rows(db, &plan.target, &plan.rows, req.dry_run).await {
+        Ok(tally) => tally,
+        Err(e) => return Ok(crate::crm::db_error_response(e, "import projects")),
+    };
+    let (inserted_count, updated_count, skipped_count, errors) = (tally.inserted, tally.updated, tally.skipped, tally.errors.clone());
 
-    let message = if errors.is_empty() {
+    let mut message = if req.dry_run {
+        tally.dry_run_message(total_records, "projects")
+    } else if errors.is_empty() {
         if skipped_count > 0 {
             format!("Successfully imported {inserted_count} records, skipped {skipped_count} duplicates")
         } else {
             format!("Successfully imported {inserted_count} records")
         }
     } else {
-        format!("Imported {} of {} records with {} errors, skipped {} duplicates", 
+        format!("Imported {} of {} records with {} errors, skipped {} duplicates",
                 inserted_count, total_records, errors.len(), skipped_count)
     };
+    if updated_count > 0 && !req.dry_run {
+        message.push_str(&format!(", updated {updated_count} existing"));
+    }
 
     Ok(HttpResponse::Ok().json(ImportResponse {
-        success: errors.is_empty() || inserted_count > 0,
+        success: errors.is_empty() || inserted_count + updated_count > 0,
         message,
         records_processed: Some(total_records),
         records_inserted: Some(inserted_count),
         records_skipped: Some(skipped_count),
-        duplicate_check_columns: Some("Name + Region + Department".to_string()),
+        records_updated: Some(updated_count),
+        duplicate_check_columns: plan.target.policy.as_ref().map(DedupPolicy::describe),
         errors,
+        dry_run: req.dry_run,
+        rows: tally.report(req.dry_run),
     }))
 }
 
@@ -150,19 +312,16 @@ pub async fn import_excel_data(
 pub async fn preview_excel_data(
     req: web::Json<ImportRequest>,
 ) -> Result<HttpResponse> {
-    println!("Preview request - file_path: {}, sheet_name: {:?}", req.file_path, req.sheet_name);
-    let records = match read_excel_file(&req.file_path, req.sheet_name.as_deref()) {
+    println!("Preview request - source: {}, sheet_name: {:?}", req.source_name(), req.sheet_name);
+    let records = match req.source().and_then(|path| read_excel_file(&path, req.sheet_name.as_deref())) {
         Ok(data) => data,
         Err(e) => {
-            return Ok(HttpResponse::BadRequest().json(ImportResponse {
-                success: false,
-                message: format!("Failed to read Excel file at '{}': {}", req.file_path, e),
-                records_processed: None,
-                records_inserted: None,
-                records_skipped: None,
-                duplicate_check_columns: None,
-                errors: vec![format!("File path: {} - {}", req.file_path, e.to_string())],
-            }));
+            return Ok(import_failure(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                format!("Failed to read Excel file {}: {}", req.source_name(), e),
+                vec![format!("{} - {}", req.source_name(), e)],
+                req.dry_run,
+            ));
         }
     };
 
@@ -181,56 +340,52 @@ pub async fn preview_excel_data(
 pub async fn get_excel_sheets(
     req: web::Json<serde_json::Value>,
 ) -> Result<HttpResponse> {
-    let file_path = match req.get("file_path").and_then(|v| v.as_str()) {
-        Some(path) => {
-            println!("Sheets request - file_path: {path}");
-            path
-        },
-        None => {
+    let file_path = req.get("file_path").and_then(|v| v.as_str());
+    let upload_id = match req.get("upload_id").and_then(|v| v.as_str()).map(Uuid::parse_str).transpose() {
+        Ok(id) => id,
+        Err(_) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "success": false,
+                "message": "upload_id must be a UUID"
+            })));
+        }
+    };
+    println!("Sheets request - file_path: {file_path:?}, upload_id: {upload_id:?}");
+    let path = match crate::uploads::resolve(file_path, upload_id) {
+        Ok(path) => path,
+        Err(e) => {
             return Ok(HttpResponse::BadRequest().json(serde_json::json!({
                 "success": false,
-                "message": "file_path is required"
+                "message": e
             })));
         }
     };
 
-    match get_excel_sheet_names(file_path) {
+    match crate::spreadsheet::sheet_names(&path) {
         Ok(sheets) => Ok(HttpResponse::Ok().json(serde_json::json!({
             "success": true,
             "sheets": sheets
         }))),
         Err(e) => Ok(HttpResponse::BadRequest().json(serde_json::json!({
             "success": false,
-            "message": format!("Failed to read Excel file at '{}': {}", file_path, e)
+            "message": format!("Failed to read Excel file: {e}")
         })))
     }
 }
 
-fn read_excel_file(file_path: &str, sheet_name: Option<&str>) -> Result<Vec<ProjectRecord>, Box<dyn std::error::Error>> {
-    let mut workbook: Xlsx<_> = open_workbook(file_path)
-        .map_err(|e| format!("File not found at: {file_path} - {e}"))?;
-    
-    let sheet_name = match sheet_name {
-        Some(name) => name.to_string(),
-        None => workbook.sheet_names().first().unwrap_or(&"Sheet1".to_string()).clone(),
-    };
-
-    let range = workbook.worksheet_range(&sheet_name)
-        .map_err(|e| format!("Error reading sheet: {e}"))?;
+fn read_excel_file(path: &Path, sheet_name: Option<&str>) -> std::result::Result<Vec<ProjectRecord>, String> {
+    let sheet = crate::spreadsheet::read_sheet(path, sheet_name)?;
 
     let mut records = Vec::new();
-    let mut headers = HashMap::new();
-    
-    // Get headers from first row
-    if let Some(first_row) = range.rows().next() {
-        for (col_idx, cell) in first_row.iter().enumerate() {
-            let header = cell.to_string().to_lowercase().trim().to_string();
-            headers.insert(col_idx, header);
-        }
-    }
+    let headers: HashMap<usize, String> = sheet
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(col_idx, header)| (col_idx, header.to_lowercase().trim().to_string()))
+        .collect();
 
-    // Process data rows (skip header row)
-    for row in range.rows().skip(1) {
+    // Process data rows
+    for row in &sheet.rows {
         let mut record = ProjectRecord {
             fiscal_year: None,
             project_number: None,
@@ -242,20 +397,14 @@ fn read_excel_file(file_path: &str, sheet_name: Option<&str>) -> Result<Vec<Proj
             project_name: None,
             committed: None,
             naics_sector: None,
+            naics_code: None,
             project_description: None,
             project_profile_url: None,
         };
 
         for (col_idx, cell) in row.iter().enumerate() {
             if let Some(header) = headers.get(&col_idx) {
-                let value = match cell {
-                    Data::Empty => None,
-                    Data::String(s) => if s.trim().is_empty() { None } else { Some(s.trim().to_string()) },
-                    Data::Float(f) => Some(f.to_string()),
-                    Data::Int(i) => Some(i.to_string()),
-                    Data::Bool(b) => Some(b.to_string()),
-                    _ => Some(cell.to_string()),
-                };
+                let value = if cell.is_blank() { None } else { Some(cell.text()) };
 
                 match header.as_str() {
                     "fiscal year" => record.fiscal_year = value,
@@ -270,6 +419,7 @@ fn read_excel_file(file_path: &str, sheet_name: Option<&str>) -> Result<Vec<Proj
                         record.committed = value.and_then(|v| v.parse::<f64>().ok());
                     }
                     "naics sector" => record.naics_sector = value,
+                    "naics code" => record.naics_code = value,
                     "project description" => record.project_description = value,
                     "project profile url" => record.project_profile_url = value,
                     _ => {} // Ignore unknown columns
@@ -286,88 +436,380 @@ fn read_excel_file(file_path: &str, sheet_name: Option<&str>) -> Result<Vec<Proj
     Ok(records)
 }
 
-fn get_excel_sheet_names(file_path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let workbook: Xlsx<_> = open_workbook(file_path)
-        .map_err(|e| format!("File not found at: {file_path} - {e}"))?;
-    Ok(workbook.sheet_names().clone())
+#[derive(Debug)]
+pub(crate) enum InsertResult {
+    /// Written, with the values by column and anything changed to make the row fit
+    Inserted(Value, Vec<String>),
+    /// Written onto the existing record it duplicates
+    Updated(Value, Vec<String>),
+    /// Matches an existing record on the duplicate-check columns
+    Skipped(String),
+    /// Failed validation, so nothing was written
+    Rejected(Vec<RowError>),
+}
+
+/// Counts and per-row reports for one import run
+#[derive(Debug, Default)]
+pub(crate) struct ImportTally {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// "Row N: reason; reason" for each rejected row
+    pub errors: Vec<String>,
+    /// Each reason a row was rejected, with the column it concerns when known
+    pub row_errors: Vec<RowError>,
+    pub rows: Vec<RowReport>,
+}
+
+impl ImportTally {
+    pub(crate) fn add(&mut self, row: usize, result: Result<InsertResult, sqlx::Error>) {
+        let (outcome, values, reasons) = match result {
+            Ok(InsertResult::Inserted(values, notes)) => {
+                self.inserted += 1;
+                (RowOutcome::Insert, Some(values), notes)
+            }
+            Ok(InsertResult::Updated(values, notes)) => {
+                self.updated += 1;
+                (RowOutcome::Update, Some(values), notes)
+            }
+            Ok(InsertResult::Skipped(reason)) => {
+                self.skipped += 1;
+                (RowOutcome::Skip, None, vec![reason])
+            }
+            Ok(InsertResult::Rejected(errors)) => {
+                let reasons = errors.iter().map(|e| e.message.clone()).collect();
+                self.row_errors.extend(errors);
+                (RowOutcome::Reject, None, reasons)
+            }
+            Err(e) => {
+                let message = crate::spreadsheet::db_message(&e);
+                self.row_errors.push(RowError { row, column: None, message: message.clone() });
+                (RowOutcome::Reject, None, vec![message])
+            }
+        };
+        if outcome == RowOutcome::Reject {
+            let error_msg = format!("Row {row}: {}", reasons.join("; "));
+            println!("Import error: {error_msg}");
+            self.errors.push(error_msg);
+        }
+        self.rows.push(RowReport { row, outcome, values, reasons });
+    }
+
+    fn dry_run_message(&self, total: usize, noun: &str) -> String {
+        format!(
+            "Dry run: would import {} of {total} {noun}, update {}, skip {} duplicates and reject {}",
+            self.inserted,
+            self.updated,
+            self.skipped,
+            self.errors.len()
+        )
+    }
+
+    /// Row reports are only returned for dry runs, where they are the point of the request
+    fn report(self, dry_run: bool) -> Option<Vec<RowReport>> {
+        dry_run.then_some(self.rows)
+    }
+}
+
+/// A source row turned into the column values an import writes
+#[derive(Debug, Default)]
+pub(crate) struct PreparedRow {
+    /// Row number used in reports
+    pub row: usize,
+    /// Column → value; values are cast to the column's type on insert
+    pub values: Map<String, Value>,
+    /// What was changed to make the row fit
+    pub notes: Vec<String>,
+    /// Why the row can't be written; such rows are rejected without touching the database
+    pub errors: Vec<RowError>,
+}
+
+impl PreparedRow {
+    fn new(row: usize, values: Value) -> Self {
+        let values = match values {
+            Value::Object(values) => values,
+            _ => Map::new(),
+        };
+        PreparedRow { row, values, ..Default::default() }
+    }
+
+    /// Reject the row with `messages`, which concern no particular column
+    fn reject(&mut self, messages: Vec<String>) {
+        let row = self.row;
+        self.errors.extend(messages.into_iter().map(|message| RowError { row, column: None, message }));
+    }
+
+    /// The values as the record a dedup policy compares
+    fn dedup_keys(&self) -> Value {
+        Value::Object(self.values.clone())
+    }
+}
+
+/// Where an import writes and how it treats duplicates
+pub(crate) struct Target {
+    pub table: String,
+    pub columns: Vec<TargetColumn>,
+    pub policy: Option<DedupPolicy>,
+    /// Recorded as `created_by` and `modified_user_id` on tables that have them
+    pub source: Option<&'static str>,
+}
+
+impl Target {
+    pub(crate) async fn load(
+        db: &Pool<Postgres>,
+        table: &str,
+        policy: Option<DedupPolicy>,
+        source: Option<&'static str>,
+    ) -> Result<Self, sqlx::Error> {
+        let columns = crate::spreadsheet::target_columns(db, &TableRef { schema: None, name: table.to_string() }).await?;
+        Ok(Target { table: table.to_string(), columns, policy, source })
+    }
+
+    fn column(&self, name: &str) -> Option<&TargetColumn> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+/// What an import will write, prepared before anything is written
+pub(crate) struct Plan {
+    pub target: Target,
+    pub rows: Vec<PreparedRow>,
 }
 
+/// Why an import can't be planned
 #[derive(Debug)]
-enum InsertResult {
-    Inserted,
-    Skipped,
-}
-
-async fn insert_project_record(
-    pool: &Pool<Postgres>,
-    record: &ProjectRecord,
-) -> Result<InsertResult, sqlx::Error> {
-    // Check for existing record based on name, region, and department
-    let existing_count = sqlx::query_scalar::<_, i64>(
-        r#"
-        SELECT COUNT(*) FROM projects 
-        WHERE name = $1 
-        AND (description LIKE '%Region: ' || $2 || '%' OR $2 IS NULL)
-        AND (description LIKE '%Department: ' || $3 || '%' OR $3 IS NULL)
-        "#
-    )
-    .bind(&record.project_name)
-    .bind(&record.region)
-    .bind(&record.department)
-    .fetch_one(pool)
-    .await?;
-
-    if existing_count > 0 {
-        // Record already exists, skip insertion
-        println!("Skipping duplicate project: {} (Region: {:?}, Department: {:?})", 
-                 record.project_name.as_deref().unwrap_or("Unknown"),
-                 record.region,
-                 record.department);
-        return Ok(InsertResult::Skipped);
-    }
-
-    let id = Uuid::new_v4();
-    let now = Utc::now();
-    
-    // Create a description combining multiple fields
-    let mut description_parts = Vec::new();
-    
-    if let Some(desc) = &record.project_description {
-        description_parts.push(desc.clone());
+pub(crate) enum PlanError {
+    /// The request itself is wrong: an unreadable file, a bad mapping or dedup policy
+    Invalid(String, Vec<String>),
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for PlanError {
+    fn from(e: sqlx::Error) -> Self {
+        PlanError::Database(e)
     }
-    
-    if let Some(dept) = &record.department {
-        description_parts.push(format!("Department: {dept}"));
+}
+
+/// Value as the text Postgres casts into the column, or NULL
+fn cast_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
     }
-    
-    if let Some(region) = &record.region {
-        description_parts.push(format!("Region: {region}"));
+}
+
+/// Insert `rows` with one multi-row INSERT. Columns a row has no value for are NULL for it.
+async fn insert_rows(conn: &mut PgConnection, target: &Target, rows: &[&PreparedRow]) -> Result<(), sqlx::Error> {
+    let mut columns: Vec<&TargetColumn> = Vec::new();
+    for name in rows.iter().flat_map(|row| row.values.keys()) {
+        if let Some(column) = target.column(name) {
+            if !columns.iter().any(|c| c.name == column.name) {
+                columns.push(column);
+            }
+        }
     }
-    
-    if let Some(country) = &record.country {
-        description_parts.push(format!("Country: {country}"));
+    let stamped: Vec<&str> = match target.source {
+        Some(_) => ["created_by", "modified_user_id"].into_iter().filter(|c| target.column(c).is_some()).collect(),
+        None => Vec::new(),
+    };
+
+    let mut qb = QueryBuilder::<Postgres>::new(format!("INSERT INTO {} (", quote_ident(&target.table)));
+    let mut names = qb.separated(", ");
+    for name in columns.iter().map(|c| c.name.as_str()).chain(stamped.iter().copied()) {
+        names.push(quote_ident(name));
     }
-    
-    if let Some(framework) = &record.framework {
-        description_parts.push(format!("Framework: {framework}"));
+    qb.push(") ");
+    qb.push_values(rows, |mut b, row| {
+        for column in &columns {
+            b.push("CAST(")
+                .push_bind_unseparated(row.values.get(&column.name).and_then(cast_text))
+                .push_unseparated(format!(" AS {})", column.cast_type()));
+        }
+        for _ in &stamped {
+            b.push_bind(target.source);
+        }
+    });
+    qb.build().execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Write one row: reject it, deal with it as a duplicate, or insert it
+pub(crate) async fn write_row(conn: &mut PgConnection, target: &Target, row: &PreparedRow) -> Result<InsertResult, sqlx::Error> {
+    if !row.errors.is_empty() {
+        return Ok(InsertResult::Rejected(row.errors.clone()));
     }
-    
-    if let Some(naics) = &record.naics_sector {
-        description_parts.push(format!("NAICS Sector: {naics}"));
+    if let Some(policy) = &target.policy {
+        if let Some(result) = handle_duplicate(conn, target, policy, row).await? {
+            return Ok(result);
+        }
     }
-    
-    if let Some(url) = &record.project_profile_url {
-        description_parts.push(format!("Profile URL: {url}"));
+    insert_rows(conn, target, &[row]).await?;
+    Ok(InsertResult::Inserted(Value::Object(row.values.clone()), row.notes.clone()))
+}
+
+/// Write a batch of rows, inserting the new ones with a single multi-row INSERT. Rows that
+/// duplicate an earlier row of the batch are found in memory, since that row isn't written yet.
+/// If the INSERT fails, its rows are retried one at a time so only the bad ones are rejected.
+pub(crate) async fn write_batch(
+    conn: &mut PgConnection,
+    target: &Target,
+    rows: &[PreparedRow],
+) -> Result<Vec<Result<InsertResult, sqlx::Error>>, sqlx::Error> {
+    let mut results: Vec<Option<Result<InsertResult, sqlx::Error>>> = rows.iter().map(|_| None).collect();
+    let mut pending: Vec<usize> = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        if !row.errors.is_empty() {
+            results[i] = Some(Ok(InsertResult::Rejected(row.errors.clone())));
+            continue;
+        }
+        if let Some(policy) = &target.policy {
+            let keys = row.dedup_keys();
+            if let Some(&earlier) = pending.iter().find(|&&j| policy.matches(&rows[j].dedup_keys(), &keys)) {
+                if policy.action == DedupAction::Skip {
+                    results[i] = Some(Ok(InsertResult::Skipped(format!("Duplicate of row {} of this import", rows[earlier].row))));
+                    continue;
+                }
+                // Updating or merging needs the earlier row in the table
+                flush_inserts(conn, target, rows, &mut pending, &mut results).await?;
+            }
+            let mut row_tx = conn.begin().await?;
+            match handle_duplicate(&mut row_tx, target, policy, row).await {
+                Ok(None) => row_tx.commit().await?,
+                Ok(Some(result)) => {
+                    row_tx.commit().await?;
+                    results[i] = Some(Ok(result));
+                    continue;
+                }
+                Err(e) => {
+                    row_tx.rollback().await?;
+                    results[i] = Some(Err(e));
+                    continue;
+                }
+            }
+        }
+        pending.push(i);
     }
-    
-    let description = if description_parts.is_empty() {
-        None
+    flush_inserts(conn, target, rows, &mut pending, &mut results).await?;
+    Ok(results.into_iter().map(|result| result.expect("every row of the batch has a result")).collect())
+}
+
+async fn flush_inserts(
+    conn: &mut PgConnection,
+    target: &Target,
+    rows: &[PreparedRow],
+    pending: &mut Vec<usize>,
+    results: &mut [Option<Result<InsertResult, sqlx::Error>>],
+) -> Result<(), sqlx::Error> {
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let inserted = |row: &PreparedRow| Ok(InsertResult::Inserted(Value::Object(row.values.clone()), row.notes.clone()));
+    let batch: Vec<&PreparedRow> = pending.iter().map(|&i| &rows[i]).collect();
+    let mut batch_tx = conn.begin().await?;
+    if insert_rows(&mut batch_tx, target, &batch).await.is_ok() {
+        batch_tx.commit().await?;
+        for &i in pending.iter() {
+            results[i] = Some(inserted(&rows[i]));
+        }
+    } else {
+        batch_tx.rollback().await?;
+        for &i in pending.iter() {
+            let mut row_tx = conn.begin().await?;
+            results[i] = Some(match insert_rows(&mut row_tx, target, &[&rows[i]]).await {
+                Ok(()) => row_tx.commit().await.and(inserted(&rows[i])),
+                Err(e) => row_tx.rollback().await.and(Err(e)),
+            });
+        }
+    }
+    pending.clear();
+    Ok(())
+}
+
+/// Write `rows` in one transaction, each row in its own savepoint so a failed row doesn't
+/// abort the rest. A dry run rolls everything back, so duplicates within the file and
+/// constraint errors are reported exactly as a real import would hit them.
+pub(crate) async fn run_rows(pool: &Pool<Postgres>, target: &Target, rows: &[PreparedRow], dry_run: bool) -> Result<ImportTally, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut tally = ImportTally::default();
+    for row in rows {
+        let mut row_tx = tx.begin().await?;
+        let result = match write_row(&mut row_tx, target, row).await {
+            Ok(written @ (InsertResult::Inserted(..) | InsertResult::Updated(..))) => row_tx.commit().await.map(|_| written),
+            other => row_tx.rollback().await.and(other),
+        };
+        tally.add(row.row, result);
+    }
+    if dry_run {
+        tx.rollback().await?;
     } else {
-        Some(description_parts.join("
+        tx.commit().await?;
+    }
+    Ok(tally)
+}
+
+/// Column limit errors for a row about to be inserted, checked as the CRM API checks them
+fn limit_errors<T: crate::crm::EntityInput>(input: T) -> Vec<String> {
+    crate::crm::validate_fields(&input.into_fields(), T::REQUIRED, true)
+}
 
-"))
+/// The request's dedup policy, or `default`, checked against the keys the import can compare
+fn dedup_policy(requested: &Option<DedupPolicy>, default: &[&str], allowed: &[&str]) -> Result<DedupPolicy, PlanError> {
+    let policy = requested.clone().unwrap_or_else(|| DedupPolicy::on(default));
+    policy.validate(allowed).map_err(|errors| PlanError::Invalid("Invalid dedup policy".to_string(), errors))?;
+    Ok(policy)
+}
+
+/// Look for a record the row duplicates and deal with it as `policy` says; `None` means the row
+/// is new and should be inserted
+pub(crate) async fn handle_duplicate(
+    conn: &mut PgConnection,
+    target: &Target,
+    policy: &DedupPolicy,
+    row: &PreparedRow,
+) -> Result<Option<InsertResult>, sqlx::Error> {
+    let table = &target.table;
+    let Some(found) = dedup::find(conn, table, policy, &row.dedup_keys()).await? else {
+        return Ok(None);
     };
+    let matched = match found.similarity {
+        Some(score) => format!("{} on {} (similarity {score:.2})", found.id, found.keys.join(" + ")),
+        None => format!("{} on {}", found.id, found.keys.join(" + ")),
+    };
+    if policy.action == DedupAction::Skip {
+        println!("Skipping duplicate {table} row: {matched}");
+        return Ok(Some(InsertResult::Skipped(format!("Duplicate of {matched}"))));
+    }
+    let written = dedup::apply(conn, table, policy.action, &found, &row.values, target.source).await?;
+    Ok(Some(if written.is_empty() {
+        InsertResult::Skipped(format!("Duplicate of {matched}; nothing to change"))
+    } else {
+        let verb = if policy.action == DedupAction::Merge { "Merged into" } else { "Updated" };
+        let mut notes = row.notes.clone();
+        notes.push(format!("{verb} {matched}: {}", written.join(", ")));
+        InsertResult::Updated(Value::Object(row.values.clone()), notes)
+    }))
+}
 
+/// Plan an Excel project import, or a generic spreadsheet import when the request maps columns
+pub(crate) async fn excel_plan(db: &Pool<Postgres>, req: &ImportRequest) -> Result<Plan, PlanError> {
+    if req.column_mappings.is_some() {
+        return crate::spreadsheet::plan(db, req).await.map(|(plan, _)| plan);
+    }
+    let records = req.source().and_then(|path| read_excel_file(&path, req.sheet_name.as_deref())).map_err(|e| {
+        PlanError::Invalid(
+            format!("Failed to read Excel file {}: {}", req.source_name(), e),
+            vec![format!("{} - {}", req.source_name(), e)],
+        )
+    })?;
+    let policy = dedup_policy(&req.dedup, &["name", "region", "department"], ProjectInput::COLUMNS)?;
+    let target = Target::load(db, "projects", Some(policy), Some("excel-import")).await?;
+    let rows = records.iter().enumerate().map(|(i, record)| prepare_project_record(i + 1, record)).collect();
+    Ok(Plan { target, rows })
+}
+
+
+fn prepare_project_record(row: usize, record: &ProjectRecord) -> PreparedRow {
     // Set priority based on committed amount
     let priority = match record.committed {
         Some(amount) if amount >= 10_000_000.0 => Some("High".to_string()),
@@ -384,34 +826,110 @@ async fn insert_project_record(
         _ => Some("Active".to_string()), // Default status
     };
 
-    sqlx::query(
-        r#"
-        INSERT INTO projects (
-            id, name, description, status, priority,
-            date_entered, date_modified, created_by, modified_user_id
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-        "#
-    )
-    .bind(id)
-    .bind(&record.project_name)
-    .bind(&description)
-    .bind(&status)
-    .bind(&priority)
-    .bind(now)
-    .bind(now)
-    .bind("excel-import") // Creator identifier
-    .bind("excel-import") // Modifier identifier
-    .execute(pool)
-    .await?;
-
-    Ok(InsertResult::Inserted)
+    let mut prepared = PreparedRow::new(row, json!({
+        "name": record.project_name,
+        "description": record.project_description,
+        "status": status,
+        "priority": priority,
+    }));
+    prepared.reject(limit_errors(ProjectInput {
+        name: Some(record.project_name.clone()),
+        status: Some(status.clone()),
+        priority: Some(priority.clone()),
+        ..Default::default()
+    }));
+    add_project_attributes(&mut prepared, record);
+    prepared
+}
+
+/// Write the structured attributes of `record` (fiscal year, region, committed amount and the
+/// rest) into the row's values, deriving the NAICS code from the sector when the source has none
+fn add_project_attributes(prepared: &mut PreparedRow, record: &ProjectRecord) {
+    let fiscal_year = record.fiscal_year.as_deref().and_then(|value| {
+        let year = crate::project_attributes::fiscal_year(value);
+        if year.is_none() {
+            prepared.notes.push(format!("fiscal_year '{value}' is not a year; left empty"));
+        }
+        year
+    });
+    let naics_code = record
+        .naics_code
+        .clone()
+        .or_else(|| record.naics_sector.as_deref().and_then(crate::project_attributes::naics_code).map(str::to_string));
+    let attributes = json!({
+        "fiscal_year": fiscal_year,
+        "project_number": record.project_number,
+        "project_type": record.project_type,
+        "department": record.department,
+        "region": record.region,
+        "country": record.country,
+        "framework": record.framework,
+        "naics_sector": record.naics_sector,
+        "naics_code": naics_code,
+        "profile_url": record.project_profile_url,
+        "committed": record.committed,
+    });
+    // Checked on their own, so without the name the caller has already checked
+    let input = ProjectInput {
+        project_number: Some(record.project_number.clone()),
+        project_type: Some(record.project_type.clone()),
+        department: Some(record.department.clone()),
+        region: Some(record.region.clone()),
+        country: Some(record.country.clone()),
+        framework: Some(record.framework.clone()),
+        naics_sector: Some(record.naics_sector.clone()),
+        naics_code: Some(naics_code),
+        committed: Some(record.committed.map(crate::crm::Decimal::from)),
+        ..Default::default()
+    };
+    prepared.reject(crate::crm::validate_fields(&input.into_fields(), &[], false));
+    if let Value::Object(attributes) = attributes {
+        prepared.values.extend(attributes);
+    }
 }
 
 /// Import JSON data directly into specified table
 pub async fn import_data(
     pool: web::Data<std::sync::Arc<crate::ApiState>>,
+    session: Session,
     req: web::Json<DataImportRequest>,
 ) -> Result<HttpResponse> {
+    let response = import_rows(&pool, &req).await?;
+    let changes = serde_json::json!({
+        "rows": req.data.len(),
+        "headers": req.headers,
+        "source": req.source,
+        "file_source": req.file_source,
+    });
+    if !req.dry_run {
+        crate::audit::record(&pool, &session, "import.data", &req.table_name, changes, &response).await;
+    }
+    Ok(response)
+}
+
+/// Plan a JSON import into accounts or projects
+pub(crate) async fn data_plan(db: &Pool<Postgres>, req: &DataImportRequest) -> Result<Plan, PlanError> {
+    match req.table_name.as_str() {
+        "accounts" => {
+            let policy = dedup_policy(&req.dedup, &["name", "industry"], <crate::crm::AccountInput as crate::crm::EntityInput>::COLUMNS)?;
+            let target = Target::load(db, "accounts", Some(policy), Some("csv-import")).await?;
+            let rows = req.data.iter().enumerate().map(|(i, record)| prepare_account_record(i + 1, record)).collect();
+            Ok(Plan { target, rows })
+        }
+        "projects" => {
+            let policy = dedup_policy(&req.dedup, &["name"], ProjectInput::COLUMNS)?;
+            let target = Target::load(db, "projects", Some(policy), Some("json-import")).await?;
+            let rows = req.data.iter().enumerate().map(|(i, record)| prepare_project_from_json(i + 1, record)).collect();
+            Ok(Plan { target, rows })
+        }
+        other => Err(PlanError::Invalid(
+            format!("Failed to import data into {other}"),
+            vec![format!("Unsupported table: {other}; expected accounts or projects")],
+        )),
+    }
+}
+
+async fn import_rows(pool: &crate::ApiState, req: &DataImportRequest) -> Result<HttpResponse> {
     let db = match &pool.db {
         Some(db) => db,
         None => {
@@ -421,287 +939,210 @@ pub async fn import_data(
                 records_processed: Some(0),
                 records_inserted: Some(0),
                 records_skipped: Some(0),
+                records_updated: None,
                 duplicate_check_columns: None,
                 errors: vec!["Database connection not available".to_string()],
+                dry_run: req.dry_run,
+                rows: None,
             }));
         }
     };
-    
-    let mut errors = Vec::new();
-    let mut imported_count = 0;
-    let mut skipped_count = 0;
-    let mut actual_duplicate_check_columns = None;
-    
-    println!("Data import request - table: {}, source: {}, records: {}", 
+
+    println!("Data import request - table: {}, source: {}, records: {}",
         req.table_name, req.source, req.data.len());
-    
-    match req.table_name.as_str() {
-        "accounts" => {
-            for (index, record) in req.data.iter().enumerate() {
-                match import_account_record(db, record).await {
-                    Ok((InsertResult::Inserted, fields_used)) => {
-                        imported_count += 1;
-                        if actual_duplicate_check_columns.is_none() {
-                            actual_duplicate_check_columns = Some(fields_used);
-                        }
-                    },
-                    Ok((InsertResult::Skipped, fields_used)) => {
-                        skipped_count += 1;
-                        if actual_duplicate_check_columns.is_none() {
-                            actual_duplicate_check_columns = Some(fields_used);
-                        }
-                    },
-                    Err(e) => {
-                        let error_msg = format!("Row {}: {}", index + 1, e);
-                        println!("Import error: {error_msg}");
-                        errors.push(error_msg);
-                    }
-                }
-            }
-        }
-        "projects" => {
-            for (index, record) in req.data.iter().enumerate() {
-                match import_project_record_from_json(db, record).await {
-                    Ok((InsertResult::Inserted, fields_used)) => {
-                        imported_count += 1;
-                        if actual_duplicate_check_columns.is_none() {
-                            actual_duplicate_check_columns = Some(fields_used);
-                        }
-                    },
-                    Ok((InsertResult::Skipped, fields_used)) => {
-                        skipped_count += 1;
-                        if actual_duplicate_check_columns.is_none() {
-                            actual_duplicate_check_columns = Some(fields_used);
-                        }
-                    },
-                    Err(e) => {
-                        let error_msg = format!("Row {}: {}", index + 1, e);
-                        println!("Import error: {error_msg}");
-                        errors.push(error_msg);
-                    }
-                }
-            }
-        }
-        _ => {
-            errors.push(format!("Unsupported table: {}", req.table_name));
+
+    let plan = match data_plan(db, req).await {
+        Ok(plan) => plan,
+        Err(PlanError::Invalid(message, errors)) => {
+            return Ok(HttpResponse::BadRequest().json(DataImportResponse {
+                success: false,
+                message,
+                imported_count: None,
+                skipped_count: None,
+                updated_count: None,
+                duplicate_check_columns: None,
+                errors,
+                dry_run: req.dry_run,
+                rows: None,
+            }));
         }
-    }
-    
-    let success = errors.is_empty() || (imported_count > 0 && errors.len() < req.data.len());
-    let message = if success {
+        Err(PlanError::Database(e)) => return Ok(crate::crm::db_error_response(e, &format!("import {}", req.table_name))),
+    };
+    let tally = match run_rows(db, &plan.target, &plan.rows, req.dry_run).await {
+        Ok(tally) => tally,
+        Err(e) => return Ok(crate::crm::db_error_response(e, &format!("import {}", req.table_name))),
+    };
+    let (imported_count, updated_count, skipped_count, errors) = (tally.inserted, tally.updated, tally.skipped, tally.errors.clone());
+
+    let success = errors.is_empty() || (imported_count + updated_count > 0 && errors.len() < req.data.len());
+    let mut message = if req.dry_run {
+        tally.dry_run_message(req.data.len(), &format!("records into {}", req.table_name))
+    } else if success {
         if errors.is_empty() {
             if skipped_count > 0 {
-                format!("Successfully imported {} records into {}, skipped {} duplicates", 
+                format!("Successfully imported {} records into {}, skipped {} duplicates",
                         imported_count, req.table_name, skipped_count)
             } else {
                 format!("Successfully imported {} records into {}", imported_count, req.table_name)
             }
         } else {
-            format!("Imported {} of {} records into {} with {} errors, skipped {} duplicates", 
+            format!("Imported {} of {} records into {} with {} errors, skipped {} duplicates",
                 imported_count, req.data.len(), req.table_name, errors.len(), skipped_count)
         }
     } else {
         format!("Failed to import data into {}", req.table_name)
     };
-    
-    let duplicate_check_columns = actual_duplicate_check_columns.or_else(|| {
-        match req.table_name.as_str() {
-            "accounts" => Some("Name + Industry".to_string()),
-            "projects" => Some("Name + Region + Department".to_string()),
-            _ => None,
-        }
-    });
-    
+    if updated_count > 0 && !req.dry_run {
+        message.push_str(&format!(", updated {updated_count} existing"));
+    }
+
     Ok(HttpResponse::Ok().json(DataImportResponse {
         success,
         message,
         imported_count: Some(imported_count),
         skipped_count: Some(skipped_count),
-        duplicate_check_columns,
+        updated_count: Some(updated_count),
+        duplicate_check_columns: plan.target.policy.as_ref().map(DedupPolicy::describe),
         errors,
+        dry_run: req.dry_run,
+        rows: tally.report(req.dry_run),
     }))
 }
 
-/// Helper function to import a single account record
-/// Returns (InsertResult, fields_used_for_duplicate_check)
-async fn import_account_record(
-    pool: &Pool<Postgres>,
-    record: &HashMap<String, serde_json::Value>,
-) -> Result<(InsertResult, String), Box<dyn std::error::Error>> {
+/// Helper function to prepare a single account record
+fn prepare_account_record(row: usize, record: &HashMap<String, serde_json::Value>) -> PreparedRow {
     // Extract fields from the record
-    let name = record.get("Name")
+    let given_name = record.get("Name")
         .or_else(|| record.get("name"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("Unknown");
-    
+        .and_then(|v| v.as_str());
+    let name = given_name.unwrap_or("Unknown");
+
     let email = record.get("Email")
         .or_else(|| record.get("email"))
         .and_then(|v| v.as_str());
-    
+
     let phone = record.get("Phone")
         .or_else(|| record.get("phone"))
         .and_then(|v| v.as_str());
-    
+
     let website = record.get("Website")
         .or_else(|| record.get("website"))
         .and_then(|v| v.as_str());
-    
+
     let industry = record.get("Industry")
         .or_else(|| record.get("industry"))
         .or_else(|| record.get("Sector"))
         .or_else(|| record.get("sector"))
         .and_then(|v| v.as_str());
-    
-    // Check for existing record based on available fields
-    let existing_count = if industry.is_some() {
-        // Use name + industry if industry is available
-        sqlx::query_scalar::<_, i64>(
-            r#"
-            SELECT COUNT(*) FROM accounts 
-            WHERE name = $1 AND industry = $2
-            "#
-        )
-        .bind(name)
-        .bind(industry)
-        .fetch_one(pool)
-        .await?
-    } else {
-        // Use only name if industry is not available
-        sqlx::query_scalar::<_, i64>(
-            r#"
-            SELECT COUNT(*) FROM accounts 
-            WHERE name = $1
-            "#
-        )
-        .bind(name)
-        .fetch_one(pool)
-        .await?
-    };
-
-    // Determine which fields were used for duplicate checking
-    let duplicate_check_fields = if industry.is_some() {
-        "Name + Industry".to_string()
-    } else {
-        "Name".to_string()
-    };
-
-    if existing_count > 0 {
-        // Record already exists, skip insertion
-        let fields_used = if industry.is_some() {
-            format!("Name + Industry: {name} (Industry: {industry:?})")
-        } else {
-            format!("Name: {name}")
-        };
-        println!("Skipping duplicate account: {fields_used}");
-        return Ok((InsertResult::Skipped, duplicate_check_fields));
-    }
 
-    let id = Uuid::new_v4();
-    let now = Utc::now().naive_utc();
-    
     // Set account type based on available data
     let account_type = if email.is_some() || phone.is_some() {
         Some("Customer")
     } else {
         Some("Prospect")
     };
-    
-    sqlx::query(
-        r#"
-        INSERT INTO accounts (
-            id, name, account_type, industry, phone_office, website,
-            date_entered, date_modified, created_by, modified_user_id
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-        "#
-    )
-    .bind(id)
-    .bind(name)
-    .bind(account_type)
-    .bind(industry)
-    .bind(phone)
-    .bind(website)
-    .bind(now)
-    .bind(now)
-    .bind("csv-import") // Creator identifier
-    .bind("csv-import") // Modifier identifier
-    .execute(pool)
-    .await?;
-
-    Ok((InsertResult::Inserted, duplicate_check_fields))
-}
-
-async fn import_project_record_from_json(
-    pool: &Pool<Postgres>,
-    record: &HashMap<String, serde_json::Value>,
-) -> Result<(InsertResult, String), Box<dyn std::error::Error>> {
+
+    let mut prepared = PreparedRow::new(row, json!({
+        "name": name,
+        "account_type": account_type,
+        "industry": industry,
+        "phone_office": phone,
+        "website": website,
+    }));
+    prepared.reject(limit_errors(crate::crm::AccountInput {
+        name: Some(Some(name.to_string())),
+        account_type: Some(account_type.map(str::to_string)),
+        industry: Some(industry.map(str::to_string)),
+        phone_office: Some(phone.map(str::to_string)),
+        website: Some(website.map(str::to_string)),
+    }));
+    if given_name.is_none() {
+        prepared.notes.push("No name given; imported as 'Unknown'".to_string());
+    }
+    prepared
+}
+
+/// Truncate a project name to fit database constraint (50 characters max), noting that it was
+fn fit_project_name(raw_name: &str, notes: &mut Vec<String>) -> String {
+    if raw_name.chars().count() > 50 {
+        let truncated: String = raw_name.chars().take(47).collect(); // Leave room for "..."
+        notes.push(format!("name truncated to 50 characters (was {})", raw_name.chars().count()));
+        format!("{truncated}...")
+    } else {
+        raw_name.to_string()
+    }
+}
+
+fn prepare_project_from_json(row: usize, record: &HashMap<String, serde_json::Value>) -> PreparedRow {
     // Handle different field name formats for DemocracyLab vs other sources
     let raw_name = record.get("project_name")
         .and_then(|v| v.as_str())
         .or_else(|| record.get("name").and_then(|v| v.as_str()))
         .unwrap_or("Unknown");
-    
-    // Truncate name to fit database constraint (50 characters max)
-    let name = if raw_name.len() > 50 {
-        let truncated = &raw_name[..47]; // Leave room for "..."
-        format!("{truncated}...")
-    } else {
-        raw_name.to_string()
-    };
-    
+
+    let mut notes = Vec::new();
+    let name = fit_project_name(raw_name, &mut notes);
+
     let description = record.get("project_description")
         .and_then(|v| v.as_str())
         .or_else(|| record.get("description").and_then(|v| v.as_str()));
 
-    // Check for existing record based on name
-    let existing_count = sqlx::query_scalar::<_, i64>(
-        r#"
-        SELECT COUNT(*) FROM projects 
-        WHERE name = $1
-        "#
-    )
-    .bind(&name)
-    .fetch_one(pool)
-    .await?;
-
-    if existing_count > 0 {
-        println!("Skipping duplicate project: {name}");
-        return Ok((InsertResult::Skipped, "Name".to_string()));
-    }
-
-    let id = Uuid::new_v4();
-    let now = Utc::now();
-
-    sqlx::query(
-        r#"
-        INSERT INTO projects (
-            id, name, description, status,
-            date_entered, date_modified, created_by, modified_user_id
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        "#
-    )
-    .bind(id)
-    .bind(&name)
-    .bind(description)
-    .bind("Active") // Default status
-    .bind(now)
-    .bind(now)
-    .bind("json-import")
-    .bind("json-import")
-    .execute(pool)
-    .await?;
-
-    Ok((InsertResult::Inserted, "Name".to_string()))
+    let mut prepared = PreparedRow { notes, ..PreparedRow::new(row, json!({ "name": name, "description": description, "status": "Active" })) };
+    // Attributes go by the Excel import's names, as the data import's headers usually come from such a sheet
+    let field = |key: &str| match record.get(key) {
+        Some(Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+    let attributes = ProjectRecord {
+        fiscal_year: field("fiscal_year"),
+        project_number: field("project_number"),
+        project_type: field("project_type"),
+        region: field("region"),
+        country: field("country"),
+        department: field("department"),
+        framework: field("framework"),
+        committed: field("committed").and_then(|v| v.parse().ok()),
+        naics_sector: field("naics_sector"),
+        naics_code: field("naics_code"),
+        project_profile_url: field("project_profile_url").or_else(|| field("profile_url")),
+        ..Default::default()
+    };
+    add_project_attributes(&mut prepared, &attributes);
+    prepared
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DemocracyLabApiResponse {
     pub projects: Vec<DemocracyLabProject>,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub dedup: Option<DedupPolicy>,
 }
 
 pub async fn import_democracylab_projects(
     pool: web::Data<std::sync::Arc<crate::ApiState>>,
+    session: Session,
     req: web::Json<DemocracyLabApiResponse>,
 ) -> Result<HttpResponse> {
+    let response = import_democracylab(&pool, &req).await?;
+    let changes = serde_json::json!({ "rows": req.projects.len() });
+    if !req.dry_run {
+        crate::audit::record(&pool, &session, "import.democracylab", "projects", changes, &response).await;
+    }
+    Ok(response)
+}
+
+/// Plan an import of DemocracyLab projects
+pub(crate) async fn democracylab_plan(db: &Pool<Postgres>, req: &DemocracyLabApiResponse) -> Result<Plan, PlanError> {
+    let policy = dedup_policy(&req.dedup, &["name"], ProjectInput::COLUMNS)?;
+    let target = Target::load(db, "projects", Some(policy), Some("democracylab-import")).await?;
+    let rows = req.projects.iter().enumerate().map(|(i, project)| prepare_democracylab_project(i + 1, project)).collect();
+    Ok(Plan { target, rows })
+}
+
+async fn import_democracylab(pool: &crate::ApiState, req: &DemocracyLabApiResponse) -> Result<HttpResponse> {
     let db = match &pool.db {
         Some(db) => db,
         None => {
@@ -711,28 +1152,33 @@ pub async fn import_democracylab_projects(
                 records_processed: Some(0),
                 records_inserted: Some(0),
                 records_skipped: Some(0),
+                records_updated: None,
                 duplicate_check_columns: None,
                 errors: vec!["Database connection not available".to_string()],
+                dry_run: req.dry_run,
+                rows: None,
             }));
         }
     };
-    
-    let mut errors = Vec::new();
-    let mut inserted_count = 0;
-    let mut skipped_count = 0;
-    let total_records = req.projects.len();
-
-    for (index, project) in req.projects.iter().enumerate() {
-        match insert_democracylab_project(db, project).await {
-            Ok(InsertResult::Inserted) => inserted_count += 1,
-            Ok(InsertResult::Skipped) => skipped_count += 1,
-            Err(e) => {
-                errors.push(format!("Row {}: {}", index + 1, e));
-            }
+
+    let plan = match democracylab_plan(db, req).await {
+        Ok(plan) => plan,
+        Err(PlanError::Invalid(message, errors)) => {
+            return Ok(import_failure(actix_web::http::StatusCode::BAD_REQUEST, message, errors, req.dry_run));
         }
-    }
+        Err(PlanError::Database(e)) => return Ok(crate::crm::db_error_response(e, "import projects")),
+    };
+
+    let total_records = plan.rows.len();
+    let tally = match run_rows(db, &plan.target, &plan.rows, req.dry_run).await {
+        Ok(tally) => tally,
+        Err(e) => return Ok(crate::crm::db_error_response(e, "import projects")),
+    };
+    let (inserted_count, updated_count, skipped_count, errors) = (tally.inserted, tally.updated, tally.skipped, tally.errors.clone());
 
-    let message = if errors.is_empty() {
+    let mut message = if req.dry_run {
+        tally.dry_run_message(total_records, "projects")
+    } else if errors.is_empty() {
         if skipped_count > 0 {
             format!("Successfully imported {inserted_count} projects, skipped {skipped_count} duplicates")
         } else {
@@ -742,74 +1188,100 @@ pub async fn import_democracylab_projects(
         format!("Imported {} of {} projects with {} errors, skipped {} duplicates",
                 inserted_count, total_records, errors.len(), skipped_count)
     };
+    if updated_count > 0 && !req.dry_run {
+        message.push_str(&format!(", updated {updated_count} existing"));
+    }
 
     Ok(HttpResponse::Ok().json(ImportResponse {
-        success: errors.is_empty() || inserted_count > 0,
+        success: errors.is_empty() || inserted_count + updated_count > 0,
         message,
         records_processed: Some(total_records),
         records_inserted: Some(inserted_count),
         records_skipped: Some(skipped_count),
-        duplicate_check_columns: Some("Name".to_string()),
+        records_updated: Some(updated_count),
+        duplicate_check_columns: plan.target.policy.as_ref().map(DedupPolicy::describe),
         errors,
+        dry_run: req.dry_run,
+        rows: tally.report(req.dry_run),
     }))
 }
 
-async fn insert_democracylab_project(
-    pool: &Pool<Postgres>,
-    project: &DemocracyLabProject,
-) -> Result<InsertResult, sqlx::Error> {
-    // Check for existing record based on name
-    let existing_count = sqlx::query_scalar::<_, i64>(
-        r#"
-        SELECT COUNT(*) FROM projects
-        WHERE name = $1
-        "#
-    )
-    .bind(&project.name)
-    .fetch_one(pool)
-    .await?;
-
-    if existing_count > 0 {
-        println!("Skipping duplicate project: {}", &project.name);
-        return Ok(InsertResult::Skipped);
-    }
+pub(crate) fn prepare_democracylab_project(row: usize, project: &DemocracyLabProject) -> PreparedRow {
+    let mut notes = Vec::new();
+    let name = fit_project_name(project.name.trim(), &mut notes);
+    let description = project.description.as_deref().filter(|d| !d.trim().is_empty()).or(project.short_description.as_deref());
+    let organization: Vec<&str> = project.organizations.iter().filter_map(DemocracyLabTag::label).collect();
+    let issue_areas: Vec<&str> = project.issue_areas.iter().filter_map(DemocracyLabTag::label).collect();
+    let skills = project.skills();
+    let external_id = project.external_id();
+    let mut prepared = PreparedRow::new(row, json!({
+        "name": name,
+        "description": description,
+        "status": "Active",
+        "profile_url": project.url,
+        "location": project.location,
+        "country": project.country,
+        "organization": (!organization.is_empty()).then(|| organization.join(", ")),
+        "issue_areas": (!issue_areas.is_empty()).then_some(issue_areas),
+        "skills": (!skills.is_empty()).then_some(skills),
+        "external_source": external_id.as_ref().map(|_| "democracylab"),
+        "external_id": external_id,
+    }));
+    prepared.notes = notes;
+    prepared.reject(limit_errors(ProjectInput {
+        name: Some(Some(name)),
+        country: Some(project.country.clone()),
+        ..Default::default()
+    }));
+    prepared
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let id = Uuid::new_v4();
-    let now = Utc::now();
+    #[test]
+    fn tally_reports_each_row_and_only_returns_them_for_dry_runs() {
+        let mut tally = ImportTally::default();
+        tally.add(1, Ok(InsertResult::Inserted(json!({ "name": "Solar" }), Vec::new())));
+        tally.add(2, Ok(InsertResult::Skipped("Duplicate of an existing project (Name)".to_string())));
+        let mut rejected = PreparedRow::new(3, json!({ "name": "x".repeat(54) }));
+        rejected.reject(limit_errors(crate::crm::ProjectInput {
+            name: Some(Some("x".repeat(54))),
+            ..Default::default()
+        }));
+        tally.add(3, Ok(InsertResult::Rejected(rejected.errors)));
+        assert_eq!((tally.inserted, tally.skipped), (1, 1));
+        assert_eq!(tally.errors, vec!["Row 3: name exceeds 50 characters (got 54)"]);
+        assert_eq!(tally.row_errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(tally.dry_run_message(3, "projects"), "Dry run: would import 1 of 3 projects, update 0, skip 1 duplicates and reject 1");
 
-    let mut description_parts = Vec::new();
-    if let Some(desc) = &project.description {
-        description_parts.push(desc.clone());
+        let rows = tally.report(true).unwrap();
+        assert_eq!(rows.iter().map(|r| r.outcome).collect::<Vec<_>>(), vec![RowOutcome::Insert, RowOutcome::Skip, RowOutcome::Reject]);
+        assert!(ImportTally::default().report(false).is_none());
     }
-    if let Some(url) = &project.url {
-        description_parts.push(format!("Project URL: {url}"));
-    }
-    let description = if description_parts.is_empty() {
-        None
-    } else {
-        Some(description_parts.join("
 
-"))
-    };
+    #[test]
+    fn excel_projects_keep_attributes_in_columns() {
+        let record = ProjectRecord {
+            project_name: Some("Kenya Solar".to_string()),
+            project_description: Some("Mini-grids".to_string()),
+            fiscal_year: Some("FY2021".to_string()),
+            region: Some("Africa".to_string()),
+            naics_sector: Some("Utilities".to_string()),
+            committed: Some(2_500_000.0),
+            ..Default::default()
+        };
+        let prepared = prepare_project_record(1, &record);
+        assert!(prepared.errors.is_empty());
+        assert_eq!(prepared.values["description"], "Mini-grids");
+        assert_eq!(prepared.values["priority"], "Medium");
+        assert_eq!(prepared.values["fiscal_year"], 2021);
+        assert_eq!(prepared.values["region"], "Africa");
+        assert_eq!(prepared.values["naics_code"], "22");
+        assert_eq!(prepared.values["committed"], 2_500_000.0);
 
-    sqlx::query(
-        r#"
-        INSERT INTO projects (
-            id, name, description, status,
-            date_entered, date_modified, created_by, modified_user_id
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        "#
-    )
-    .bind(id)
-    .bind(&project.name)
-    .bind(&description)
-    .bind("Active") // Default status
-    .bind(now)
-    .bind(now)
-    .bind("democracylab-import")
-    .bind("democracylab-import")
-    .execute(pool)
-    .await?;
-
-    Ok(InsertResult::Inserted)
-}
\ No newline at end of file
+        let bad_year = prepare_project_record(2, &ProjectRecord { fiscal_year: Some("TBD".to_string()), ..record });
+        assert_eq!(bad_year.values["fiscal_year"], Value::Null);
+        assert_eq!(bad_year.notes, vec!["fiscal_year 'TBD' is not a year; left empty"]);
+    }
+}
@@ -0,0 +1,373 @@
+// src/import_jobs.rs
+//! Background imports under `/api/import/jobs`.
+//!
+//! A job takes the body of one of the import endpoints, prepares every row up front and then
+//! writes them from a background task in batches. Each batch goes in with one multi-row INSERT
+//! and commits together with the job's progress in `import_jobs`, so a job that fails, or is cut
+//! off by a server restart, can be resumed from the first batch that didn't commit. Dry runs
+//! stay on the import endpoints, which report every row.
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::types::Json;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::crm::{db_error_response, db_pool};
+use crate::import::{ImportTally, Plan, PlanError, PreparedRow, Target};
+use crate::ApiState;
+
+const DEFAULT_BATCH_SIZE: i32 = 500;
+const MAX_BATCH_SIZE: i32 = 5000;
+/// Row errors kept on a job; `rejected` still counts all of them
+const MAX_STORED_ERRORS: i32 = 1000;
+
+/// Which import endpoint's request a job carries
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    /// `POST /api/import/excel`
+    Excel,
+    /// `POST /api/import/spreadsheet`
+    Spreadsheet,
+    /// `POST /api/import/data`
+    Data,
+    /// `POST /api/import/democracylab`
+    Democracylab,
+}
+
+impl JobKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Excel => "excel",
+            JobKind::Spreadsheet => "spreadsheet",
+            JobKind::Data => "data",
+            JobKind::Democracylab => "democracylab",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobRequest {
+    pub kind: JobKind,
+    /// Rows per committed batch
+    pub batch_size: Option<i32>,
+    /// The body the `kind` endpoint takes
+    pub request: Value,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ImportJob {
+    pub id: Uuid,
+    pub kind: String,
+    pub table_name: String,
+    /// running, completed or failed
+    pub status: String,
+    pub batch_size: i32,
+    pub total_rows: i32,
+    /// Rows in committed batches; a resumed job starts after these
+    pub rows_done: i32,
+    pub inserted: i32,
+    pub updated: i32,
+    pub skipped: i32,
+    pub rejected: i32,
+    pub errors: Value,
+    /// Why the job failed
+    pub error: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+const JOB_COLUMNS: &str = "id, kind, table_name, status, batch_size, total_rows, rows_done, inserted, updated, \
+    skipped, rejected, errors, error, created_by, created_at, updated_at, finished_at";
+
+fn not_found(id: Uuid) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "success": false,
+        "message": format!("Import job {id} not found")
+    }))
+}
+
+fn plan_error_response(e: PlanError) -> HttpResponse {
+    match e {
+        PlanError::Invalid(message, errors) => HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "message": message,
+            "errors": errors
+        })),
+        PlanError::Database(e) => db_error_response(e, "prepare import"),
+    }
+}
+
+/// Prepare the rows a job writes, the same way its import endpoint would
+async fn plan(db: &Pool<Postgres>, kind: JobKind, request: &Value) -> std::result::Result<Plan, PlanError> {
+    fn parse<T: DeserializeOwned>(request: &Value) -> std::result::Result<T, PlanError> {
+        serde_json::from_value(request.clone()).map_err(|e| PlanError::Invalid("Invalid import request".to_string(), vec![e.to_string()]))
+    }
+    if request.get("dry_run") == Some(&Value::Bool(true)) {
+        return Err(PlanError::Invalid(
+            "Dry runs don't run as jobs".to_string(),
+            vec![format!("Send the dry run to POST /api/import/{} instead", kind.as_str())],
+        ));
+    }
+    match kind {
+        JobKind::Excel => crate::import::excel_plan(db, &parse(request)?).await,
+        JobKind::Spreadsheet => crate::spreadsheet::plan(db, &parse(request)?).await.map(|(plan, _)| plan),
+        JobKind::Data => crate::import::data_plan(db, &parse(request)?).await,
+        JobKind::Democracylab => crate::import::democracylab_plan(db, &parse(request)?).await,
+    }
+}
+
+/// Write one batch and record it on the job, in one transaction
+async fn commit_batch(db: &Pool<Postgres>, id: Uuid, target: &Target, batch: &[PreparedRow]) -> std::result::Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let results = crate::import::write_batch(&mut tx, target, batch).await?;
+    let mut tally = ImportTally::default();
+    for (row, result) in batch.iter().zip(results) {
+        tally.add(row.row, result);
+    }
+    sqlx::query(
+        r#"
+        UPDATE import_jobs
+        SET rows_done = rows_done + $2, inserted = inserted + $3, updated = updated + $4,
+            skipped = skipped + $5, rejected = rejected + $6,
+            errors = CASE WHEN jsonb_array_length(errors) < $8 THEN errors || $7 ELSE errors END,
+            updated_at = now()
+        WHERE id = $1
+        "#
+    )
+    .bind(id)
+    .bind(batch.len() as i32)
+    .bind(tally.inserted as i32)
+    .bind(tally.updated as i32)
+    .bind(tally.skipped as i32)
+    .bind(tally.errors.len() as i32)
+    .bind(Json(&tally.row_errors))
+    .bind(MAX_STORED_ERRORS)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Write the plan's rows from `start` on, then mark the job completed, or failed at the first
+/// batch that can't commit
+async fn run(db: Pool<Postgres>, id: Uuid, plan: Plan, batch_size: usize, start: usize) {
+    let rows = &plan.rows[start.min(plan.rows.len())..];
+    let mut failure = None;
+    for batch in rows.chunks(batch_size) {
+        if let Err(e) = commit_batch(&db, id, &plan.target, batch).await {
+            log::error!("Import job {id} failed: {e}");
+            failure = Some(e.to_string());
+            break;
+        }
+    }
+    let status = if failure.is_some() { "failed" } else { "completed" };
+    let finished = sqlx::query("UPDATE import_jobs SET status = $2, error = $3, updated_at = now(), finished_at = now() WHERE id = $1")
+        .bind(id)
+        .bind(status)
+        .bind(failure)
+        .execute(&db)
+        .await;
+    if let Err(e) = finished {
+        log::error!("Cannot mark import job {id} {status}: {e}");
+    }
+}
+
+/// Jobs still marked running when the server starts lost their task; mark them failed so they
+/// can be resumed
+pub async fn fail_interrupted(db: &Pool<Postgres>) -> std::result::Result<u64, sqlx::Error> {
+    sqlx::query(
+        "UPDATE import_jobs SET status = 'failed', error = 'Interrupted by a server restart', \
+         updated_at = now(), finished_at = now() WHERE status = 'running'",
+    )
+    .execute(db)
+    .await
+    .map(|done| done.rows_affected())
+}
+
+// POST /api/import/jobs
+pub async fn create_import_job(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: web::Json<JobRequest>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let batch_size = req.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    if !(1..=MAX_BATCH_SIZE).contains(&batch_size) {
+        return Ok(plan_error_response(PlanError::Invalid(
+            "Invalid import job".to_string(),
+            vec![format!("batch_size must be between 1 and {MAX_BATCH_SIZE}")],
+        )));
+    }
+    let plan = match plan(db, req.kind, &req.request).await {
+        Ok(plan) => plan,
+        Err(e) => return Ok(plan_error_response(e)),
+    };
+
+    let (table, total_rows) = (plan.target.table.clone(), plan.rows.len());
+    let created_by = crate::sessions::current_user(&session).map(|user| user.email);
+    let result = sqlx::query_as::<_, ImportJob>(&format!(
+        "INSERT INTO import_jobs (kind, table_name, request, batch_size, total_rows, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {JOB_COLUMNS}"
+    ))
+    .bind(req.kind.as_str())
+    .bind(&table)
+    .bind(&req.request)
+    .bind(batch_size)
+    .bind(total_rows as i32)
+    .bind(created_by)
+    .fetch_one(db)
+    .await;
+
+    let (response, target) = match result {
+        Ok(job) => {
+            let target = format!("import_jobs/{}", job.id);
+            actix_web::rt::spawn(run(db.clone(), job.id, plan, batch_size as usize, 0));
+            let response = HttpResponse::Accepted().json(json!({
+                "success": true,
+                "message": format!("Importing {} rows into {} in batches of {batch_size}", job.total_rows, job.table_name),
+                "data": job
+            }));
+            (response, target)
+        }
+        Err(e) => (db_error_response(e, "create import job"), "import_jobs".to_string()),
+    };
+    let changes = json!({ "kind": req.kind, "table": table, "rows": total_rows, "batch_size": batch_size });
+    crate::audit::record(&data, &session, "import.job.create", &target, changes, &response).await;
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobListQuery {
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// GET /api/import/jobs
+pub async fn list_import_jobs(data: web::Data<Arc<ApiState>>, query: web::Query<JobListQuery>) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    match sqlx::query_as::<_, ImportJob>(&format!(
+        "SELECT {JOB_COLUMNS} FROM import_jobs WHERE ($1::text IS NULL OR status = $1) ORDER BY created_at DESC LIMIT $2"
+    ))
+    .bind(&query.status)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+    {
+        Ok(jobs) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "data": jobs,
+            "total": jobs.len(),
+        }))),
+        Err(e) => Ok(db_error_response(e, "list import jobs")),
+    }
+}
+
+// GET /api/import/jobs/{id}
+pub async fn get_import_job(data: web::Data<Arc<ApiState>>, path: web::Path<Uuid>) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    match sqlx::query_as::<_, ImportJob>(&format!("SELECT {JOB_COLUMNS} FROM import_jobs WHERE id = $1")).bind(id).fetch_optional(db).await {
+        Ok(Some(job)) => Ok(HttpResponse::Ok().json(json!({ "success": true, "data": job }))),
+        Ok(None) => Ok(not_found(id)),
+        Err(e) => Ok(db_error_response(e, "fetch import job")),
+    }
+}
+
+/// What resuming needs from the stored job
+#[derive(sqlx::FromRow)]
+struct StoredJob {
+    kind: String,
+    request: Value,
+    status: String,
+    batch_size: i32,
+    total_rows: i32,
+    rows_done: i32,
+}
+
+async fn resume(db: &Pool<Postgres>, id: Uuid) -> HttpResponse {
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return db_error_response(e, "resume import job"),
+    };
+    let job = match sqlx::query_as::<_, StoredJob>(
+        "SELECT kind, request, status, batch_size, total_rows, rows_done FROM import_jobs WHERE id = $1 FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(job)) => job,
+        Ok(None) => return not_found(id),
+        Err(e) => return db_error_response(e, "fetch import job"),
+    };
+    if job.status != "failed" {
+        return HttpResponse::Conflict().json(json!({
+            "success": false,
+            "message": format!("Only failed jobs can be resumed; this one is {}", job.status)
+        }));
+    }
+    let kind: JobKind = match serde_json::from_value(Value::String(job.kind.clone())) {
+        Ok(kind) => kind,
+        Err(_) => return plan_error_response(PlanError::Invalid(format!("Unknown import job kind '{}'", job.kind), Vec::new())),
+    };
+    let plan = match plan(db, kind, &job.request).await {
+        Ok(plan) => plan,
+        Err(e) => return plan_error_response(e),
+    };
+    // Resuming skips rows by position, which only holds if the source still has the same rows
+    if plan.rows.len() != job.total_rows as usize {
+        return HttpResponse::Conflict().json(json!({
+            "success": false,
+            "message": format!("The source has changed since the job started: it had {} rows and now has {}", job.total_rows, plan.rows.len())
+        }));
+    }
+    let resumed = sqlx::query_as::<_, ImportJob>(&format!(
+        "UPDATE import_jobs SET status = 'running', error = NULL, finished_at = NULL, updated_at = now() \
+         WHERE id = $1 RETURNING {JOB_COLUMNS}"
+    ))
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await;
+    let resumed = match resumed {
+        Ok(resumed) => match tx.commit().await {
+            Ok(()) => resumed,
+            Err(e) => return db_error_response(e, "resume import job"),
+        },
+        Err(e) => return db_error_response(e, "resume import job"),
+    };
+    actix_web::rt::spawn(run(db.clone(), id, plan, job.batch_size as usize, job.rows_done as usize));
+    HttpResponse::Accepted().json(json!({
+        "success": true,
+        "message": format!("Resuming at row {} of {}", job.rows_done + 1, job.total_rows),
+        "data": resumed
+    }))
+}
+
+// POST /api/import/jobs/{id}/resume
+pub async fn resume_import_job(data: web::Data<Arc<ApiState>>, session: Session, path: web::Path<Uuid>) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let response = resume(db, id).await;
+    crate::audit::record(&data, &session, "import.job.resume", &format!("import_jobs/{id}"), json!({}), &response).await;
+    Ok(response)
+}
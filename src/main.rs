@@ -1,18 +1,18 @@
 // src/main.rs
 use actix_cors::Cors;
+use actix_session::Session;
 use actix_web::{web, App, HttpResponse, HttpServer, Result, middleware, middleware::DefaultHeaders, HttpRequest};
 use anyhow::Context;
-use chrono::{Utc, NaiveDate, NaiveDateTime};
-use clap::{Parser, Subcommand};
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres, Row, Column, ValueRef, TypeInfo};
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres, Row};
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use std::process::{Child, Command};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::path::Path;
-use uuid::Uuid;
 use url::Url;
 use notify::{Watcher, RecursiveMode, RecommendedWatcher, Config as NotifyConfig};
 use std::sync::mpsc::channel;
@@ -32,8 +32,31 @@ mod oauth;
 mod prompts;
 mod semantic_search;
 mod api_integration;
+mod migrations;
+mod crm;
+mod relationships;
+mod sessions;
+mod auth;
+mod connections;
+mod sql_sandbox;
+mod table_rows;
+mod export;
+mod pg_json;
+mod schema;
+mod saved_queries;
+mod audit;
+mod history;
+mod purge;
+mod spreadsheet;
+mod dedup;
+mod import_jobs;
+mod uploads;
+mod project_attributes;
+mod democracylab_sync;
 use recommendations::RecommendationRequest;
-use oauth::{OAuthConfig, UserSession, OAuthUrlResponse};
+use oauth::{OAuthConfig, UserSession, OAuthUrlResponse, PendingAuthorization};
+use auth::Permission;
+use pg_json::{column_to_json, row_to_json};
 
 // Configuration structure
 #[derive(Debug, Deserialize, Clone)]
@@ -190,16 +213,83 @@ enum Commands {
     Serve,
     /// Initialize database schema
     InitDb,
+    /// Apply, roll back or inspect versioned schema migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+        /// Which database to migrate
+        #[arg(long, global = true, value_enum, default_value_t = MigrationTarget::Commons)]
+        database: MigrationTarget,
+    },
+    /// Give a user a role ("admin" or "editor"), creating the user and role if needed
+    GrantRole {
+        /// Email the user signs in with
+        email: String,
+        /// Role name
+        role: String,
+    },
+    /// Permanently remove soft-deleted CRM records
+    Purge {
+        /// Only records deleted at least this many days ago (0 for all)
+        #[arg(long, default_value_t = 30)]
+        older_than_days: u32,
+        /// Only this entity table, e.g. "projects"
+        #[arg(long)]
+        entity: Option<String>,
+        /// Count what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move "Label: value" blocks that imports appended to project descriptions into the
+    /// structured project columns
+    BackfillProjectAttributes {
+        /// Count what would be moved without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Fetch projects from the DemocracyLab API and insert or update them by DemocracyLab id
+    SyncDemocracylab {
+        /// Stop after this many pages (default: DEMOCRACYLAB_SYNC_MAX_PAGES, or 100)
+        #[arg(long)]
+        max_pages: Option<u32>,
+        /// Write each page and roll it back, reporting what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Up,
+    /// Roll back the most recently applied migration
+    Down {
+        /// Confirm rolling back a migration whose down script drops tables or columns with data
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List applied and pending migrations
+    Status,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MigrationTarget {
+    /// CRM schema on the main database connection
+    Commons,
+    /// Industry Database (EXIOBASE_* connection)
+    Industry,
 }
 
 // API State
 struct ApiState {
     db: Option<Pool<Postgres>>,
     config: SharedConfig,
+    connections: Arc<connections::ConnectionRegistry>,
+    saved_query_cache: saved_queries::ResultCache,
 }
 
 // Function to start watching .env file for changes
-fn start_env_watcher(config: SharedConfig) -> anyhow::Result<()> {
+fn start_env_watcher(config: SharedConfig, connections: Arc<connections::ConnectionRegistry>) -> anyhow::Result<()> {
     use notify::{Event, EventKind};
     
     let (tx, rx) = channel();
@@ -231,6 +321,8 @@ fn start_env_watcher(config: SharedConfig) -> anyhow::Result<()> {
                                             if let Ok(mut config_guard) = config_clone.lock() {
                                                 *config_guard = new_config;
                                                 log::info!("Configuration reloaded successfully");
+                                                // Rebuild pools whose *_HOST/_USER/... settings changed
+                                                connections.refresh();
                                             } else {
                                                 log::error!("Failed to acquire config lock for reload");
                                             }
@@ -266,16 +358,6 @@ fn start_env_watcher(config: SharedConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
-// Request/Response types for projects
-#[derive(Debug, Serialize, Deserialize)]
-struct CreateProjectRequest {
-    name: String,
-    description: Option<String>,
-    status: Option<String>,
-    estimated_start_date: Option<String>,
-    estimated_end_date: Option<String>,
-}
-
 // Google Cloud project creation request
 #[derive(Debug, Serialize, Deserialize)]
 struct CreateGoogleProjectRequest {
@@ -340,7 +422,10 @@ struct GoogleCloudProjectsResponse {
 
 #[derive(Debug, Serialize)]
 struct TableInfo {
+    /// `table`, or `schema.table` outside the current schema
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<String>,
     row_count: i64,
 }
 
@@ -354,7 +439,10 @@ struct DatabaseResponse {
 
 #[derive(Serialize)]
 struct TableInfoDetailed {
+    /// `table`, or `schema.table` outside the current schema
     name: String,
+    schema: String,
+    table_name: String,
     rows: Option<i64>,
     description: Option<String>,
 }
@@ -370,16 +458,8 @@ struct ConnectionInfo {
 #[derive(Deserialize)]
 struct QueryRequest {
     query: String,
-}
-
-#[derive(Deserialize)]
-struct TableRowsRequest {
-    table: String,
-    connection: Option<String>,
-    page: Option<i64>,
-    size: Option<i64>,
-    sort_field: Option<String>,
-    sort_dir: Option<String>,
+    /// Lower the sandbox row cap for this request
+    max_rows: Option<usize>,
 }
 
 #[derive(Serialize, Clone)]
@@ -726,7 +806,7 @@ async fn get_env_config() -> Result<HttpResponse> {
 }
 
 // Restart server endpoint (for development)
-async fn restart_server() -> Result<HttpResponse> {
+async fn restart_server(data: web::Data<Arc<ApiState>>, session: Session) -> Result<HttpResponse> {
     // In a production environment, you might want to add authentication here
     
     // For development, just exit and let the user restart manually
@@ -736,14 +816,22 @@ async fn restart_server() -> Result<HttpResponse> {
         std::process::exit(0); // Clean exit
     });
     
-    Ok(HttpResponse::Ok().json(json!({
+    let response = HttpResponse::Ok().json(json!({
         "message": "Server shutdown initiated. Please restart manually with 'cargo run serve'",
         "status": "success"
-    })))
+    }));
+    audit::record(&data, &session, "server.restart", "server", json!({}), &response).await;
+    Ok(response)
 }
 
 // Stop local web server on port 8887 (development helper)
-async fn stop_webroot_server() -> Result<HttpResponse> {
+async fn stop_webroot_server(data: web::Data<Arc<ApiState>>, session: Session) -> Result<HttpResponse> {
+    let response = kill_webroot_server()?;
+    audit::record(&data, &session, "server.stop_webroot", "port 8887", json!({}), &response).await;
+    Ok(response)
+}
+
+fn kill_webroot_server() -> Result<HttpResponse> {
     #[cfg(target_family = "unix")]
     {
         let output = Command::new("sh")
@@ -782,7 +870,36 @@ async fn stop_webroot_server() -> Result<HttpResponse> {
 }
 
 // Save environment configuration to .env file
-async fn save_env_config(req: web::Json<SaveEnvConfigRequest>) -> Result<HttpResponse> {
+async fn save_env_config(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: web::Json<SaveEnvConfigRequest>,
+) -> Result<HttpResponse> {
+    // Compare against the live values before they are overwritten; secrets are redacted by key
+    let submitted = [
+        ("GEMINI_API_KEY", &req.gemini_api_key),
+        ("GOOGLE_PROJECT_ID", &req.google_project_id),
+        ("GOOGLE_USER_EMAIL", &req.google_user_email),
+        ("GOOGLE_ORG_ID", &req.google_org_id),
+        ("GOOGLE_BILLING_ID", &req.google_billing_id),
+        ("GOOGLE_SERVICE_KEY", &req.google_service_key),
+    ];
+    let mut before = serde_json::Map::new();
+    let mut after = serde_json::Map::new();
+    for (key, value) in submitted {
+        if let Some(value) = value.as_ref().filter(|v| !v.is_empty()) {
+            before.insert(key.to_string(), json!(std::env::var(key).ok()));
+            after.insert(key.to_string(), json!(value));
+        }
+    }
+    let changes = audit::diff(&before.into(), &after.into());
+
+    let response = write_env_config(&req)?;
+    audit::record(&data, &session, "config.env.save", "docker/.env", changes, &response).await;
+    Ok(response)
+}
+
+fn write_env_config(req: &SaveEnvConfigRequest) -> Result<HttpResponse> {
     use std::fs::OpenOptions;
     use std::io::{BufRead, BufReader, Write};
 
@@ -884,7 +1001,27 @@ async fn save_env_config(req: web::Json<SaveEnvConfigRequest>) -> Result<HttpRes
 }
 
 // Create .env file from .env.example content
-async fn create_env_config(req: web::Json<CreateEnvConfigRequest>) -> Result<HttpResponse> {
+async fn create_env_config(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: web::Json<CreateEnvConfigRequest>,
+) -> Result<HttpResponse> {
+    // Key names only; the template may already hold real values
+    let keys: Vec<&str> = req
+        .content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('=').map(|(key, _)| key.trim()))
+        .collect();
+    let changes = json!({ "created": true, "keys": keys });
+
+    let response = write_new_env_config(&req)?;
+    audit::record(&data, &session, "config.env.create", "docker/.env", changes, &response).await;
+    Ok(response)
+}
+
+fn write_new_env_config(req: &CreateEnvConfigRequest) -> Result<HttpResponse> {
     use std::fs;
 
     // Check if .env file already exists in docker directory
@@ -913,7 +1050,18 @@ async fn create_env_config(req: web::Json<CreateEnvConfigRequest>) -> Result<Htt
 }
 
 // Save CSV file to projects directory
-async fn save_csv_file(req: web::Json<SaveCsvRequest>) -> Result<HttpResponse> {
+async fn save_csv_file(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: web::Json<SaveCsvRequest>,
+) -> Result<HttpResponse> {
+    let changes = json!({ "size": req.content.len(), "lines": req.content.lines().count() });
+    let response = write_csv_file(&req)?;
+    audit::record(&data, &session, "files.csv.save", &format!("projects/{}", req.filename), changes, &response).await;
+    Ok(response)
+}
+
+fn write_csv_file(req: &SaveCsvRequest) -> Result<HttpResponse> {
     use std::fs;
     use std::path::Path;
     
@@ -1036,6 +1184,7 @@ async fn create_google_project(req: web::Json<CreateGoogleProjectRequest>) -> Re
 // Supports Google, GitHub, LinkedIn, Microsoft, and Facebook
 
 async fn oauth_provider_url(
+    session: Session,
     provider: web::Path<String>,
 ) -> Result<HttpResponse> {
     let provider_name = provider.into_inner();
@@ -1064,6 +1213,9 @@ async fn oauth_provider_url(
     
     // Handle demo provider specially
     if provider_name == "demo" {
+        if !demo_login_enabled() {
+            return Ok(demo_login_disabled());
+        }
         return Ok(HttpResponse::Ok().json(json!({
             "auth_url": "/api/auth/demo/login",
             "state": "demo_state"
@@ -1080,20 +1232,17 @@ async fn oauth_provider_url(
         })));
     }
     
-    // Generate OAuth URL (simplified implementation)
+    // Issue a CSRF state (and PKCE verifier) that the callback must present from this same session
     let redirect_uri = oauth_config.get_redirect_uri(&provider_name);
-    let state = uuid::Uuid::new_v4().to_string();
-    let scopes = provider_config.scopes.join(" ");
-    
-    let auth_url = format!(
-        "{}?client_id={}&redirect_uri={}&response_type={}&scope={}&state={}",
-        provider_config.authorization_endpoint,
-        urlencoding::encode(&provider_config.client_id),
-        urlencoding::encode(&redirect_uri),
-        provider_config.response_type,
-        urlencoding::encode(&scopes),
-        state
-    );
+    let (pending, pkce_challenge) = PendingAuthorization::begin(&provider_name, provider_config.pkce_enabled);
+    let state = pending.state().to_string();
+    if let Err(e) = session.insert(sessions::OAUTH_KEY, &pending) {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": "Session error",
+            "message": format!("Failed to start sign-in: {e}")
+        })));
+    }
+    let auth_url = provider_config.authorization_url(&redirect_uri, &state, pkce_challenge.as_deref());
     
     Ok(HttpResponse::Ok().json(OAuthUrlResponse {
         auth_url,
@@ -1101,42 +1250,98 @@ async fn oauth_provider_url(
     }))
 }
 
+fn csrf_timeout(oauth_config: &OAuthConfig) -> std::time::Duration {
+    std::time::Duration::from_secs(u64::from(oauth_config.oauth.common.csrf_token_timeout_minutes) * 60)
+}
+
+// Send the browser back to the frontend with the outcome of the sign-in
+fn auth_redirect(query: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .append_header(("Location", format!("http://localhost:8887/team?{query}")))
+        .finish()
+}
+
 async fn oauth_provider_callback(
+    session: Session,
     provider: web::Path<String>,
     query: web::Query<std::collections::HashMap<String, String>>,
 ) -> Result<HttpResponse> {
     let provider_name = provider.into_inner();
-    let code = match query.get("code") {
-        Some(code) => code,
-        None => {
-            return Ok(HttpResponse::Found()
-                .append_header(("Location", "http://localhost:8887/team?auth=error&message=no_code"))
-                .finish());
+
+    // Provider-side denial, e.g. the user clicked "Cancel"
+    if let Some(error) = query.get("error") {
+        log::warn!("{provider_name} authorization failed: {error}");
+        return Ok(auth_redirect(&format!("auth=error&message={}", urlencoding::encode(error))));
+    }
+    let Some(code) = query.get("code") else {
+        return Ok(auth_redirect("auth=error&message=no_code"));
+    };
+    let Some(state) = query.get("state") else {
+        return Ok(auth_redirect("auth=error&message=invalid_state"));
+    };
+
+    let oauth_config = match OAuthConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("Failed to load OAuth config: {e}");
+            return Ok(auth_redirect("auth=error&message=config_error"));
         }
     };
-    
-    // For now, create a demo user session for any successful OAuth callback
-    // In production, this would exchange the code for a token and fetch user info
-    let user_session = UserSession::new(
-        format!("{}_user_{}", provider_name, &code[..8]),
-        format!("user@{}.com", provider_name),
-        format!("{} User", provider_name.to_uppercase()),
-        None,
-        provider_name,
-    );
-    
-    // In a real implementation, you would:
-    // 1. Exchange authorization code for access token
-    // 2. Fetch user information from provider
-    // 3. Store/update user in database
-    // 4. Create session
-    
-    Ok(HttpResponse::Found()
-        .append_header(("Location", "http://localhost:8887/team?auth=success#account/preferences"))
-        .finish())
+    let Some(provider_config) = oauth_config.get_provider(&provider_name) else {
+        return Ok(auth_redirect("auth=error&message=unknown_provider"));
+    };
+
+    let pending = session.remove_as::<PendingAuthorization>(sessions::OAUTH_KEY).and_then(Result::ok);
+    let redeemed = pending
+        .ok_or(oauth::StateError::Unknown)
+        .and_then(|pending| pending.redeem(state, &provider_name, csrf_timeout(&oauth_config)));
+    let pkce_verifier = match redeemed {
+        Ok(verifier) => verifier,
+        Err(e) => {
+            log::warn!("Rejected {provider_name} callback: {}", e.code());
+            return Ok(auth_redirect(&format!("auth=error&message={}", e.code())));
+        }
+    };
+
+    let client = reqwest::Client::new();
+    let redirect_uri = oauth_config.get_redirect_uri(&provider_name);
+    let user_session = match oauth::exchange_code(&client, provider_config, code, &redirect_uri, pkce_verifier.as_deref()).await {
+        Ok(tokens) => oauth::fetch_user_session(&client, &provider_name, provider_config, &tokens.access_token).await,
+        Err(e) => Err(e),
+    };
+    let user_session = match user_session {
+        Ok(user) => user.expires_in_hours(oauth_config.oauth.common.session_timeout_hours),
+        Err(e) => {
+            log::error!("{provider_name} sign-in failed: {e:#}");
+            return Ok(auth_redirect("auth=error&message=exchange_failed"));
+        }
+    };
+
+    if let Err(e) = sessions::sign_in(&session, &user_session) {
+        log::error!("Failed to start session: {e:#}");
+        return Ok(auth_redirect("auth=error&message=session_error"));
+    }
+    log::info!("{} sign-in for {} ({})", user_session.provider, user_session.user_id, user_session.email);
+    Ok(auth_redirect("auth=success#account/preferences"))
+}
+
+/// The demo sign-in skips the provider entirely, so it is only served when `DEMO_LOGIN_ENABLED=true`
+/// is set for local development
+fn demo_login_enabled() -> bool {
+    std::env::var("DEMO_LOGIN_ENABLED").is_ok_and(|v| v.eq_ignore_ascii_case("true") || v == "1")
 }
 
-async fn demo_login() -> Result<HttpResponse> {
+fn demo_login_disabled() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "success": false,
+        "error": "Demo login is disabled"
+    }))
+}
+
+async fn demo_login(session: Session) -> Result<HttpResponse> {
+    if !demo_login_enabled() {
+        return Ok(demo_login_disabled());
+    }
     // Load demo user from configuration
     let oauth_config = match OAuthConfig::load() {
         Ok(config) => config,
@@ -1168,7 +1373,15 @@ async fn demo_login() -> Result<HttpResponse> {
             None,
             "demo".to_string(),
         )
-    };
+    }
+    .expires_in_hours(oauth_config.oauth.common.session_timeout_hours);
+
+    if let Err(e) = sessions::sign_in(&session, &user_session) {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "success": false,
+            "error": format!("Failed to start session: {e}")
+        })));
+    }
     
     Ok(HttpResponse::Ok().json(json!({
         "success": true,
@@ -1176,18 +1389,22 @@ async fn demo_login() -> Result<HttpResponse> {
     })))
 }
 
-async fn get_current_user() -> Result<HttpResponse> {
-    // For now, return not authenticated
-    // In a real implementation, this would check the session
-    Ok(HttpResponse::Ok().json(json!({
-        "success": false,
-        "error": "Not authenticated"
-    })))
+async fn get_current_user(session: Session) -> Result<HttpResponse> {
+    match sessions::current_user(&session) {
+        Some(user) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "user": user
+        }))),
+        None => Ok(HttpResponse::Ok().json(json!({
+            "success": false,
+            "error": "Not authenticated"
+        }))),
+    }
 }
 
-async fn logout_user() -> Result<HttpResponse> {
-    // For now, just return success
-    // In a real implementation, this would clear the session
+async fn logout_user(session: Session) -> Result<HttpResponse> {
+    // Deletes the server-side row, so the cookie stops working even if it was copied
+    session.purge();
     Ok(HttpResponse::Ok().json(json!({
         "success": true
     })))
@@ -1792,41 +2009,11 @@ async fn get_tables(data: web::Data<Arc<ApiState>>, query: web::Query<std::colle
     // Check if a specific connection is requested
     let connection_name = query.get("connection");
     let pool = if let Some(connection_name) = connection_name {
-        // Get the database URL for this connection
-        let database_url = if let Ok(url) = std::env::var(connection_name) {
-            // Direct URL environment variable
-            url
-        } else {
-            // Try component-based configuration
-            let host_key = format!("{connection_name}_HOST");
-            let port_key = format!("{connection_name}_PORT");
-            let name_key = format!("{connection_name}_NAME");
-            let user_key = format!("{connection_name}_USER");
-            let password_key = format!("{connection_name}_PASSWORD");
-            let ssl_key = format!("{connection_name}_SSL_MODE");
-            
-            if let (Ok(host), Ok(port), Ok(name), Ok(user), Ok(password)) = (
-                std::env::var(&host_key),
-                std::env::var(&port_key),
-                std::env::var(&name_key),
-                std::env::var(&user_key),
-                std::env::var(&password_key)
-            ) {
-                let ssl_mode = std::env::var(&ssl_key).unwrap_or_else(|_| "require".to_string());
-                format!("postgres://{user}:{password}@{host}:{port}/{name}?sslmode={ssl_mode}")
-            } else {
-                return Ok(HttpResponse::BadRequest().json(json!({
-                    "error": format!("Connection '{}' not found in environment variables", connection_name)
-                })));
-            }
-        };
-        
-        // Use the specified connection
-        match sqlx::postgres::PgPool::connect(&database_url).await {
+        match data.connections.get(connection_name).await {
             Ok(pool) => pool,
             Err(e) => {
-                return Ok(HttpResponse::InternalServerError().json(json!({
-                    "error": format!("Failed to connect to {}: {}", connection_name, e)
+                return Ok(HttpResponse::build(e.status_code()).json(json!({
+                    "error": e.to_string()
                 })));
             }
         }
@@ -1842,18 +2029,20 @@ async fn get_tables(data: web::Data<Arc<ApiState>>, query: web::Query<std::colle
         }
     };
     
-    match get_database_tables(&pool, None, connection_name).await {
+    match get_database_tables(&pool, None, query.get("schema").map(String::as_str), connection_name).await {
         Ok(tables) => {
             let mut table_info = Vec::new();
             
             // Get actual row counts for each table
             for table in tables {
-                let query = format!("SELECT COUNT(*) FROM {}", table.name);
+                let table_ref = schema::TableRef { schema: Some(table.schema.clone()), name: table.table_name.clone() };
+                let query = format!("SELECT COUNT(*) FROM {}", table_ref.sql());
                 match sqlx::query(&query).fetch_one(&pool).await {
                     Ok(row) => {
                         let count: i64 = row.get(0);
                         table_info.push(TableInfo {
                             name: table.name.clone(),
+                            schema: Some(table.schema.clone()),
                             row_count: count,
                         });
                     }
@@ -1861,6 +2050,7 @@ async fn get_tables(data: web::Data<Arc<ApiState>>, query: web::Query<std::colle
                         // Table might not be accessible, use estimated count
                         table_info.push(TableInfo {
                             name: table.name.clone(),
+                            schema: Some(table.schema.clone()),
                             row_count: table.rows.unwrap_or(0),
                         });
                     }
@@ -1889,6 +2079,7 @@ async fn get_tables_mock() -> Result<HttpResponse> {
     let table_info: Vec<TableInfo> = tables.iter().map(|table_name| {
         TableInfo {
             name: table_name.to_string(),
+            schema: None,
             row_count: 0, // Mock data shows 0 rows
         }
     }).collect();
@@ -1924,6 +2115,20 @@ async fn db_test_connection(data: web::Data<Arc<ApiState>>) -> Result<HttpRespon
     }
 }
 
+// GET /api/db/connections - pool stats for the default and named connections
+async fn db_list_connections(data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
+    let mut pools = Vec::new();
+    if let Some(db) = &data.db {
+        pools.push(connections::PoolStats::of("default", db, None));
+    }
+    pools.extend(data.connections.stats());
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "connections": pools
+    })))
+}
+
 // Test Commons database connection specifically
 async fn db_test_commons_connection(data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
     match &data.db {
@@ -1957,7 +2162,7 @@ async fn db_test_commons_connection(data: web::Data<Arc<ApiState>>) -> Result<Ht
 }
 
 // Test Locations Database connection specifically
-async fn db_test_location_connection(_data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
+async fn db_test_location_connection(data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
     // Check if Locations environment variables are configured
     let location_host = std::env::var("LOCATIONS_HOST").unwrap_or_default();
     let location_name = std::env::var("LOCATIONS_NAME").unwrap_or_default();
@@ -1976,12 +2181,8 @@ async fn db_test_location_connection(_data: web::Data<Arc<ApiState>>) -> Result<
         })));
     }
     
-    // Attempt to create a temporary connection to test
-    let ssl_mode = std::env::var("LOCATIONS_SSL_MODE").unwrap_or_else(|_| "require".to_string());
-    let location_port = std::env::var("LOCATIONS_PORT").unwrap_or_else(|_| "5432".to_string());
-    let database_url = format!("postgres://{location_user}:{location_password}@{location_host}:{location_port}/{location_name}?sslmode={ssl_mode}");
-    
-    match sqlx::postgres::PgPool::connect(&database_url).await {
+    // Reuses the registry pool, so repeated checks do not open new connections
+    match data.connections.get("LOCATIONS").await {
         Ok(pool) => {
             match test_db_connection(&pool).await {
                 Ok(info) => Ok(HttpResponse::Ok().json(json!({
@@ -2011,7 +2212,7 @@ async fn db_test_location_connection(_data: web::Data<Arc<ApiState>>) -> Result<
 }
 
 // Test Industry Database connection specifically
-async fn db_test_exiobase_connection(_data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
+async fn db_test_exiobase_connection(data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
     // Check if Exiobase environment variables are configured
     let exiobase_host = std::env::var("EXIOBASE_HOST").unwrap_or_default();
     let exiobase_name = std::env::var("EXIOBASE_NAME").unwrap_or_default();
@@ -2030,11 +2231,8 @@ async fn db_test_exiobase_connection(_data: web::Data<Arc<ApiState>>) -> Result<
         })));
     }
     
-    // Attempt to create a temporary connection to test
-    let ssl_mode = std::env::var("EXIOBASE_SSL_MODE").unwrap_or_else(|_| "require".to_string());
-    let database_url = format!("postgres://{exiobase_user}:{exiobase_password}@{exiobase_host}:5432/{exiobase_name}?sslmode={ssl_mode}");
-    
-    match sqlx::postgres::PgPool::connect(&database_url).await {
+    // Reuses the registry pool, so repeated checks do not open new connections
+    match data.connections.get("EXIOBASE").await {
         Ok(pool) => {
             match test_db_connection(&pool).await {
                 Ok(info) => Ok(HttpResponse::Ok().json(json!({
@@ -2067,20 +2265,11 @@ async fn db_test_exiobase_connection(_data: web::Data<Arc<ApiState>>) -> Result<
 // Industry Database (EXIOBASE) — Trade Data Insert
 // ============================================================
 
-fn get_exiobase_database_url() -> Result<String, String> {
-    let host = std::env::var("EXIOBASE_HOST").unwrap_or_default();
-    let name = std::env::var("EXIOBASE_NAME").unwrap_or_default();
-    let user = std::env::var("EXIOBASE_USER").unwrap_or_default();
-    let password = std::env::var("EXIOBASE_PASSWORD").unwrap_or_default();
-    let ssl_mode = std::env::var("EXIOBASE_SSL_MODE").unwrap_or_else(|_| "require".to_string());
-    if host.is_empty() || name.is_empty() || user.is_empty() || password.is_empty() {
-        return Err("Industry Database not configured — set EXIOBASE_* environment variables".to_string());
-    }
-    Ok(format!("postgres://{user}:{password}@{host}:5432/{name}?sslmode={ssl_mode}"))
-}
+const EXIOBASE_NOT_CONFIGURED: &str = "Industry Database not configured — set EXIOBASE_* environment variables";
 
+// One-off connection for CLI commands; the server goes through `exiobase_pool`
 async fn connect_to_exiobase() -> Result<Pool<Postgres>, String> {
-    let url = get_exiobase_database_url()?;
+    let url = connections::database_url("EXIOBASE").ok_or_else(|| EXIOBASE_NOT_CONFIGURED.to_string())?;
     PgPoolOptions::new()
         .max_connections(5)
         .connect(&url)
@@ -2088,6 +2277,14 @@ async fn connect_to_exiobase() -> Result<Pool<Postgres>, String> {
         .map_err(|e| format!("Failed to connect to Industry Database: {e}"))
 }
 
+async fn exiobase_pool(data: &ApiState) -> Result<Pool<Postgres>, String> {
+    match data.connections.get("EXIOBASE").await {
+        Ok(pool) => Ok(pool),
+        Err(connections::ConnectionError::NotConfigured(_)) => Err(EXIOBASE_NOT_CONFIGURED.to_string()),
+        Err(connections::ConnectionError::Connect(_, e)) => Err(format!("Failed to connect to Industry Database: {e}")),
+    }
+}
+
 async fn fetch_github_csv(url: &str) -> Result<String, String> {
     reqwest::get(url)
         .await
@@ -2097,155 +2294,21 @@ async fn fetch_github_csv(url: &str) -> Result<String, String> {
         .map_err(|e| format!("Failed to read response body from {url}: {e}"))
 }
 
-// Run SQL, log result but don't propagate errors (graceful degradation for existing DBs)
-async fn try_exec(pool: &Pool<Postgres>, sql: &str, steps: &mut Vec<String>) {
-    match sqlx::query(sql).execute(pool).await {
-        Ok(_)  => steps.push(format!("OK: {}", sql.split_whitespace().take(5).collect::<Vec<_>>().join(" "))),
-        Err(e) => steps.push(format!("SKIP: {} — {}", sql.split_whitespace().take(5).collect::<Vec<_>>().join(" "), e)),
-    }
-}
-
 async fn init_industry_tables_in_pool(pool: &Pool<Postgres>) -> Result<Vec<String>, String> {
-    let mut steps: Vec<String> = Vec::new();
-
-    // Create tables without FK references first (existing DBs may lack PKs)
-
-    // industry — columns only; PK added separately so existing tables are handled
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS industry (
-            industry_id VARCHAR(10),
-            name        TEXT        NOT NULL,
-            category    VARCHAR(100)
-        )
-    "#).execute(pool).await.map_err(|e| e.to_string())?;
-    steps.push("Ensured table: industry".to_string());
-    try_exec(pool, "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='industry_pkey') THEN ALTER TABLE industry ADD PRIMARY KEY (industry_id); END IF; END $$", &mut steps).await;
-
-    // factor
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS factor (
-            factor_id   INTEGER,
-            unit        VARCHAR(50),
-            stressor    TEXT,
-            extension   VARCHAR(100)
-        )
-    "#).execute(pool).await.map_err(|e| e.to_string())?;
-    steps.push("Ensured table: factor".to_string());
-    try_exec(pool, "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='factor_pkey') THEN ALTER TABLE factor ADD PRIMARY KEY (factor_id); END IF; END $$", &mut steps).await;
-
-    // trade
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS trade (
-            id        BIGSERIAL     PRIMARY KEY,
-            trade_id  INTEGER       NOT NULL,
-            year      SMALLINT      NOT NULL,
-            region1   VARCHAR(10)   NOT NULL,
-            region2   VARCHAR(10)   NOT NULL,
-            industry1 VARCHAR(10),
-            industry2 VARCHAR(10),
-            amount    NUMERIC(18,4),
-            flow_type VARCHAR(10)   NOT NULL DEFAULT 'unknown',
-            country   VARCHAR(10)   NOT NULL DEFAULT 'unknown'
-        )
-    "#).execute(pool).await.map_err(|e| e.to_string())?;
-    steps.push("Ensured table: trade".to_string());
-    try_exec(pool, "ALTER TABLE trade ADD COLUMN IF NOT EXISTS flow_type VARCHAR(10) NOT NULL DEFAULT 'unknown'", &mut steps).await;
-    try_exec(pool, "ALTER TABLE trade ADD COLUMN IF NOT EXISTS country   VARCHAR(10) NOT NULL DEFAULT 'unknown'", &mut steps).await;
-    try_exec(pool, "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='trade_dedup') THEN ALTER TABLE trade ADD CONSTRAINT trade_dedup UNIQUE (trade_id, year, country, flow_type); END IF; END $$", &mut steps).await;
-
-    // trade_factor
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS trade_factor (
-            id           BIGSERIAL      PRIMARY KEY,
-            trade_id     INTEGER        NOT NULL,
-            year         SMALLINT       NOT NULL,
-            country      VARCHAR(10)    NOT NULL,
-            flow_type    VARCHAR(10)    NOT NULL,
-            factor_id    INTEGER        NOT NULL,
-            coefficient  NUMERIC(20,10),
-            level NUMERIC(20,6)
-        )
-    "#).execute(pool).await.map_err(|e| e.to_string())?;
-    steps.push("Ensured table: trade_factor".to_string());
-
-    // interstate
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS interstate (
-            id                  BIGSERIAL     PRIMARY KEY,
-            trade_id            INTEGER       NOT NULL,
-            year                SMALLINT      NOT NULL,
-            region1             VARCHAR(10)   NOT NULL,
-            region2             VARCHAR(10)   NOT NULL,
-            industry1           VARCHAR(10),
-            industry2           VARCHAR(10),
-            amount              NUMERIC(18,4),
-            commodity_code      VARCHAR(30),
-            industry_code       VARCHAR(30),
-            economic_multiplier NUMERIC(10,6)
-        )
-    "#).execute(pool).await.map_err(|e| e.to_string())?;
-    steps.push("Ensured table: interstate".to_string());
-
-    // interstate_factor
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS interstate_factor (
-            id                  BIGSERIAL     PRIMARY KEY,
-            interstate_id       VARCHAR(80)   NOT NULL,
-            trade_id            INTEGER       NOT NULL,
-            factor_id           INTEGER,
-            coefficient         NUMERIC(20,10),
-            state_industry_code VARCHAR(30),
-            level          NUMERIC(20,6),
-            flow_type           VARCHAR(20),
-            employment_impact   NUMERIC(20,10)
-        )
-    "#).execute(pool).await.map_err(|e| e.to_string())?;
-    // Add factor_id / coefficient columns if table already existed without them
-    try_exec(pool, "ALTER TABLE interstate_factor ADD COLUMN IF NOT EXISTS factor_id INTEGER", &mut steps).await;
-    try_exec(pool, "ALTER TABLE interstate_factor ADD COLUMN IF NOT EXISTS coefficient NUMERIC(20,10)", &mut steps).await;
-    steps.push("Ensured table: interstate_factor".to_string());
-
-    // FK constraints (gracefully skipped if PKs unavailable)
-    for sql in &[
-        "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='fk_trade_industry1') THEN ALTER TABLE trade ADD CONSTRAINT fk_trade_industry1 FOREIGN KEY (industry1) REFERENCES industry(industry_id); END IF; END $$",
-        "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='fk_trade_industry2') THEN ALTER TABLE trade ADD CONSTRAINT fk_trade_industry2 FOREIGN KEY (industry2) REFERENCES industry(industry_id); END IF; END $$",
-        "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='fk_tf_factor') THEN ALTER TABLE trade_factor ADD CONSTRAINT fk_tf_factor FOREIGN KEY (factor_id) REFERENCES factor(factor_id); END IF; END $$",
-        "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='fk_istate_industry1') THEN ALTER TABLE interstate ADD CONSTRAINT fk_istate_industry1 FOREIGN KEY (industry1) REFERENCES industry(industry_id); END IF; END $$",
-        "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='fk_istate_industry2') THEN ALTER TABLE interstate ADD CONSTRAINT fk_istate_industry2 FOREIGN KEY (industry2) REFERENCES industry(industry_id); END IF; END $$",
-        "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname='fk_isf_factor') THEN ALTER TABLE interstate_factor ADD CONSTRAINT fk_isf_factor FOREIGN KEY (factor_id) REFERENCES factor(factor_id); END IF; END $$",
-    ] {
-        try_exec(pool, sql, &mut steps).await;
-    }
-    steps.push("Processed FK constraints".to_string());
-
-    // Indexes
-    for sql in &[
-        "CREATE INDEX IF NOT EXISTS idx_trade_lookup       ON trade (trade_id, year, country, flow_type)",
-        "CREATE INDEX IF NOT EXISTS idx_trade_year_country ON trade (year, country)",
-        "CREATE INDEX IF NOT EXISTS idx_trade_region1      ON trade (region1)",
-        "CREATE INDEX IF NOT EXISTS idx_trade_region2      ON trade (region2)",
-        "CREATE INDEX IF NOT EXISTS idx_trade_flow_type    ON trade (flow_type)",
-        "CREATE INDEX IF NOT EXISTS idx_tf_lookup          ON trade_factor (trade_id, year, country, flow_type)",
-        "CREATE INDEX IF NOT EXISTS idx_tf_factor_id       ON trade_factor (factor_id)",
-        "CREATE INDEX IF NOT EXISTS idx_tf_year_country    ON trade_factor (year, country)",
-        "CREATE INDEX IF NOT EXISTS idx_istate_trade_id    ON interstate (trade_id)",
-        "CREATE INDEX IF NOT EXISTS idx_istate_year        ON interstate (year)",
-        "CREATE INDEX IF NOT EXISTS idx_istate_region1     ON interstate (region1)",
-        "CREATE INDEX IF NOT EXISTS idx_istate_region2     ON interstate (region2)",
-        "CREATE INDEX IF NOT EXISTS idx_isf_trade_id       ON interstate_factor (trade_id)",
-        "CREATE INDEX IF NOT EXISTS idx_isf_interstate_id  ON interstate_factor (interstate_id)",
-        "CREATE INDEX IF NOT EXISTS idx_isf_factor_id      ON interstate_factor (factor_id)",
-    ] {
-        try_exec(pool, sql, &mut steps).await;
-    }
-    steps.push("Processed indexes".to_string());
-
-    Ok(steps)
+    migrations::migrate_up(pool, &migrations::INDUSTRY)
+        .await
+        .map_err(|e| format!("{e:#}"))
 }
 
 // POST /api/db/init-industry-tables
-async fn db_init_industry_tables(_data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
-    match connect_to_exiobase().await {
+async fn db_init_industry_tables(data: web::Data<Arc<ApiState>>, session: Session) -> Result<HttpResponse> {
+    let response = init_industry_tables(&data).await?;
+    audit::record(&data, &session, "industry.init_tables", "EXIOBASE", json!({}), &response).await;
+    Ok(response)
+}
+
+async fn init_industry_tables(data: &ApiState) -> Result<HttpResponse> {
+    match exiobase_pool(data).await {
         Err(e) => Ok(HttpResponse::ServiceUnavailable().json(json!({
             "success": false, "error": e
         }))),
@@ -2499,9 +2562,17 @@ struct InsertTradeDataRequest {
 
 // POST /api/db/insert-trade-data
 async fn db_insert_trade_data(
-    _data: web::Data<Arc<ApiState>>,
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
     req: web::Json<InsertTradeDataRequest>,
 ) -> Result<HttpResponse> {
+    let response = insert_trade_data(&data, &req).await?;
+    let changes = json!({ "year": req.year.trim(), "country": req.country.trim().to_uppercase() });
+    audit::record(&data, &session, "industry.insert_trade_data", "EXIOBASE", changes, &response).await;
+    Ok(response)
+}
+
+async fn insert_trade_data(data: &ApiState, req: &InsertTradeDataRequest) -> Result<HttpResponse> {
     let year_str = req.year.trim().to_string();
     let country  = req.country.trim().to_uppercase();
 
@@ -2512,7 +2583,7 @@ async fn db_insert_trade_data(
         }))),
     };
 
-    let pool = match connect_to_exiobase().await {
+    let pool = match exiobase_pool(data).await {
         Ok(p) => p,
         Err(e) => return Ok(HttpResponse::ServiceUnavailable().json(json!({
             "success": false, "error": e
@@ -2602,8 +2673,8 @@ async fn db_insert_trade_data(
 }
 
 // GET /api/db/industry-schema
-async fn db_get_industry_schema(_data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
-    let pool = match connect_to_exiobase().await {
+async fn db_get_industry_schema(data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
+    let pool = match exiobase_pool(&data).await {
         Ok(p) => p,
         Err(e) => return Ok(HttpResponse::ServiceUnavailable().json(json!({
             "success": false, "error": e,
@@ -2615,7 +2686,7 @@ async fn db_get_industry_schema(_data: web::Data<Arc<ApiState>>) -> Result<HttpR
     let col_rows = sqlx::query(r#"
         SELECT table_name, column_name, data_type, ordinal_position
         FROM information_schema.columns
-        WHERE table_schema = 'public'
+        WHERE table_schema = current_schema()
           AND table_name IN ('trade','trade_factor','factor','industry','interstate','interstate_factor')
         ORDER BY table_name, ordinal_position
     "#).fetch_all(&pool).await;
@@ -2624,7 +2695,8 @@ async fn db_get_industry_schema(_data: web::Data<Arc<ApiState>>) -> Result<HttpR
     let count_rows = sqlx::query(r#"
         SELECT relname AS table_name, n_live_tup AS row_count
         FROM pg_stat_user_tables
-        WHERE relname IN ('trade','trade_factor','factor','industry','interstate','interstate_factor')
+        WHERE schemaname = current_schema()
+          AND relname IN ('trade','trade_factor','factor','industry','interstate','interstate_factor')
     "#).fetch_all(&pool).await;
 
     let mut tables: HashMap<String, serde_json::Value> = HashMap::new();
@@ -2759,7 +2831,7 @@ async fn db_list_tables(
     let limit = query.get("limit").and_then(|s| s.parse::<i32>().ok());
     match &data.db {
         Some(db) => {
-            match get_database_tables(db, limit, None).await {
+            match get_database_tables(db, limit, query.get("schema").map(String::as_str), None).await {
                 Ok(tables) => Ok(HttpResponse::Ok().json(DatabaseResponse {
                     success: true,
                     message: Some(format!("Found {} tables", tables.len())),
@@ -2783,6 +2855,53 @@ async fn db_list_tables(
     }
 }
 
+// List schemas with their table counts
+async fn db_list_schemas(
+    data: web::Data<Arc<ApiState>>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let pool = if let Some(connection_name) = query.get("connection") {
+        match data.connections.get(connection_name).await {
+            Ok(pool) => pool,
+            Err(e) => {
+                return Ok(HttpResponse::build(e.status_code()).json(DatabaseResponse {
+                    success: false,
+                    message: None,
+                    error: Some(e.to_string()),
+                    data: None,
+                }));
+            }
+        }
+    } else {
+        match &data.db {
+            Some(db) => db.clone(),
+            None => {
+                return Ok(HttpResponse::ServiceUnavailable().json(DatabaseResponse {
+                    success: false,
+                    message: None,
+                    error: Some("Database not available. Server started without database connection.".to_string()),
+                    data: None,
+                }));
+            }
+        }
+    };
+
+    match schema::schemas(&pool).await {
+        Ok(schemas) => Ok(HttpResponse::Ok().json(DatabaseResponse {
+            success: true,
+            message: Some(format!("Found {} schemas", schemas.len())),
+            error: None,
+            data: Some(serde_json::json!({ "schemas": schemas })),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(DatabaseResponse {
+            success: false,
+            message: None,
+            error: Some(format!("Failed to list schemas: {e}")),
+            data: None,
+        })),
+    }
+}
+
 // Get table information
 async fn db_get_table_info(
     data: web::Data<Arc<ApiState>>,
@@ -2793,46 +2912,13 @@ async fn db_get_table_info(
     
     // Check if a specific connection is requested
     let pool = if let Some(connection_name) = query.get("connection") {
-        // Get the database URL for this connection
-        let database_url = if let Ok(url) = std::env::var(connection_name) {
-            // Direct URL environment variable
-            url
-        } else {
-            // Try component-based configuration
-            let host_key = format!("{connection_name}_HOST");
-            let port_key = format!("{connection_name}_PORT");
-            let name_key = format!("{connection_name}_NAME");
-            let user_key = format!("{connection_name}_USER");
-            let password_key = format!("{connection_name}_PASSWORD");
-            let ssl_key = format!("{connection_name}_SSL_MODE");
-            
-            if let (Ok(host), Ok(port), Ok(name), Ok(user), Ok(password)) = (
-                std::env::var(&host_key),
-                std::env::var(&port_key),
-                std::env::var(&name_key),
-                std::env::var(&user_key),
-                std::env::var(&password_key)
-            ) {
-                let ssl_mode = std::env::var(&ssl_key).unwrap_or_else(|_| "require".to_string());
-                format!("postgres://{user}:{password}@{host}:{port}/{name}?sslmode={ssl_mode}")
-            } else {
-                return Ok(HttpResponse::BadRequest().json(DatabaseResponse {
-                    success: false,
-                    message: None,
-                    error: Some(format!("Connection '{connection_name}' not found in environment variables")),
-                    data: None,
-                }));
-            }
-        };
-        
-        // Use the specified connection
-        match sqlx::postgres::PgPool::connect(&database_url).await {
+        match data.connections.get(connection_name).await {
             Ok(pool) => pool,
             Err(e) => {
-                return Ok(HttpResponse::InternalServerError().json(DatabaseResponse {
+                return Ok(HttpResponse::build(e.status_code()).json(DatabaseResponse {
                     success: false,
                     message: None,
-                    error: Some(format!("Failed to connect to {connection_name}: {e}")),
+                    error: Some(e.to_string()),
                     data: None,
                 }));
             }
@@ -2852,12 +2938,35 @@ async fn db_get_table_info(
         }
     };
     
-    match get_table_details(&pool, &table_name).await {
-        Ok(info) => Ok(HttpResponse::Ok().json(DatabaseResponse {
-            success: true,
-            message: Some(format!("Table {table_name} found")),
-            error: None,
-            data: Some(serde_json::to_value(info).unwrap()),
+    let table = match schema::TableRef::resolve(&table_name, query.get("schema").map(String::as_str)) {
+        Ok(table) => table,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(DatabaseResponse {
+                success: false,
+                message: None,
+                error: Some(e),
+                data: None,
+            }));
+        }
+    };
+
+    match schema::table_details(&pool, &table).await {
+        Ok(Some(mut info)) => {
+            if info.description.is_none() && table.schema.is_none() {
+                info.description = get_table_description(&table.name);
+            }
+            Ok(HttpResponse::Ok().json(DatabaseResponse {
+                success: true,
+                message: Some(format!("Table {table_name} found")),
+                error: None,
+                data: Some(serde_json::to_value(info).unwrap()),
+            }))
+        }
+        Ok(None) => Ok(HttpResponse::NotFound().json(DatabaseResponse {
+            success: false,
+            message: None,
+            error: Some(format!("Table {table_name} not found")),
+            data: None,
         })),
         Err(e) => Ok(HttpResponse::InternalServerError().json(DatabaseResponse {
             success: false,
@@ -2868,71 +2977,24 @@ async fn db_get_table_info(
     }
 }
 
-// Execute custom query (use with caution!)
-async fn db_execute_query(
+// Foreign-key graph of a schema, read from pg_catalog
+async fn db_get_relationships(
     data: web::Data<Arc<ApiState>>,
-    query_req: web::Json<QueryRequest>,
     query: web::Query<std::collections::HashMap<String, String>>,
 ) -> Result<HttpResponse> {
-    // Only allow safe SELECT queries for security
-    let query_text = query_req.query.trim().to_lowercase();
-    if !query_text.starts_with("select") {
-        return Ok(HttpResponse::BadRequest().json(DatabaseResponse {
-            success: false,
-            message: None,
-            error: Some("Only SELECT queries are allowed".to_string()),
-            data: None,
-        }));
-    }
-
-    // Check if a specific connection is requested
     let pool = if let Some(connection_name) = query.get("connection") {
-        // Get the database URL for this connection
-        let database_url = if let Ok(url) = std::env::var(connection_name) {
-            // Direct URL environment variable
-            url
-        } else {
-            // Try component-based configuration
-            let host_key = format!("{connection_name}_HOST");
-            let port_key = format!("{connection_name}_PORT");
-            let name_key = format!("{connection_name}_NAME");
-            let user_key = format!("{connection_name}_USER");
-            let password_key = format!("{connection_name}_PASSWORD");
-            let ssl_key = format!("{connection_name}_SSL_MODE");
-            
-            if let (Ok(host), Ok(port), Ok(name), Ok(user), Ok(password)) = (
-                std::env::var(&host_key),
-                std::env::var(&port_key),
-                std::env::var(&name_key),
-                std::env::var(&user_key),
-                std::env::var(&password_key)
-            ) {
-                let ssl_mode = std::env::var(&ssl_key).unwrap_or_else(|_| "require".to_string());
-                format!("postgres://{user}:{password}@{host}:{port}/{name}?sslmode={ssl_mode}")
-            } else {
-                return Ok(HttpResponse::BadRequest().json(DatabaseResponse {
-                    success: false,
-                    message: None,
-                    error: Some(format!("Connection '{connection_name}' not found in environment variables")),
-                    data: None,
-                }));
-            }
-        };
-        
-        // Use the specified connection
-        match sqlx::postgres::PgPool::connect(&database_url).await {
+        match data.connections.get(connection_name).await {
             Ok(pool) => pool,
             Err(e) => {
-                return Ok(HttpResponse::InternalServerError().json(DatabaseResponse {
+                return Ok(HttpResponse::build(e.status_code()).json(DatabaseResponse {
                     success: false,
                     message: None,
-                    error: Some(format!("Failed to connect to {connection_name}: {e}")),
+                    error: Some(e.to_string()),
                     data: None,
                 }));
             }
         }
     } else {
-        // Use default connection
         match &data.db {
             Some(db) => db.clone(),
             None => {
@@ -2946,642 +3008,103 @@ async fn db_execute_query(
         }
     };
 
-    match execute_safe_query(&pool, &query_req.query).await {
-        Ok(result) => Ok(HttpResponse::Ok().json(DatabaseResponse {
+    match schema::foreign_keys(&pool, query.get("schema").map(String::as_str)).await {
+        Ok(foreign_keys) => Ok(HttpResponse::Ok().json(DatabaseResponse {
             success: true,
-            message: Some("Query executed successfully".to_string()),
+            message: Some(format!("{} foreign keys found", foreign_keys.len())),
             error: None,
-            data: Some(result),
+            data: Some(schema::relationship_graph(&foreign_keys)),
         })),
         Err(e) => Ok(HttpResponse::InternalServerError().json(DatabaseResponse {
             success: false,
             message: None,
-            error: Some(format!("Query failed: {e}")),
+            error: Some(format!("Failed to read foreign keys: {e}")),
             data: None,
         })),
     }
 }
 
-// Get paginated, sortable rows from a table
-async fn db_get_table_rows(
+// Execute a read-only query in the SQL sandbox (see sql_sandbox.rs)
+async fn db_execute_query(
     data: web::Data<Arc<ApiState>>,
-    req: web::Json<TableRowsRequest>,
+    query_req: web::Json<QueryRequest>,
+    query: web::Query<std::collections::HashMap<String, String>>,
 ) -> Result<HttpResponse> {
-    // Validate table name
-    if req.table.is_empty() || !req.table.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        return Ok(HttpResponse::BadRequest().json(json!({
-            "error": "Invalid table name: only alphanumeric characters and underscores are allowed"
-        })));
-    }
-    // Validate sort field
-    if let Some(field) = &req.sort_field {
-        if !field.chars().all(|c| c.is_alphanumeric() || c == '_') {
-            return Ok(HttpResponse::BadRequest().json(json!({
-                "error": "Invalid sort field: only alphanumeric characters and underscores are allowed"
-            })));
-        }
+    // Refuse bad queries before opening a connection
+    if let Err(e) = sql_sandbox::validate(&query_req.query) {
+        return Ok(HttpResponse::BadRequest().json(DatabaseResponse {
+            success: false,
+            message: None,
+            error: Some(e.to_string()),
+            data: None,
+        }));
     }
 
-    let page = req.page.unwrap_or(1).max(1);
-    let size = req.size.unwrap_or(200).clamp(1, 1000);
-    let offset = (page - 1) * size;
-
-    let sort_clause = match (&req.sort_field, &req.sort_dir) {
-        (Some(field), Some(dir)) => {
-            let direction = if dir.to_lowercase() == "desc" { "DESC" } else { "ASC" };
-            format!("ORDER BY \"{field}\" {direction}")
-        }
-        (Some(field), None) => format!("ORDER BY \"{field}\" ASC"),
-        _ => String::new(),
-    };
-
-    let pool = if let Some(connection_name) = &req.connection {
-        let database_url = if let Ok(url) = std::env::var(connection_name) {
-            url
-        } else {
-            let host_key = format!("{connection_name}_HOST");
-            let port_key = format!("{connection_name}_PORT");
-            let name_key = format!("{connection_name}_NAME");
-            let user_key = format!("{connection_name}_USER");
-            let password_key = format!("{connection_name}_PASSWORD");
-            let ssl_key = format!("{connection_name}_SSL_MODE");
-            if let (Ok(host), Ok(port), Ok(name), Ok(user), Ok(password)) = (
-                std::env::var(&host_key),
-                std::env::var(&port_key),
-                std::env::var(&name_key),
-                std::env::var(&user_key),
-                std::env::var(&password_key),
-            ) {
-                let ssl_mode = std::env::var(&ssl_key).unwrap_or_else(|_| "require".to_string());
-                format!("postgres://{user}:{password}@{host}:{port}/{name}?sslmode={ssl_mode}")
-            } else {
-                return Ok(HttpResponse::BadRequest().json(json!({
-                    "error": format!("Connection '{connection_name}' not found in environment variables")
-                })));
-            }
-        };
-        match sqlx::postgres::PgPool::connect(&database_url).await {
+    // Check if a specific connection is requested
+    let pool = if let Some(connection_name) = query.get("connection") {
+        match data.connections.get(connection_name).await {
             Ok(pool) => pool,
-            Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
-                "error": format!("Failed to connect to {connection_name}: {e}")
-            }))),
+            Err(e) => {
+                return Ok(HttpResponse::build(e.status_code()).json(DatabaseResponse {
+                    success: false,
+                    message: None,
+                    error: Some(e.to_string()),
+                    data: None,
+                }));
+            }
         }
     } else {
+        // Use default connection
         match &data.db {
             Some(db) => db.clone(),
-            None => return Ok(HttpResponse::ServiceUnavailable().json(json!({
-                "error": "Database not available"
-            }))),
+            None => {
+                return Ok(HttpResponse::ServiceUnavailable().json(DatabaseResponse {
+                    success: false,
+                    message: None,
+                    error: Some("Database not available. Server started without database connection.".to_string()),
+                    data: None,
+                }));
+            }
         }
     };
 
-    let count_sql = format!("SELECT COUNT(*) FROM \"{}\"", req.table);
-    let total: i64 = match sqlx::query(&count_sql).fetch_one(&pool).await {
-        Ok(row) => row.get(0),
-        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
-            "error": format!("Failed to count rows in {}: {e}", req.table)
-        }))),
-    };
-
-    let data_sql = format!("SELECT * FROM \"{}\" {} LIMIT {size} OFFSET {offset}", req.table, sort_clause);
-    let rows = match execute_safe_query(&pool, &data_sql).await {
-        Ok(rows) => rows,
-        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
-            "error": format!("Failed to fetch rows from {}: {e}", req.table)
+    let config = sql_sandbox::SandboxConfig::for_connection(query.get("connection").map(String::as_str));
+    match sql_sandbox::run(&pool, &query_req.query, &config, query_req.max_rows).await {
+        Ok(result) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "message": if result.truncated {
+                format!("Query executed successfully; showing the first {} rows", result.max_rows)
+            } else {
+                "Query executed successfully".to_string()
+            },
+            "error": null,
+            "data": result.rows,
+            "row_count": result.rows.len(),
+            "truncated": result.truncated,
+            "max_rows": result.max_rows,
+            "elapsed_ms": result.elapsed_ms,
         }))),
-    };
-
-    let last_page = ((total as f64) / (size as f64)).ceil() as i64;
-
-    Ok(HttpResponse::Ok().json(json!({
-        "last_page": last_page.max(1),
-        "data": rows,
-        "total": total,
-        "page": page,
-        "size": size,
-    })))
-}
-
-// Create a new project
-// Get all projects from database
-async fn get_projects(data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
-    let db = match &data.db {
-        Some(db) => db,
-        None => {
-            return Ok(HttpResponse::ServiceUnavailable().json(json!({
-                "error": "Database not available. Server started without database connection."
-            })));
-        }
-    };
-    
-    let projects_query = sqlx::query(
-        "SELECT id, name, description, status, date_entered, date_modified FROM projects ORDER BY date_modified DESC LIMIT 50"
-    )
-    .fetch_all(db)
-    .await;
-    
-    match projects_query {
-        Ok(rows) => {
-            let projects: Vec<serde_json::Value> = rows.into_iter().map(|row| {
-                json!({
-                    "id": row.get::<Uuid, _>("id"),
-                    "name": row.get::<String, _>("name"),
-                    "description": row.get::<Option<String>, _>("description"),
-                    "status": row.get::<Option<String>, _>("status"),
-                    "created_date": row.get::<chrono::DateTime<Utc>, _>("date_entered"),
-                    "modified_date": row.get::<chrono::DateTime<Utc>, _>("date_modified")
-                })
-            }).collect();
-            
-            Ok(HttpResponse::Ok().json(json!({
-                "success": true,
-                "data": projects
-            })))
-        },
         Err(e) => {
-            println!("Error fetching projects: {e}");
-            // Return empty array if database query fails
-            Ok(HttpResponse::Ok().json(json!({
-                "success": true,
-                "data": []
-            })))
-        }
-    }
-}
-
-async fn create_project(
-    data: web::Data<Arc<ApiState>>,
-    req: web::Json<CreateProjectRequest>,
-) -> Result<HttpResponse> {
-    let db = match &data.db {
-        Some(db) => db,
-        None => {
-            return Ok(HttpResponse::ServiceUnavailable().json(json!({
-                "error": "Database not available. Server started without database connection."
-            })));
+            let status = if e.is_client_error() {
+                actix_web::http::StatusCode::BAD_REQUEST
+            } else {
+                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+            };
+            Ok(HttpResponse::build(status).json(DatabaseResponse {
+                success: false,
+                message: None,
+                error: Some(e.to_string()),
+                data: None,
+            }))
         }
-    };
-    
-    let id = Uuid::new_v4();
-    let now = Utc::now();
-    
-    // Parse date strings into NaiveDate
-    let start_date = req.estimated_start_date.as_ref()
-        .and_then(|s| if s.is_empty() { None } else { Some(s) })
-        .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
-    
-    let end_date = req.estimated_end_date.as_ref()
-        .and_then(|s| if s.is_empty() { None } else { Some(s) })
-        .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
-    
-    let result = sqlx::query(
-        r#"
-        INSERT INTO projects (
-            id, name, description, status, 
-            estimated_start_date, estimated_end_date,
-            date_entered, date_modified, created_by, modified_user_id
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-        "#
-    )
-    .bind(id)
-    .bind(&req.name)
-    .bind(&req.description)
-    .bind(&req.status)
-    .bind(start_date)
-    .bind(end_date)
-    .bind(now)
-    .bind(now)
-    .bind("1") // Default user ID
-    .bind("1") // Default user ID
-    .execute(db)
-    .await;
-    
-    match result {
-        Ok(_) => Ok(HttpResponse::Created().json(json!({
-            "id": id.to_string(),
-            "message": "Project created successfully"
-        }))),
-        Err(e) => Ok(HttpResponse::BadRequest().json(json!({
-            "error": e.to_string()
-        }))),
     }
 }
 
 // Initialize database schema (simplified version with core tables)
 async fn init_database(pool: &Pool<Postgres>) -> anyhow::Result<()> {
-    // Create users table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            user_name VARCHAR(60),
-            first_name VARCHAR(30),
-            last_name VARCHAR(30),
-            email VARCHAR(100),
-            status VARCHAR(100),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create accounts table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS accounts (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(150),
-            account_type VARCHAR(50),
-            industry VARCHAR(50),
-            phone_office VARCHAR(100),
-            website VARCHAR(255),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create contacts table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS contacts (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            salutation VARCHAR(255),
-            first_name VARCHAR(100),
-            last_name VARCHAR(100),
-            title VARCHAR(100),
-            department VARCHAR(255),
-            account_id UUID REFERENCES accounts(id),
-            phone_work VARCHAR(100),
-            phone_mobile VARCHAR(100),
-            email VARCHAR(100),
-            primary_address_street VARCHAR(150),
-            primary_address_city VARCHAR(100),
-            primary_address_state VARCHAR(100),
-            primary_address_postalcode VARCHAR(20),
-            primary_address_country VARCHAR(255),
-            description TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create projects table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS projects (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(50),
-            description TEXT,
-            status VARCHAR(50),
-            priority VARCHAR(255),
-            estimated_start_date DATE,
-            estimated_end_date DATE,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create opportunities table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS opportunities (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(50),
-            account_id UUID REFERENCES accounts(id),
-            opportunity_type VARCHAR(255),
-            lead_source VARCHAR(50),
-            amount DECIMAL(26,6),
-            currency_id VARCHAR(36),
-            date_closed DATE,
-            sales_stage VARCHAR(255),
-            probability DECIMAL(3,0),
-            description TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create activities table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS activities (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(255),
-            date_due TIMESTAMP WITH TIME ZONE,
-            date_start TIMESTAMP WITH TIME ZONE,
-            parent_type VARCHAR(255),
-            parent_id UUID,
-            status VARCHAR(100),
-            priority VARCHAR(255),
-            description TEXT,
-            contact_id UUID REFERENCES contacts(id),
-            account_id UUID REFERENCES accounts(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create leads table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS leads (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            salutation VARCHAR(255),
-            first_name VARCHAR(100),
-            last_name VARCHAR(100),
-            title VARCHAR(100),
-            company VARCHAR(100),
-            phone_work VARCHAR(100),
-            phone_mobile VARCHAR(100),
-            email VARCHAR(100),
-            status VARCHAR(100),
-            lead_source VARCHAR(100),
-            description TEXT,
-            converted BOOLEAN DEFAULT false,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create campaigns table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS campaigns (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(50),
-            campaign_type VARCHAR(100),
-            status VARCHAR(100),
-            start_date DATE,
-            end_date DATE,
-            budget DECIMAL(26,6),
-            expected_cost DECIMAL(26,6),
-            actual_cost DECIMAL(26,6),
-            expected_revenue DECIMAL(26,6),
-            objective TEXT,
-            content TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create documents table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS documents (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            document_name VARCHAR(255),
-            filename VARCHAR(255),
-            file_ext VARCHAR(100),
-            file_mime_type VARCHAR(100),
-            revision VARCHAR(100),
-            category_id VARCHAR(100),
-            subcategory_id VARCHAR(100),
-            status VARCHAR(100),
-            description TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create events table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS events (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(255),
-            date_start TIMESTAMP WITH TIME ZONE,
-            date_end TIMESTAMP WITH TIME ZONE,
-            duration_hours INTEGER,
-            duration_minutes INTEGER,
-            location VARCHAR(255),
-            description TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create products table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS products (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(50),
-            product_code VARCHAR(50),
-            category VARCHAR(100),
-            manufacturer VARCHAR(50),
-            cost DECIMAL(26,6),
-            price DECIMAL(26,6),
-            description TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create roles table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS roles (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(150),
-            description TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create calls table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS calls (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(50),
-            date_start TIMESTAMP WITH TIME ZONE,
-            date_end TIMESTAMP WITH TIME ZONE,
-            duration_hours INTEGER,
-            duration_minutes INTEGER,
-            status VARCHAR(100),
-            direction VARCHAR(100),
-            parent_type VARCHAR(255),
-            parent_id UUID,
-            contact_id UUID REFERENCES contacts(id),
-            account_id UUID REFERENCES accounts(id),
-            description TEXT,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create surveyquestionoptions table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS surveyquestionoptions (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(50),
-            survey_question_id UUID,
-            sort_order INTEGER,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            created_by VARCHAR(36),
-            modified_user_id VARCHAR(36)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create tags table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS tags (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            name VARCHAR(255),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            date_modified TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create taggables table (polymorphic relationship)
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS taggables (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            tag_id UUID REFERENCES tags(id),
-            taggable_type VARCHAR(100),
-            taggable_id UUID,
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(tag_id, taggable_type, taggable_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Create relationship tables
-    
-    // User roles relationship
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users_roles (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            user_id UUID REFERENCES users(id),
-            role_id UUID REFERENCES roles(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(user_id, role_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Account contacts relationship
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS accounts_contacts (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            account_id UUID REFERENCES accounts(id),
-            contact_id UUID REFERENCES contacts(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(account_id, contact_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Account opportunities relationship
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS accounts_opportunities (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            account_id UUID REFERENCES accounts(id),
-            opportunity_id UUID REFERENCES opportunities(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(account_id, opportunity_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Contact opportunities relationship
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS contacts_opportunities (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            contact_id UUID REFERENCES contacts(id),
-            opportunity_id UUID REFERENCES opportunities(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(contact_id, opportunity_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Campaign leads relationship
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS campaigns_leads (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            campaign_id UUID REFERENCES campaigns(id),
-            lead_id UUID REFERENCES leads(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(campaign_id, lead_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Project contacts relationship
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS projects_contacts (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            project_id UUID REFERENCES projects(id),
-            contact_id UUID REFERENCES contacts(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(project_id, contact_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
-    // Project accounts relationship
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS projects_accounts (
-            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            project_id UUID REFERENCES projects(id),
-            account_id UUID REFERENCES accounts(id),
-            date_entered TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(project_id, account_id)
-        )
-        "#
-    ).execute(pool).await?;
-    
+    for step in migrations::migrate_up(pool, &migrations::COMMONS).await? {
+        println!("{step}");
+    }
     println!("Database schema initialized successfully!");
     Ok(())
 }
@@ -3608,65 +3131,36 @@ async fn test_db_connection(pool: &Pool<Postgres>) -> Result<ConnectionInfo, sql
     })
 }
 
-async fn get_database_tables(pool: &Pool<Postgres>, limit: Option<i32>, connection_name: Option<&String>) -> Result<Vec<TableInfoDetailed>, sqlx::Error> {
-    let query = if let Some(limit_val) = limit {
-        format!(
-            r#"
-            SELECT 
-                table_name,
-                (
-                    SELECT reltuples::bigint 
-                    FROM pg_class 
-                    WHERE relname = table_name
-                ) as estimated_rows
-            FROM information_schema.tables 
-            WHERE table_schema = 'public' 
-                AND table_type = 'BASE TABLE'
-            ORDER BY table_name
-            LIMIT {limit_val}
-            "#
-        )
-    } else {
-        r#"
-        SELECT 
-            table_name,
-            (
-                SELECT reltuples::bigint 
-                FROM pg_class 
-                WHERE relname = table_name
-            ) as estimated_rows
-        FROM information_schema.tables 
-        WHERE table_schema = 'public' 
-            AND table_type = 'BASE TABLE'
-        ORDER BY table_name
-        "#.to_string()
-    };
-    
-    let rows = sqlx::query(&query)
-    .fetch_all(pool)
-    .await?;
-
+async fn get_database_tables(
+    pool: &Pool<Postgres>,
+    limit: Option<i32>,
+    schema_name: Option<&str>,
+    connection_name: Option<&String>,
+) -> Result<Vec<TableInfoDetailed>, sqlx::Error> {
     let mut tables = Vec::new();
-    for row in rows {
-        let table_name: String = row.get("table_name");
-        let estimated_rows: Option<i64> = row.get("estimated_rows");
-        
+    for table in schema::tables(pool, schema_name, limit.map(i64::from)).await? {
         // Filter tables for EXIOBASE connection - only include valid tables
         if let Some(conn_name) = connection_name {
             if conn_name == "EXIOBASE" {
                 let valid_tables = ["trade", "industry", "factor", "trade_factor", "interstate", "interstate_factor"];
-                if !valid_tables.contains(&table_name.as_str()) {
+                if !valid_tables.contains(&table.name.as_str()) {
                     continue; // Skip tables not in the valid list
                 }
             }
         }
         
-        // Add description based on table name
-        let description = get_table_description(&table_name);
+        // Prefer the table comment, then a description based on table name (current schema only)
+        let description = match table.description {
+            Some(comment) => Some(comment),
+            None if table.identifier == table.name => get_table_description(&table.name),
+            None => None,
+        };
         
         tables.push(TableInfoDetailed {
-            name: table_name,
-            rows: estimated_rows,
+            name: table.identifier,
+            schema: table.schema,
+            table_name: table.name,
+            rows: table.estimated_rows,
             description,
         });
     }
@@ -3674,129 +3168,6 @@ async fn get_database_tables(pool: &Pool<Postgres>, limit: Option<i32>, connecti
     Ok(tables)
 }
 
-async fn get_table_details(pool: &Pool<Postgres>, table_name: &str) -> Result<HashMap<String, serde_json::Value>, sqlx::Error> {
-    // Get basic table info
-    let row = sqlx::query(
-        r#"
-        SELECT 
-            (SELECT reltuples::bigint FROM pg_class WHERE relname = $1) as estimated_rows,
-            (SELECT count(*) FROM information_schema.columns WHERE table_name = $1) as column_count
-        "#,
-    )
-    .bind(table_name)
-    .fetch_one(pool)
-    .await?;
-
-    // Get column information
-    let column_rows = sqlx::query(
-        r#"
-        SELECT 
-            column_name,
-            data_type,
-            is_nullable,
-            column_default,
-            character_maximum_length,
-            numeric_precision,
-            numeric_scale
-        FROM information_schema.columns 
-        WHERE table_name = $1 
-        ORDER BY ordinal_position
-        "#,
-    )
-    .bind(table_name)
-    .fetch_all(pool)
-    .await?;
-
-    let mut columns = Vec::new();
-    for col_row in column_rows {
-        let mut column_info = serde_json::Map::new();
-        column_info.insert("name".to_string(), serde_json::Value::String(col_row.get::<String, _>("column_name")));
-        column_info.insert("type".to_string(), serde_json::Value::String(col_row.get::<String, _>("data_type")));
-        column_info.insert("nullable".to_string(), serde_json::Value::String(col_row.get::<String, _>("is_nullable")));
-        
-        if let Some(default_value) = col_row.get::<Option<String>, _>("column_default") {
-            column_info.insert("default".to_string(), serde_json::Value::String(default_value));
-        }
-        
-        if let Some(max_length) = col_row.get::<Option<i32>, _>("character_maximum_length") {
-            column_info.insert("max_length".to_string(), serde_json::json!(max_length));
-        }
-        
-        columns.push(serde_json::Value::Object(column_info));
-    }
-
-    let mut info = HashMap::new();
-    info.insert("table_name".to_string(), serde_json::Value::String(table_name.to_string()));
-    info.insert("estimated_rows".to_string(), serde_json::json!(row.get::<Option<i64>, _>("estimated_rows")));
-    info.insert("column_count".to_string(), serde_json::json!(row.get::<i64, _>("column_count")));
-    info.insert("description".to_string(), serde_json::Value::String(
-        get_table_description(table_name).unwrap_or_else(|| "No description available".to_string())
-    ));
-    info.insert("columns".to_string(), serde_json::Value::Array(columns));
-
-    Ok(info)
-}
-
-async fn execute_safe_query(pool: &Pool<Postgres>, query: &str) -> Result<serde_json::Value, sqlx::Error> {
-    let rows = sqlx::query(query).fetch_all(pool).await?;
-
-    let mut results = Vec::new();
-    for row in rows {
-        let mut row_map = serde_json::Map::new();
-
-        for (i, column) in row.columns().iter().enumerate() {
-            let type_name = column.type_info().name().to_lowercase();
-
-            let value = match row.try_get_raw(i) {
-                Ok(raw) if raw.is_null() => serde_json::Value::Null,
-                Ok(_) => {
-                    if type_name.contains("int") || type_name == "serial" || type_name == "bigserial" {
-                        if let Ok(v) = row.try_get::<i64, _>(i) { json!(v) }
-                        else if let Ok(v) = row.try_get::<i32, _>(i) { json!(v) }
-                        else if let Ok(v) = row.try_get::<i16, _>(i) { json!(v) }
-                        else { serde_json::Value::Null }
-                    } else if type_name == "float4" || type_name == "real" {
-                        if let Ok(v) = row.try_get::<f32, _>(i) { json!(v) }
-                        else { serde_json::Value::Null }
-                    } else if type_name == "float8" || type_name == "double precision"
-                           || type_name == "numeric" || type_name == "decimal" {
-                        if let Ok(v) = row.try_get::<f64, _>(i) { json!(v) }
-                        else { serde_json::Value::Null }
-                    } else if type_name == "bool" || type_name == "boolean" {
-                        if let Ok(v) = row.try_get::<bool, _>(i) { json!(v) }
-                        else { serde_json::Value::Null }
-                    } else if type_name == "uuid" {
-                        if let Ok(v) = row.try_get::<Uuid, _>(i) { json!(v.to_string()) }
-                        else { serde_json::Value::Null }
-                    } else if type_name.starts_with("timestamp") {
-                        if let Ok(v) = row.try_get::<chrono::DateTime<Utc>, _>(i) { json!(v.to_rfc3339()) }
-                        else if let Ok(v) = row.try_get::<NaiveDateTime, _>(i) { json!(v.to_string()) }
-                        else { serde_json::Value::Null }
-                    } else if type_name == "date" {
-                        if let Ok(v) = row.try_get::<NaiveDate, _>(i) { json!(v.to_string()) }
-                        else { serde_json::Value::Null }
-                    } else if type_name == "json" || type_name == "jsonb" {
-                        if let Ok(v) = row.try_get::<serde_json::Value, _>(i) { v }
-                        else { serde_json::Value::Null }
-                    } else {
-                        match row.try_get::<String, _>(i) {
-                            Ok(s) => json!(s),
-                            Err(_) => serde_json::Value::Null,
-                        }
-                    }
-                }
-                Err(_) => serde_json::Value::Null,
-            };
-
-            row_map.insert(column.name().to_string(), value);
-        }
-
-        results.push(serde_json::Value::Object(row_map));
-    }
-
-    Ok(serde_json::Value::Array(results))
-}
-
 fn get_table_description(table_name: &str) -> Option<String> {
     match table_name {
         "accounts" => Some("Customer accounts and organizations".to_string()),
@@ -3830,6 +3201,17 @@ fn get_table_description(table_name: &str) -> Option<String> {
     }
 }
 
+/// Origins allowed to call the API with the session cookie, from the comma-separated
+/// `ALLOWED_ORIGINS` (default: the local frontend on port 8887)
+fn allowed_origins() -> Vec<String> {
+    std::env::var("ALLOWED_ORIGINS")
+        .unwrap_or_else(|_| "http://localhost:8887,http://127.0.0.1:8887".to_string())
+        .split(',')
+        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect()
+}
+
 // Run the API server
 async fn run_api_server(config: Config) -> anyhow::Result<()> {
     println!("Attempting to connect to database: {}", &config.database_url);
@@ -3841,6 +3223,13 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
     {
         Ok(pool) => {
             println!("Database connection successful!");
+            // Nothing is left running the jobs a previous server started
+            match import_jobs::fail_interrupted(&pool).await {
+                Ok(0) => {}
+                Ok(n) => log::warn!("Marked {n} interrupted import job(s) failed; resume them with POST /api/import/jobs/{{id}}/resume"),
+                Err(e) => log::warn!("Cannot check for interrupted import jobs: {e}"),
+            }
+            democracylab_sync::schedule(pool.clone(), democracylab_sync::SyncConfig::from_env());
             Some(pool)
         }
         Err(e) => {
@@ -3854,14 +3243,19 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
     // Create shared config for hot reloading
     let shared_config = Arc::new(Mutex::new(config));
     
+    // Named connections (EXIOBASE, LOCATIONS, ...) share one pool each
+    let connections = Arc::new(connections::ConnectionRegistry::from_env());
+
     // Start watching .env file for changes
-    if let Err(e) = start_env_watcher(shared_config.clone()) {
+    if let Err(e) = start_env_watcher(shared_config.clone(), connections.clone()) {
         log::warn!("Failed to start .env file watcher: {e}");
     }
     
     let state = Arc::new(ApiState {
         db: pool,
         config: shared_config.clone(),
+        connections,
+        saved_query_cache: saved_queries::ResultCache::default(),
     });
     
     // Create persistent Claude session manager
@@ -3881,9 +3275,21 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
     
     let cognito_config_clone = cognito_config.clone();
 
+    // Cookie sessions live in the `sessions` table on the main connection
+    let session_key = sessions::signing_key();
+    let session_timeout_hours = OAuthConfig::load()
+        .map(|c| c.oauth.common.session_timeout_hours)
+        .unwrap_or(24);
+
+    let origins = allowed_origins();
+    log::info!("CORS allows credentials from {}", origins.join(", "));
+
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
+        // Credentialed requests need explicit origins; a wildcard would let any site act as the user
+        let cors = origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+            .supports_credentials()
             .allow_any_method()
             .allow_any_header()
             .max_age(3600);
@@ -3892,6 +3298,7 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
             .app_data(web::Data::new(state.clone()))
             .app_data(web::Data::new(session_manager_clone.clone()))
             .app_data(web::Data::new(cognito_config_clone.clone()))
+            .wrap(sessions::middleware(state.db.clone(), session_key.clone(), session_timeout_hours))
             .wrap(cors)
             .wrap(DefaultHeaders::new().add(("Access-Control-Allow-Private-Network", "true")))
             .wrap(middleware::Logger::default())
@@ -3900,20 +3307,31 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
                     .route("/health", web::get().to(health_check))
                     .route("/tables", web::get().to(get_tables))
                     .route("/tables/mock", web::get().to(get_tables_mock))
-                    .route("/projects", web::get().to(get_projects))
-                    .route("/projects", web::post().to(create_project))
+                    .route("/audit", web::get().to(audit::list_audit_entries).wrap(auth::require(Permission::Admin)))
+                    .configure(crm::configure_routes)
                     .service(
                         web::scope("/db")
                             .route("/test-connection", web::get().to(db_test_connection))
+                            .route("/connections", web::get().to(db_list_connections))
                             .route("/test-commons-connection", web::get().to(db_test_commons_connection))
                             .route("/test-exiobase-connection", web::get().to(db_test_exiobase_connection))
                             .route("/test-locations-connection", web::get().to(db_test_location_connection))
+                            .route("/schemas", web::get().to(db_list_schemas))
                             .route("/tables", web::get().to(db_list_tables))
                             .route("/table/{table_name}", web::get().to(db_get_table_info))
-                            .route("/table-rows", web::post().to(db_get_table_rows))
+                            .route("/relationships", web::get().to(db_get_relationships))
+                            .route("/table-rows", web::post().to(table_rows::get_table_rows))
                             .route("/query", web::post().to(db_execute_query))
-                            .route("/init-industry-tables", web::post().to(db_init_industry_tables))
-                            .route("/insert-trade-data", web::post().to(db_insert_trade_data))
+                            .route("/export", web::get().to(export::export_table))
+                            .route("/export/query", web::post().to(export::export_query))
+                            .route("/saved", web::get().to(saved_queries::list_saved_queries))
+                            .route("/saved", web::post().to(saved_queries::create_saved_query).wrap(auth::require(Permission::Write)))
+                            .route("/saved/{id}", web::get().to(saved_queries::get_saved_query))
+                            .route("/saved/{id}", web::put().to(saved_queries::update_saved_query).wrap(auth::require(Permission::Write)))
+                            .route("/saved/{id}", web::delete().to(saved_queries::delete_saved_query).wrap(auth::require(Permission::Write)))
+                            .route("/saved/{id}/run", web::post().to(saved_queries::run_saved_query))
+                            .route("/init-industry-tables", web::post().to(db_init_industry_tables).wrap(auth::require(Permission::Admin)))
+                            .route("/insert-trade-data", web::post().to(db_insert_trade_data).wrap(auth::require(Permission::Write)))
                             .route("/industry-schema", web::get().to(db_get_industry_schema))
                     )
                     .service(
@@ -3921,8 +3339,17 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
                             .route("/excel", web::post().to(import::import_excel_data))
                             .route("/excel/preview", web::post().to(import::preview_excel_data))
                             .route("/excel/sheets", web::post().to(import::get_excel_sheets))
+                            .route("/spreadsheet", web::post().to(spreadsheet::import_spreadsheet))
                             .route("/data", web::post().to(import::import_data))
                             .route("/democracylab", web::post().to(import::import_democracylab_projects))
+                            .route("/jobs", web::post().to(import_jobs::create_import_job))
+                            .route("/jobs", web::get().to(import_jobs::list_import_jobs))
+                            .route("/jobs/{id}", web::get().to(import_jobs::get_import_job))
+                            .route("/jobs/{id}/resume", web::post().to(import_jobs::resume_import_job))
+                            .route("/uploads", web::post().to(uploads::create_upload))
+                            .route("/uploads/{id}", web::get().to(uploads::get_upload))
+                            .route("/uploads/{id}", web::delete().to(uploads::delete_upload))
+                            .wrap(auth::require(Permission::Write))
                     )
                     .service(
                         web::scope("/claude")
@@ -3942,7 +3369,7 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
                     )
                     .service(
                         web::scope("/github")
-                            .route("/token", web::get().to(get_github_token))
+                            .route("/token", web::get().to(get_github_token).wrap(auth::require(Permission::Admin)))
                     )
                     .service(
                         web::scope("/github-cli")
@@ -3976,15 +3403,15 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
                         web::scope("/config")
                             .route("/current", web::get().to(get_current_config))
                             .route("/env", web::get().to(get_env_config))
-                            .route("/env", web::post().to(save_env_config))
-                            .route("/env/create", web::post().to(create_env_config))
+                            .route("/env", web::post().to(save_env_config).wrap(auth::require(Permission::Admin)))
+                            .route("/env/create", web::post().to(create_env_config).wrap(auth::require(Permission::Admin)))
                             .route("/gemini", web::get().to(gemini_insights::test_gemini_api))
-                            .route("/restart", web::post().to(restart_server))
-                            .route("/stop-webroot", web::post().to(stop_webroot_server))
+                            .route("/restart", web::post().to(restart_server).wrap(auth::require(Permission::Admin)))
+                            .route("/stop-webroot", web::post().to(stop_webroot_server).wrap(auth::require(Permission::Admin)))
                     )
                     .service(
                         web::scope("/files")
-                            .route("/csv", web::post().to(save_csv_file))
+                            .route("/csv", web::post().to(save_csv_file).wrap(auth::require(Permission::Write)))
                     )
                     .service(
                         web::scope("/proxy")
@@ -3993,7 +3420,7 @@ async fn run_api_server(config: Config) -> anyhow::Result<()> {
                             .route("/hdf5", web::post().to(proxy_hdf5_file))
                     )
                     .route("/scrape", web::get().to(scrape_site))
-                    .route("/admin/git", web::post().to(run_git_script))
+                    .route("/admin/git", web::post().to(run_git_script).wrap(auth::require(Permission::Admin)))
                     .service(
                         web::scope("/recommendations")
                             .route("", web::post().to(get_recommendations_handler))
@@ -4372,7 +3799,19 @@ struct RunGitRequest {
     action: Option<String>,
 }
 
-async fn run_git_script(req: HttpRequest, body: web::Json<RunGitRequest>) -> Result<HttpResponse> {
+async fn run_git_script(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: HttpRequest,
+    body: web::Json<RunGitRequest>,
+) -> Result<HttpResponse> {
+    let response = run_git_script_with_token(req, &body).await?;
+    let changes = json!({ "action": body.action });
+    audit::record(&data, &session, "git.run", "git", changes, &response).await;
+    Ok(response)
+}
+
+async fn run_git_script_with_token(req: HttpRequest, body: &RunGitRequest) -> Result<HttpResponse> {
     // Authenticate using a GitHub token passed by the client.
     // Accept token in `Authorization` header (Bearer or token) or `x-github-token`.
     // Validate token by calling GitHub API /user. If valid, pass it to the script as GITHUB_TOKEN
@@ -4500,6 +3939,27 @@ async fn run_git_script(req: HttpRequest, body: web::Json<RunGitRequest>) -> Res
     }
 }
 
+async fn run_migrate_command(pool: &Pool<Postgres>, set: &migrations::MigrationSet, action: MigrateAction) -> anyhow::Result<()> {
+    match action {
+        MigrateAction::Up => {
+            for step in migrations::migrate_up(pool, set).await? {
+                println!("{step}");
+            }
+        }
+        MigrateAction::Down { yes } => match migrations::migrate_down(pool, set, yes).await? {
+            Some(label) => println!("Rolled back {label}"),
+            None => println!("No applied {} migrations to roll back", set.name),
+        },
+        MigrateAction::Status => {
+            for entry in migrations::status(pool, set).await? {
+                let applied_at = entry.applied_at.map(|t| t.to_rfc3339()).unwrap_or_default();
+                println!("{:04}_{:<30} {:<18} {}", entry.version, entry.name, entry.state, applied_at);
+            }
+        }
+    }
+    Ok(())
+}
+
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
@@ -4521,6 +3981,107 @@ async fn main() -> anyhow::Result<()> {
                         .context("Failed to connect to database for init")?;
                     init_database(&pool).await?;
                 }
+                Commands::Migrate { action, database } => {
+                    let (pool, set) = match database {
+                        MigrationTarget::Commons => (
+                            PgPoolOptions::new()
+                                .connect(&config.database_url)
+                                .await
+                                .context("Failed to connect to database for migrations")?,
+                            &migrations::COMMONS,
+                        ),
+                        MigrationTarget::Industry => (
+                            connect_to_exiobase().await.map_err(anyhow::Error::msg)?,
+                            &migrations::INDUSTRY,
+                        ),
+                    };
+                    run_migrate_command(&pool, set, action).await?;
+                }
+                Commands::GrantRole { email, role } => {
+                    let pool = PgPoolOptions::new()
+                        .connect(&config.database_url)
+                        .await
+                        .context("Failed to connect to database")?;
+                    if auth::grant_role(&pool, &email, &role).await? {
+                        audit::write(Some(&pool), None, "roles.grant", &email, &json!({ "role": role, "via": "cli" }), 200).await;
+                        println!("Granted role '{role}' to {email}");
+                    } else {
+                        println!("{email} already has role '{role}'");
+                    }
+                }
+                Commands::Purge { older_than_days, entity, dry_run } => {
+                    let tables: Vec<&'static str> = match &entity {
+                        Some(name) => match crm::ENTITY_TABLES.iter().find(|t| **t == name.as_str()) {
+                            Some(table) => vec![*table],
+                            None => anyhow::bail!("Unknown entity '{name}'. Expected one of: {}", crm::ENTITY_TABLES.join(", ")),
+                        },
+                        None => crm::ENTITY_TABLES.to_vec(),
+                    };
+                    let pool = PgPoolOptions::new()
+                        .connect(&config.database_url)
+                        .await
+                        .context("Failed to connect to database")?;
+                    let cutoff = Utc::now() - chrono::Duration::days(older_than_days as i64);
+                    let counts = purge::purge(&pool, &tables, cutoff, dry_run).await?;
+                    let verb = if dry_run { "would purge" } else { "purged" };
+                    for count in counts.iter().filter(|c| c.purged > 0 || c.kept > 0) {
+                        print!("{}: {verb} {}", count.table, count.purged);
+                        if count.links_removed > 0 {
+                            print!(" ({} relationship links removed)", count.links_removed);
+                        }
+                        if count.kept > 0 {
+                            print!(", kept {} still referenced by other records", count.kept);
+                        }
+                        println!();
+                    }
+                    let total: u64 = counts.iter().map(|c| c.purged).sum();
+                    let outcome = if dry_run { "would be purged" } else { "purged" };
+                    println!("{total} record(s) {outcome}, deleted before {}", cutoff.format("%Y-%m-%d %H:%M UTC"));
+                    if !dry_run && total > 0 {
+                        let summary: serde_json::Map<String, serde_json::Value> = counts
+                            .iter()
+                            .filter(|c| c.purged > 0)
+                            .map(|c| (c.table.to_string(), json!(c.purged)))
+                            .collect();
+                        let changes = json!({ "purged": summary, "older_than_days": older_than_days, "via": "cli" });
+                        audit::write(Some(&pool), None, "records.purge", "crm", &changes, 200).await;
+                    }
+                }
+                Commands::BackfillProjectAttributes { dry_run } => {
+                    let pool = PgPoolOptions::new()
+                        .connect(&config.database_url)
+                        .await
+                        .context("Failed to connect to database")?;
+                    let count = project_attributes::backfill(&pool, dry_run).await?;
+                    let verb = if dry_run { "would fill" } else { "filled" };
+                    println!("projects: {verb} {} column value(s) on {} project(s)", count.values, count.projects);
+                    if count.kept > 0 {
+                        println!("{} block(s) left in descriptions where the column already has another value or the value doesn't fit", count.kept);
+                    }
+                    if !dry_run && count.projects > 0 {
+                        let changes = json!({ "projects": count.projects, "values": count.values, "kept": count.kept, "via": "cli" });
+                        audit::write(Some(&pool), None, "projects.backfill_attributes", "projects", &changes, 200).await;
+                    }
+                }
+                Commands::SyncDemocracylab { max_pages, dry_run } => {
+                    let pool = PgPoolOptions::new()
+                        .connect(&config.database_url)
+                        .await
+                        .context("Failed to connect to database")?;
+                    let mut sync_config = democracylab_sync::SyncConfig::from_env();
+                    if let Some(max_pages) = max_pages {
+                        sync_config.max_pages = max_pages.max(1);
+                    }
+                    let summary = democracylab_sync::sync(&pool, &democracylab_sync::client(), &sync_config, dry_run).await?;
+                    for error in &summary.errors {
+                        println!("{error}");
+                    }
+                    println!("{}", summary.message(dry_run));
+                    if !dry_run {
+                        let changes = json!({ "summary": summary.changes(), "via": "cli" });
+                        audit::write(Some(&pool), None, "import.democracylab.sync", "projects", &changes, 200).await;
+                    }
+                }
             }
         }
         Err(_) => {
@@ -0,0 +1,302 @@
+// src/migrations.rs
+//! Versioned, checksummed schema migrations.
+//!
+//! Migration files live under `migrations/<set>/NNNN_name.{up,down}.sql` and are
+//! embedded at compile time. Applied versions are recorded per set in
+//! `schema_migrations` together with the checksum of the up script, so an edited migration that has
+//! already run somewhere is reported as drift instead of being silently ignored.
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, Pool, Postgres, Row};
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+    /// The down script drops tables or columns that hold data, so `migrate_down` needs an
+    /// explicit confirmation
+    pub destructive: bool,
+}
+
+impl Migration {
+    /// Hex-encoded SHA-256 of the up script
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.up.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    pub fn label(&self) -> String {
+        format!("{:04}_{}", self.version, self.name)
+    }
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal, $file:literal, destructive: $destructive:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../migrations/", $file, ".up.sql")),
+            down: include_str!(concat!("../migrations/", $file, ".down.sql")),
+            destructive: $destructive,
+        }
+    };
+}
+
+/// An ordered list of migrations tracked under one name in `schema_migrations`
+#[derive(Debug)]
+pub struct MigrationSet {
+    pub name: &'static str,
+    pub migrations: &'static [Migration],
+}
+
+/// CRM schema on the main (COMMONS / DATABASE_URL) connection
+pub const COMMONS: MigrationSet = MigrationSet {
+    name: "commons",
+    migrations: &[
+        migration!(1, "crm_schema", "commons/0001_crm_schema", destructive: true),
+        migration!(2, "sessions", "commons/0002_sessions", destructive: true),
+        migration!(3, "saved_queries", "commons/0003_saved_queries", destructive: true),
+        migration!(4, "audit_log", "commons/0004_audit_log", destructive: true),
+        migration!(5, "entity_audit", "commons/0005_entity_audit", destructive: true),
+        migration!(6, "soft_delete", "commons/0006_soft_delete", destructive: true),
+        migration!(7, "import_jobs", "commons/0007_import_jobs", destructive: true),
+        migration!(8, "project_attributes", "commons/0008_project_attributes", destructive: true),
+        migration!(9, "democracylab_sync", "commons/0009_democracylab_sync", destructive: true),
+        migration!(10, "trigram_dedup", "commons/0010_trigram_dedup", destructive: false),
+    ],
+};
+
+/// Industry Database (EXIOBASE) tables
+pub const INDUSTRY: MigrationSet = MigrationSet {
+    name: "industry",
+    migrations: &[
+        migration!(1, "industry_tables", "industry/0001_industry_tables", destructive: true),
+    ],
+};
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    /// "applied", "pending", "checksum_mismatch" or "missing" (applied but no longer shipped)
+    pub state: &'static str,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+struct AppliedMigration {
+    name: String,
+    checksum: String,
+    applied_at: DateTime<Utc>,
+}
+
+async fn ensure_migrations_table(pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    // Checked first so routine runs don't log an "already exists" notice
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('schema_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+    if exists {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            migration_set VARCHAR(50) NOT NULL,
+            version       BIGINT NOT NULL,
+            name          TEXT NOT NULL,
+            checksum      TEXT NOT NULL,
+            applied_at    TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            execution_ms  BIGINT NOT NULL DEFAULT 0,
+            PRIMARY KEY (migration_set, version)
+        )
+        "#
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create schema_migrations table")?;
+    Ok(())
+}
+
+async fn applied_migrations(pool: &Pool<Postgres>, set: &MigrationSet) -> anyhow::Result<HashMap<i64, AppliedMigration>> {
+    let rows = sqlx::query("SELECT version, name, checksum, applied_at FROM schema_migrations WHERE migration_set = $1")
+        .bind(set.name)
+        .fetch_all(pool)
+        .await
+        .context("Failed to read schema_migrations")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.get::<i64, _>("version"),
+                AppliedMigration {
+                    name: row.get("name"),
+                    checksum: row.get("checksum"),
+                    applied_at: row.get("applied_at"),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Report every known and applied migration, in version order
+pub async fn status(pool: &Pool<Postgres>, set: &MigrationSet) -> anyhow::Result<Vec<MigrationStatus>> {
+    ensure_migrations_table(pool).await?;
+    let mut applied = applied_migrations(pool, set).await?;
+
+    let mut report: Vec<MigrationStatus> = set
+        .migrations
+        .iter()
+        .map(|m| match applied.remove(&m.version) {
+            Some(a) => MigrationStatus {
+                version: m.version,
+                name: m.name.to_string(),
+                state: if a.checksum == m.checksum() { "applied" } else { "checksum_mismatch" },
+                applied_at: Some(a.applied_at),
+            },
+            None => MigrationStatus {
+                version: m.version,
+                name: m.name.to_string(),
+                state: "pending",
+                applied_at: None,
+            },
+        })
+        .collect();
+
+    report.extend(applied.into_iter().map(|(version, a)| MigrationStatus {
+        version,
+        name: a.name,
+        state: "missing",
+        applied_at: Some(a.applied_at),
+    }));
+    report.sort_by_key(|s| s.version);
+    Ok(report)
+}
+
+/// Apply all pending migrations, each in its own transaction.
+/// Refuses to run if an applied migration's checksum no longer matches its file.
+pub async fn migrate_up(pool: &Pool<Postgres>, set: &MigrationSet) -> anyhow::Result<Vec<String>> {
+    ensure_migrations_table(pool).await?;
+    let applied = applied_migrations(pool, set).await?;
+
+    let drifted: Vec<String> = set
+        .migrations
+        .iter()
+        .filter(|m| applied.get(&m.version).is_some_and(|a| a.checksum != m.checksum()))
+        .map(Migration::label)
+        .collect();
+    if !drifted.is_empty() {
+        bail!("Applied migrations were modified after they ran: {}", drifted.join(", "));
+    }
+
+    let mut steps = Vec::new();
+    for migration in set.migrations.iter().filter(|m| !applied.contains_key(&m.version)) {
+        let started = Instant::now();
+        let mut tx = pool.begin().await?;
+        (&mut *tx).execute(migration.up)
+            .await
+            .with_context(|| format!("Migration {} failed", migration.label()))?;
+        sqlx::query(
+            "INSERT INTO schema_migrations (migration_set, version, name, checksum, execution_ms) VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(set.name)
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(migration.checksum())
+        .bind(started.elapsed().as_millis() as i64)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        log::info!("Applied {} migration {}", set.name, migration.label());
+        steps.push(format!("Applied {}", migration.label()));
+    }
+
+    if steps.is_empty() {
+        steps.push("Schema is up to date".to_string());
+    }
+    Ok(steps)
+}
+
+/// Roll back the most recently applied migration. Returns its label, or None if nothing was applied.
+pub async fn migrate_down(pool: &Pool<Postgres>, set: &MigrationSet, confirmed: bool) -> anyhow::Result<Option<String>> {
+    ensure_migrations_table(pool).await?;
+    let latest: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_migrations WHERE migration_set = $1")
+        .bind(set.name)
+        .fetch_one(pool)
+        .await?;
+    let Some(version) = latest else {
+        return Ok(None);
+    };
+
+    let migration = set
+        .migrations
+        .iter()
+        .find(|m| m.version == version)
+        .ok_or_else(|| anyhow!("Latest applied migration {version} has no down script in this build"))?;
+    if migration.destructive && !confirmed {
+        bail!(
+            "Rolling back {} migration {} drops tables or columns and the data in them; run again with --yes to confirm",
+            set.name,
+            migration.label()
+        );
+    }
+
+    let mut tx = pool.begin().await?;
+    (&mut *tx).execute(migration.down)
+        .await
+        .with_context(|| format!("Rollback of {} failed", migration.label()))?;
+    sqlx::query("DELETE FROM schema_migrations WHERE migration_set = $1 AND version = $2")
+        .bind(set.name)
+        .bind(version)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    log::info!("Rolled back {} migration {}", set.name, migration.label());
+    Ok(Some(migration.label()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_strictly_increasing() {
+        for set in [&COMMONS, &INDUSTRY] {
+            assert!(set.migrations.windows(2).all(|w| w[0].version < w[1].version));
+        }
+    }
+
+    #[test]
+    fn every_migration_has_both_scripts() {
+        for m in COMMONS.migrations.iter().chain(INDUSTRY.migrations) {
+            assert!(!m.up.trim().is_empty(), "{} has an empty up script", m.label());
+            assert!(!m.down.trim().is_empty(), "{} has an empty down script", m.label());
+        }
+    }
+
+    #[test]
+    fn down_scripts_that_drop_data_need_confirmation() {
+        for m in COMMONS.migrations.iter().chain(INDUSTRY.migrations) {
+            let down = m.down.to_uppercase();
+            if down.contains("DROP TABLE") || down.contains("DROP COLUMN") {
+                assert!(m.destructive, "{} drops data but is not marked destructive", m.label());
+            }
+        }
+    }
+
+    #[test]
+    fn checksum_is_stable_hex_sha256() {
+        let m = Migration { version: 1, name: "t", up: "SELECT 1;", down: "", destructive: false };
+        assert_eq!(m.checksum().len(), 64);
+        assert_eq!(m.checksum(), m.checksum());
+        assert_eq!(m.label(), "0001_t");
+    }
+}
@@ -1,8 +1,10 @@
 // OAuth Provider Configuration and Handler
 // Supports Google, GitHub, LinkedIn, Microsoft, and Facebook OAuth2
 
+use oauth2::{CsrfToken, PkceCodeChallenge};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct OAuthConfig {
@@ -92,6 +94,12 @@ impl UserSession {
             expires_at,
         }
     }
+
+    /// Override the default 24 hour lifetime (`session_timeout_hours`)
+    pub fn expires_in_hours(mut self, hours: u32) -> Self {
+        self.expires_at = self.created_at + i64::from(hours) * 60 * 60;
+        self
+    }
 }
 
 // Provider-specific user info structures
@@ -99,6 +107,8 @@ impl UserSession {
 pub struct GoogleUserInfo {
     pub id: String,
     pub email: String,
+    #[serde(default, alias = "email_verified")]
+    pub verified_email: bool,
     pub name: String,
     pub picture: Option<String>,
     pub given_name: Option<String>,
@@ -136,9 +146,6 @@ pub struct MicrosoftUserInfo {
     pub id: String,
     #[serde(rename = "displayName")]
     pub display_name: String,
-    #[serde(rename = "userPrincipalName")]
-    pub email: String,
-    pub mail: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -164,6 +171,8 @@ pub struct DiscordUserInfo {
     pub id: String,
     pub username: String,
     pub email: Option<String>,
+    #[serde(default)]
+    pub verified: bool,
     pub avatar: Option<String>,
     pub discriminator: String,
     pub global_name: Option<String>,
@@ -211,4 +220,424 @@ impl OAuthConfig {
     }
 }
 
-use anyhow::Context;
\ No newline at end of file
+impl OAuthProvider {
+    /// Build the authorization redirect, including the PKCE challenge when one is given
+    pub fn authorization_url(&self, redirect_uri: &str, state: &str, pkce_challenge: Option<&str>) -> String {
+        let mut url = format!(
+            "{}?client_id={}&redirect_uri={}&response_type={}&scope={}&state={}",
+            self.authorization_endpoint,
+            urlencoding::encode(&self.client_id),
+            urlencoding::encode(redirect_uri),
+            self.response_type,
+            urlencoding::encode(&self.scopes.join(" ")),
+            urlencoding::encode(state)
+        );
+        if let Some(challenge) = pkce_challenge {
+            url.push_str(&format!("&code_challenge={}&code_challenge_method=S256", urlencoding::encode(challenge)));
+        }
+        url
+    }
+}
+
+// Pending authorization request, kept in the session of the browser that started the sign-in
+
+/// An issued `state` with its PKCE verifier. It is stored in the requesting browser's session,
+/// so a callback carrying the state only succeeds in that browser and only once.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingAuthorization {
+    state: String,
+    provider: String,
+    pkce_verifier: Option<String>,
+    created_at: i64,
+}
+
+/// Why a callback's `state` was rejected; the code is passed back to the frontend
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    Unknown,
+    Expired,
+    ProviderMismatch,
+}
+
+impl StateError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            StateError::Unknown => "invalid_state",
+            StateError::Expired => "state_expired",
+            StateError::ProviderMismatch => "provider_mismatch",
+        }
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+impl PendingAuthorization {
+    /// Issue a new state for `provider`, with a PKCE challenge when PKCE is enabled
+    pub fn begin(provider: &str, pkce_enabled: bool) -> (Self, Option<String>) {
+        let (challenge, verifier) = if pkce_enabled {
+            let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+            (Some(challenge.as_str().to_string()), Some(verifier.secret().clone()))
+        } else {
+            (None, None)
+        };
+        let pending = PendingAuthorization {
+            state: CsrfToken::new_random().secret().clone(),
+            provider: provider.to_string(),
+            pkce_verifier: verifier,
+            created_at: unix_now(),
+        };
+        (pending, challenge)
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// Check a callback's `state` and provider against this request, returning its PKCE verifier (if any)
+    pub fn redeem(self, state: &str, provider: &str, timeout: Duration) -> Result<Option<String>, StateError> {
+        if self.state != state {
+            return Err(StateError::Unknown);
+        }
+        if unix_now() - self.created_at >= timeout.as_secs() as i64 {
+            return Err(StateError::Expired);
+        }
+        if self.provider != provider {
+            return Err(StateError::ProviderMismatch);
+        }
+        Ok(self.pkce_verifier)
+    }
+}
+
+// Code exchange and userinfo
+
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// Exchange an authorization code for an access token at the provider's token endpoint
+pub async fn exchange_code(
+    client: &reqwest::Client,
+    provider: &OAuthProvider,
+    code: &str,
+    redirect_uri: &str,
+    pkce_verifier: Option<&str>,
+) -> anyhow::Result<TokenResponse> {
+    let mut form = vec![
+        ("grant_type", provider.grant_type.as_str()),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("client_id", provider.client_id.as_str()),
+        ("client_secret", provider.client_secret.as_str()),
+    ];
+    if let Some(verifier) = pkce_verifier {
+        form.push(("code_verifier", verifier));
+    }
+
+    // GitHub answers with form encoding unless JSON is requested explicitly
+    let response = client
+        .post(&provider.token_endpoint)
+        .header("Accept", "application/json")
+        .form(&form)
+        .send()
+        .await
+        .with_context(|| format!("{} token request failed", provider.name))?;
+    let status = response.status();
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .with_context(|| format!("{} returned an unreadable token response ({})", provider.name, status))?;
+
+    // Some providers (GitHub) report errors with a 200 status
+    if let Some(error) = body.get("error").and_then(|e| e.as_str()) {
+        let description = body.get("error_description").and_then(|d| d.as_str()).unwrap_or("");
+        anyhow::bail!("{} rejected the authorization code: {} {}", provider.name, error, description);
+    }
+    if !status.is_success() {
+        anyhow::bail!("{} token endpoint returned {}", provider.name, status);
+    }
+    serde_json::from_value(body).with_context(|| format!("{} token response has no access_token", provider.name))
+}
+
+/// Fetch the signed-in user's profile and map it into a session
+pub async fn fetch_user_session(
+    client: &reqwest::Client,
+    provider_name: &str,
+    provider: &OAuthProvider,
+    access_token: &str,
+) -> anyhow::Result<UserSession> {
+    let mut request = client
+        .get(&provider.userinfo_endpoint)
+        .bearer_auth(access_token)
+        .header("Accept", "application/json")
+        // GitHub rejects requests without a User-Agent
+        .header("User-Agent", "PartnerTools");
+    if let Some(fields) = &provider.fields {
+        request = request.query(&[("fields", fields)]);
+    }
+
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("{} userinfo request failed", provider.name))?;
+    if !response.status().is_success() {
+        anyhow::bail!("{} userinfo endpoint returned {}", provider.name, response.status());
+    }
+    let body = response.text().await?;
+    let mut session = session_from_userinfo(provider_name, &body)
+        .with_context(|| format!("Unexpected {} userinfo response", provider.name))?;
+
+    // GitHub leaves `email` null when the address is private; ask the emails endpoint instead
+    if provider_name == "github" && session.email.is_empty() {
+        session.email = github_primary_email(client, provider, access_token)
+            .await
+            .unwrap_or_default();
+    }
+    Ok(session)
+}
+
+async fn github_primary_email(
+    client: &reqwest::Client,
+    provider: &OAuthProvider,
+    access_token: &str,
+) -> anyhow::Result<String> {
+    let emails: Vec<GitHubEmail> = client
+        .get(format!("{}/emails", provider.userinfo_endpoint.trim_end_matches('/')))
+        .bearer_auth(access_token)
+        .header("Accept", "application/json")
+        .header("User-Agent", "PartnerTools")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    emails
+        .into_iter()
+        .find(|e| e.primary && e.verified)
+        .map(|e| e.email)
+        .ok_or_else(|| anyhow::anyhow!("No verified primary email"))
+}
+
+/// Map a provider's userinfo JSON into a `UserSession`. Roles are granted by email, so an address
+/// the provider has not verified is refused rather than trusted.
+pub fn session_from_userinfo(provider_name: &str, body: &str) -> anyhow::Result<UserSession> {
+    let provider = provider_name.to_string();
+    let session = match provider_name {
+        "google" => {
+            let info: GoogleUserInfo = serde_json::from_str(body)?;
+            if !info.verified_email {
+                anyhow::bail!("Google has not verified {}", info.email);
+            }
+            UserSession::new(info.id, info.email, info.name, info.picture, provider)
+        }
+        "github" => {
+            let info: GitHubUserInfo = serde_json::from_str(body)?;
+            let name = info.name.unwrap_or_else(|| info.login.clone());
+            UserSession::new(info.id.to_string(), info.email.unwrap_or_default(), name, info.avatar_url, provider)
+        }
+        "linkedin" => {
+            let info: LinkedInUserInfo = serde_json::from_str(body)?;
+            let name = [info.first_name, info.last_name]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ");
+            // r_liteprofile carries no email address
+            UserSession::new(info.id, String::new(), name, info.profile_picture.map(|p| p.display_image), provider)
+        }
+        "microsoft" => {
+            let info: MicrosoftUserInfo = serde_json::from_str(body)?;
+            // Graph doesn't say whether `mail` or the principal name was verified, so neither is used
+            UserSession::new(info.id, String::new(), info.display_name, None, provider)
+        }
+        "facebook" => {
+            let info: FacebookUserInfo = serde_json::from_str(body)?;
+            UserSession::new(info.id, info.email.unwrap_or_default(), info.name, info.picture.map(|p| p.data.url), provider)
+        }
+        "discord" => {
+            let info: DiscordUserInfo = serde_json::from_str(body)?;
+            let (name, avatar) = (info.get_display_name(), info.get_avatar_url());
+            let email = match info.email {
+                Some(email) if !info.verified => anyhow::bail!("Discord has not verified {email}"),
+                email => email.unwrap_or_default(),
+            };
+            UserSession::new(info.id, email, name, avatar, provider)
+        }
+        other => anyhow::bail!("No userinfo mapping for provider '{}'", other),
+    };
+    Ok(session)
+}
+
+use anyhow::Context;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_provider(base: &str, pkce_enabled: bool) -> OAuthProvider {
+        OAuthProvider {
+            name: "Mock".to_string(),
+            client_id: "client-123".to_string(),
+            client_secret: "secret-456".to_string(),
+            authorization_endpoint: format!("{base}/authorize"),
+            token_endpoint: format!("{base}/token"),
+            userinfo_endpoint: format!("{base}/user"),
+            issuer: None,
+            scopes: vec!["openid".to_string(), "email".to_string()],
+            pkce_enabled,
+            response_type: "code".to_string(),
+            grant_type: "authorization_code".to_string(),
+            fields: None,
+            demo_user: None,
+        }
+    }
+
+    #[test]
+    fn state_must_match_and_be_provider_bound() {
+        let timeout = Duration::from_secs(600);
+
+        let (pending, challenge) = PendingAuthorization::begin("google", true);
+        assert!(challenge.is_some());
+        let state = pending.state().to_string();
+        assert!(pending.redeem(&state, "google", timeout).unwrap().is_some());
+
+        let (pending, challenge) = PendingAuthorization::begin("github", false);
+        assert!(challenge.is_none());
+        assert_eq!(pending.redeem("forged", "github", timeout), Err(StateError::Unknown));
+
+        let (pending, _) = PendingAuthorization::begin("github", false);
+        let state = pending.state().to_string();
+        assert_eq!(pending.redeem(&state, "google", timeout), Err(StateError::ProviderMismatch));
+    }
+
+    #[test]
+    fn state_expires_after_timeout() {
+        let (pending, _) = PendingAuthorization::begin("google", false);
+        let state = pending.state().to_string();
+        assert_eq!(pending.redeem(&state, "google", Duration::ZERO), Err(StateError::Expired));
+    }
+
+    #[test]
+    fn authorization_url_carries_pkce_challenge() {
+        let provider = mock_provider("https://auth.example", true);
+        let url = provider.authorization_url("http://localhost/cb", "abc", Some("xyz"));
+        assert!(url.starts_with("https://auth.example/authorize?client_id=client-123"));
+        assert!(url.contains("scope=openid%20email"));
+        assert!(url.ends_with("&state=abc&code_challenge=xyz&code_challenge_method=S256"));
+    }
+
+    #[tokio::test]
+    async fn exchanges_code_and_fetches_userinfo_from_mock_server() {
+        let mut server = mockito::Server::new_async().await;
+        let token = server
+            .mock("POST", "/token")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("code".into(), "auth-code".into()),
+                mockito::Matcher::UrlEncoded("code_verifier".into(), "verifier".into()),
+                mockito::Matcher::UrlEncoded("client_secret".into(), "secret-456".into()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"access_token":"tok-789","token_type":"Bearer","expires_in":3600}"#)
+            .create_async()
+            .await;
+        let userinfo = server
+            .mock("GET", "/user")
+            .match_header("authorization", "Bearer tok-789")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"42","email":"ada@example.com","verified_email":true,"name":"Ada Lovelace","picture":null}"#)
+            .create_async()
+            .await;
+
+        let client = reqwest::Client::new();
+        let provider = mock_provider(&server.url(), true);
+        let tokens = exchange_code(&client, &provider, "auth-code", "http://localhost/cb", Some("verifier"))
+            .await
+            .unwrap();
+        assert_eq!(tokens.access_token, "tok-789");
+
+        let session = fetch_user_session(&client, "google", &provider, &tokens.access_token).await.unwrap();
+        assert_eq!((session.user_id.as_str(), session.email.as_str(), session.provider.as_str()), ("42", "ada@example.com", "google"));
+        token.assert_async().await;
+        userinfo.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn token_errors_reported_with_200_status_are_rejected() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/token")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"error":"bad_verification_code","error_description":"The code is incorrect or expired."}"#)
+            .create_async()
+            .await;
+
+        let provider = mock_provider(&server.url(), false);
+        let err = exchange_code(&reqwest::Client::new(), &provider, "stale", "http://localhost/cb", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("bad_verification_code"));
+    }
+
+    #[tokio::test]
+    async fn github_private_email_falls_back_to_emails_endpoint() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/user")
+            .with_body(r#"{"id":7,"login":"octo","name":null,"email":null,"avatar_url":"https://avatars.example/7"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/user/emails")
+            .with_body(r#"[{"email":"old@example.com","primary":false,"verified":true},{"email":"octo@example.com","primary":true,"verified":true}]"#)
+            .create_async()
+            .await;
+
+        let provider = mock_provider(&server.url(), false);
+        let session = fetch_user_session(&reqwest::Client::new(), "github", &provider, "tok").await.unwrap();
+        assert_eq!((session.user_id.as_str(), session.name.as_str(), session.email.as_str()), ("7", "octo", "octo@example.com"));
+    }
+
+    #[test]
+    fn maps_discord_and_microsoft_userinfo() {
+        let discord = session_from_userinfo(
+            "discord",
+            r#"{"id":"1","username":"pat","email":"pat@example.com","verified":true,"avatar":"abc","discriminator":"0","global_name":"Pat"}"#,
+        )
+        .unwrap();
+        assert_eq!(discord.name, "Pat");
+        assert_eq!(discord.picture.as_deref(), Some("https://cdn.discordapp.com/avatars/1/abc.png"));
+
+        let microsoft = session_from_userinfo(
+            "microsoft",
+            r#"{"id":"m1","displayName":"Sam","userPrincipalName":"sam@tenant.onmicrosoft.com","mail":"sam@example.com"}"#,
+        )
+        .unwrap();
+        assert_eq!((microsoft.name.as_str(), microsoft.email.as_str()), ("Sam", ""));
+        assert!(session_from_userinfo("myspace", "{}").is_err());
+    }
+
+    #[test]
+    fn unverified_emails_are_refused() {
+        let google = r#"{"id":"42","email":"ada@example.com","verified_email":false,"name":"Ada"}"#;
+        assert!(session_from_userinfo("google", google).is_err());
+        let oidc = r#"{"id":"42","email":"ada@example.com","email_verified":true,"name":"Ada"}"#;
+        assert_eq!(session_from_userinfo("google", oidc).unwrap().email, "ada@example.com");
+
+        let discord = r#"{"id":"1","username":"pat","email":"pat@example.com","verified":false,"avatar":null,"discriminator":"0"}"#;
+        assert!(session_from_userinfo("discord", discord).is_err());
+        let no_email = r#"{"id":"1","username":"pat","email":null,"avatar":null,"discriminator":"0"}"#;
+        assert_eq!(session_from_userinfo("discord", no_email).unwrap().email, "");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,655 @@
+// src/pg_json.rs
+//! Postgres values to JSON for every endpoint that returns arbitrary rows.
+//!
+//! Values are decoded from the wire bytes by type OID rather than through `try_get`, so types
+//! sqlx has no Rust mapping for (arrays, enums, composites, ranges, inet, money, ...) still come
+//! back as data instead of null. NUMERIC and MONEY are returned as exact decimal strings,
+//! BYTEA as base64, and anything unrecognised as its UTF-8 text (or base64 if it isn't text).
+use base64::Engine;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime};
+use serde_json::{json, Map, Number, Value};
+use sqlx::postgres::{PgRow, PgTypeInfo, PgTypeKind, PgValueFormat};
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// Built-in type OIDs (pg_type.dat); these are fixed across Postgres versions
+const BOOL: u32 = 16;
+const BYTEA: u32 = 17;
+const CHAR: u32 = 18;
+const INT8: u32 = 20;
+const INT2: u32 = 21;
+const INT4: u32 = 23;
+const OID: u32 = 26;
+const JSON: u32 = 114;
+const CIDR: u32 = 650;
+const FLOAT4: u32 = 700;
+const FLOAT8: u32 = 701;
+const MACADDR8: u32 = 774;
+const MONEY: u32 = 790;
+const MACADDR: u32 = 829;
+const INET: u32 = 869;
+const DATE: u32 = 1082;
+const TIME: u32 = 1083;
+const TIMESTAMP: u32 = 1114;
+const TIMESTAMPTZ: u32 = 1184;
+const INTERVAL: u32 = 1186;
+const TIMETZ: u32 = 1266;
+const BIT: u32 = 1560;
+const VARBIT: u32 = 1562;
+const NUMERIC: u32 = 1700;
+const RECORD: u32 = 2249;
+const UUID: u32 = 2950;
+const JSONB: u32 = 3802;
+const TSVECTOR: u32 = 3614;
+const POINT: u32 = 600;
+const LSEG: u32 = 601;
+const PATH: u32 = 602;
+const BOX: u32 = 603;
+const POLYGON: u32 = 604;
+const LINE: u32 = 628;
+const CIRCLE: u32 = 718;
+/// regproc, regclass, regtype, ... are OIDs on the wire
+const REG_TYPES: [u32; 10] = [24, 2202, 2203, 2204, 2205, 2206, 3734, 3769, 4089, 4096];
+
+/// Convert a row into a JSON object keyed by column name
+pub fn row_to_json(row: &PgRow) -> Value {
+    let mut row_map = Map::new();
+    for (i, column) in row.columns().iter().enumerate() {
+        row_map.insert(column.name().to_string(), column_to_json(row, i));
+    }
+    Value::Object(row_map)
+}
+
+/// Convert one column of a row to JSON based on its Postgres type
+pub fn column_to_json(row: &PgRow, i: usize) -> Value {
+    let raw = match row.try_get_raw(i) {
+        Ok(raw) if !raw.is_null() => raw,
+        _ => return Value::Null,
+    };
+    match raw.as_bytes() {
+        Ok(bytes) => value_to_json(row.columns()[i].type_info(), raw.format(), bytes),
+        Err(_) => Value::Null,
+    }
+}
+
+/// Decode a non-null value of type `ty` from its wire bytes
+pub fn value_to_json(ty: &PgTypeInfo, format: PgValueFormat, bytes: &[u8]) -> Value {
+    match ty.kind() {
+        PgTypeKind::Domain(base) => return value_to_json(base, format, bytes),
+        PgTypeKind::Enum(_) => return text_or_base64(bytes),
+        _ => {}
+    }
+    let oid = ty.oid().map(|oid| oid.0);
+    if format == PgValueFormat::Text {
+        return decode_text(oid, bytes);
+    }
+
+    let decoded = match ty.kind() {
+        PgTypeKind::Array(element) => decode_array(bytes, Some(element)),
+        PgTypeKind::Composite(fields) => decode_record(bytes, Some(fields)),
+        PgTypeKind::Range(element) => decode_range(bytes, element),
+        _ => match oid {
+            Some(oid) => decode_binary(oid, bytes),
+            None => Ok(text_or_base64(bytes)),
+        },
+    };
+    decoded.unwrap_or_else(|e| {
+        log::warn!("Could not decode {} value: {e}", ty.name());
+        text_or_base64(bytes)
+    })
+}
+
+type Decoded = Result<Value, String>;
+
+/// Big-endian reader over a value's bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err(format!("expected {n} more bytes, found {}", self.bytes.len()));
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.array::<1>()?[0])
+    }
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+    fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_be_bytes(self.array()?))
+    }
+
+    /// A geometric point as `(x,y)`
+    fn point(&mut self) -> Result<String, String> {
+        Ok(format!("({},{})", self.f64()?, self.f64()?))
+    }
+
+    /// A length-prefixed value; None for SQL NULL (length -1)
+    fn value(&mut self) -> Result<Option<&'a [u8]>, String> {
+        let len = self.i32()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        self.take(len as usize).map(Some)
+    }
+}
+
+fn reader(bytes: &[u8]) -> Reader<'_> {
+    Reader { bytes }
+}
+
+/// Fallback for types without a decoder: text-like values as-is, binary payloads as base64
+fn text_or_base64(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r')) => json!(text),
+        _ => json!(base64::engine::general_purpose::STANDARD.encode(bytes)),
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Floats that JSON can't represent (NaN, ±Infinity) come back as Postgres spells them
+fn float(v: f64) -> Value {
+    match Number::from_f64(v) {
+        Some(n) => Value::Number(n),
+        None if v.is_nan() => json!("NaN"),
+        None if v > 0.0 => json!("Infinity"),
+        None => json!("-Infinity"),
+    }
+}
+
+fn decode_text(oid: Option<u32>, bytes: &[u8]) -> Value {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return text_or_base64(bytes);
+    };
+    let parsed = match oid {
+        Some(BOOL) => Some(json!(text == "t")),
+        Some(INT2 | INT4 | INT8 | OID) => text.parse::<i64>().ok().map(|v| json!(v)),
+        Some(FLOAT4 | FLOAT8) => text.parse::<f64>().ok().map(float),
+        Some(JSON | JSONB) => serde_json::from_str(text).ok(),
+        Some(BYTEA) => text.strip_prefix("\\x").and_then(|hex| {
+            let bytes: Option<Vec<u8>> = (0..hex.len())
+                .step_by(2)
+                .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+                .collect();
+            bytes.map(|b| json!(base64::engine::general_purpose::STANDARD.encode(b)))
+        }),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| json!(text))
+}
+
+fn decode_binary(oid: u32, bytes: &[u8]) -> Decoded {
+    let mut r = reader(bytes);
+    Ok(match oid {
+        BOOL => json!(r.u8()? != 0),
+        BYTEA => json!(base64::engine::general_purpose::STANDARD.encode(bytes)),
+        CHAR => json!((r.u8()? as char).to_string()),
+        INT2 => json!(r.i16()?),
+        INT4 => json!(r.i32()?),
+        INT8 => json!(r.i64()?),
+        OID => json!(r.u32()?),
+        oid if REG_TYPES.contains(&oid) => json!(r.u32()?),
+        // Via the shortest f32 spelling so 1.1 stays 1.1 rather than 1.100000023841858
+        FLOAT4 => float(f32::from_be_bytes(r.array()?).to_string().parse().unwrap_or(f64::NAN)),
+        FLOAT8 => float(r.f64()?),
+        NUMERIC => json!(decode_numeric(bytes)?),
+        MONEY => json!(format_money(r.i64()?)),
+        JSON => serde_json::from_str(utf8(bytes)?).map_err(|e| e.to_string())?,
+        JSONB => {
+            // Version byte, then the JSON text
+            r.u8()?;
+            serde_json::from_str(utf8(r.bytes)?).map_err(|e| e.to_string())?
+        }
+        UUID => json!(uuid::Uuid::from_slice(bytes).map_err(|e| e.to_string())?.to_string()),
+        DATE => json!(format_date(r.i32()?)),
+        TIME => json!(format_time(r.i64()?)),
+        TIMETZ => {
+            let time = format_time(r.i64()?);
+            // Stored as seconds west of UTC
+            json!(format!("{time}{}", format_offset(-r.i32()?)))
+        }
+        TIMESTAMP => json!(format_timestamp(r.i64()?, false)),
+        TIMESTAMPTZ => json!(format_timestamp(r.i64()?, true)),
+        INTERVAL => {
+            let micros = r.i64()?;
+            let days = r.i32()?;
+            let months = r.i32()?;
+            json!(format_interval(months, days, micros))
+        }
+        INET | CIDR => json!(decode_inet(bytes)?),
+        MACADDR | MACADDR8 => json!(bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(":")),
+        BIT | VARBIT => {
+            let len = r.i32()? as usize;
+            let bits: String = r.bytes.iter().flat_map(|b| (0..8).rev().map(move |i| if b >> i & 1 == 1 { '1' } else { '0' })).take(len).collect();
+            json!(bits)
+        }
+        RECORD => decode_record(bytes, None)?,
+        TSVECTOR => json!(decode_tsvector(bytes)?),
+        POINT | LSEG | PATH | BOX | POLYGON | LINE | CIRCLE => json!(decode_geometry(oid, bytes)?),
+        _ => text_or_base64(bytes),
+    })
+}
+
+/// Element decoding inside arrays and records, where only the OID is on the wire
+fn decode_element(oid: u32, known: Option<&PgTypeInfo>, bytes: &[u8]) -> Value {
+    match known {
+        Some(ty) if ty.oid().map(|o| o.0) == Some(oid) || ty.oid().is_none() => value_to_json(ty, PgValueFormat::Binary, bytes),
+        _ => decode_binary(oid, bytes).unwrap_or_else(|_| text_or_base64(bytes)),
+    }
+}
+
+fn decode_array(bytes: &[u8], element: Option<&PgTypeInfo>) -> Decoded {
+    let mut r = reader(bytes);
+    let ndim = r.i32()?;
+    let _has_nulls = r.i32()?;
+    let element_oid = r.u32()?;
+    if ndim <= 0 {
+        return Ok(json!([]));
+    }
+    let mut dims = Vec::with_capacity(ndim as usize);
+    for _ in 0..ndim {
+        dims.push(r.i32()?.max(0) as usize);
+        let _lower_bound = r.i32()?;
+    }
+
+    fn build(r: &mut Reader<'_>, dims: &[usize], oid: u32, element: Option<&PgTypeInfo>) -> Decoded {
+        let mut items = Vec::with_capacity(dims[0]);
+        for _ in 0..dims[0] {
+            items.push(if dims.len() > 1 {
+                build(r, &dims[1..], oid, element)?
+            } else {
+                match r.value()? {
+                    Some(bytes) => decode_element(oid, element, bytes),
+                    None => Value::Null,
+                }
+            });
+        }
+        Ok(Value::Array(items))
+    }
+    build(&mut r, &dims, element_oid, element)
+}
+
+/// Composite values become objects when the field names are known, arrays otherwise
+fn decode_record(bytes: &[u8], fields: Option<&[(String, PgTypeInfo)]>) -> Decoded {
+    let mut r = reader(bytes);
+    let count = r.i32()?.max(0) as usize;
+    let mut values = Vec::with_capacity(count);
+    for i in 0..count {
+        let oid = r.u32()?;
+        let known = fields.and_then(|f| f.get(i)).map(|(_, ty)| ty);
+        values.push(match r.value()? {
+            Some(bytes) => decode_element(oid, known, bytes),
+            None => Value::Null,
+        });
+    }
+    Ok(match fields {
+        Some(fields) if fields.len() == values.len() => {
+            Value::Object(fields.iter().map(|(name, _)| name.clone()).zip(values).collect())
+        }
+        _ => Value::Array(values),
+    })
+}
+
+/// Ranges in Postgres' own text form, e.g. `[2020-01-01,2021-01-01)`
+fn decode_range(bytes: &[u8], element: &PgTypeInfo) -> Decoded {
+    const EMPTY: u8 = 0x01;
+    const LOWER_INCLUSIVE: u8 = 0x02;
+    const UPPER_INCLUSIVE: u8 = 0x04;
+    const LOWER_INFINITE: u8 = 0x08;
+    const UPPER_INFINITE: u8 = 0x10;
+
+    let mut r = reader(bytes);
+    let flags = r.u8()?;
+    if flags & EMPTY != 0 {
+        return Ok(json!("empty"));
+    }
+    let mut bound = |infinite: bool| -> Result<String, String> {
+        if infinite {
+            return Ok(String::new());
+        }
+        let bytes = r.value()?.ok_or("null range bound")?;
+        Ok(match value_to_json(element, PgValueFormat::Binary, bytes) {
+            Value::String(s) => s,
+            other => other.to_string(),
+        })
+    };
+    let lower = bound(flags & LOWER_INFINITE != 0)?;
+    let upper = bound(flags & UPPER_INFINITE != 0)?;
+    Ok(json!(format!(
+        "{}{lower},{upper}{}",
+        if flags & LOWER_INCLUSIVE != 0 { '[' } else { '(' },
+        if flags & UPPER_INCLUSIVE != 0 { ']' } else { ')' },
+    )))
+}
+
+/// tsvector in its text form: `'fat':2 'rat':3,5A`
+fn decode_tsvector(bytes: &[u8]) -> Result<String, String> {
+    const WEIGHTS: [&str; 4] = ["", "C", "B", "A"];
+    let mut r = reader(bytes);
+    let count = r.i32()?.max(0) as usize;
+    let mut lexemes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let end = r.bytes.iter().position(|b| *b == 0).ok_or("unterminated lexeme")?;
+        let word = utf8(r.take(end)?)?.replace('\'', "''");
+        r.take(1)?;
+        let positions = (0..r.u16()?)
+            .map(|_| r.u16().map(|p| format!("{}{}", p & 0x3FFF, WEIGHTS[(p >> 14) as usize])))
+            .collect::<Result<Vec<_>, _>>()?;
+        lexemes.push(if positions.is_empty() {
+            format!("'{word}'")
+        } else {
+            format!("'{word}':{}", positions.join(","))
+        });
+    }
+    Ok(lexemes.join(" "))
+}
+
+/// Geometric types in Postgres' text forms, e.g. `(1,2)`, `((0,0),(1,1))`, `<(0,0),2>`
+fn decode_geometry(oid: u32, bytes: &[u8]) -> Result<String, String> {
+    let mut r = reader(bytes);
+    Ok(match oid {
+        POINT => r.point()?,
+        LSEG => format!("[{},{}]", r.point()?, r.point()?),
+        BOX => format!("{},{}", r.point()?, r.point()?),
+        LINE => format!("{{{},{},{}}}", r.f64()?, r.f64()?, r.f64()?),
+        CIRCLE => format!("<{},{}>", r.point()?, r.f64()?),
+        PATH | POLYGON => {
+            let closed = oid == POLYGON || r.u8()? != 0;
+            let points = (0..r.i32()?.max(0)).map(|_| r.point()).collect::<Result<Vec<_>, _>>()?.join(",");
+            if closed { format!("({points})") } else { format!("[{points}]") }
+        }
+        _ => return Err(format!("not a geometric type: {oid}")),
+    })
+}
+
+/// NUMERIC's base-10000 binary form as an exact decimal string
+fn decode_numeric(bytes: &[u8]) -> Result<String, String> {
+    const NEGATIVE: u16 = 0x4000;
+    const NAN: u16 = 0xC000;
+    const POSITIVE_INFINITY: u16 = 0xD000;
+    const NEGATIVE_INFINITY: u16 = 0xF000;
+
+    let mut r = reader(bytes);
+    let ndigits = r.i16()?.max(0) as usize;
+    let weight = r.i16()? as i32;
+    let sign = r.u16()?;
+    let dscale = r.u16()? as usize;
+    let digits = (0..ndigits).map(|_| r.i16()).collect::<Result<Vec<_>, _>>()?;
+
+    match sign {
+        NAN => return Ok("NaN".to_string()),
+        POSITIVE_INFINITY => return Ok("Infinity".to_string()),
+        NEGATIVE_INFINITY => return Ok("-Infinity".to_string()),
+        _ => {}
+    }
+    // Group k is worth 10000^(weight - k); groups outside the stored digits are zero
+    let group = |k: i32| if k >= 0 && (k as usize) < digits.len() { digits[k as usize] } else { 0 };
+
+    let mut out = String::new();
+    if sign == NEGATIVE && digits.iter().any(|d| *d != 0) {
+        out.push('-');
+    }
+    if weight < 0 {
+        out.push('0');
+    } else {
+        for k in 0..=weight {
+            if k == 0 {
+                out.push_str(&group(k).to_string());
+            } else {
+                out.push_str(&format!("{:04}", group(k)));
+            }
+        }
+    }
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut k = weight + 1;
+        while fraction.len() < dscale {
+            fraction.push_str(&format!("{:04}", group(k)));
+            k += 1;
+        }
+        fraction.truncate(dscale);
+        out.push('.');
+        out.push_str(&fraction);
+    }
+    Ok(out)
+}
+
+/// MONEY is a count of cents (assuming a two-digit lc_monetary, as in the default locale)
+fn format_money(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    format!("{sign}{}.{:02}", cents / 100, cents % 100)
+}
+
+fn pg_epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date").and_hms_opt(0, 0, 0).expect("valid time")
+}
+
+fn format_date(days: i32) -> String {
+    match days {
+        i32::MAX => "infinity".to_string(),
+        i32::MIN => "-infinity".to_string(),
+        days => match pg_epoch().date().checked_add_signed(Duration::days(days as i64)) {
+            Some(date) => date.to_string(),
+            None => pg_date_text(days as i64),
+        },
+    }
+}
+
+/// Postgres' text form of a date past chrono's last year (262143); Postgres allows up to 5874897.
+/// The arithmetic is Postgres' own j2date().
+fn pg_date_text(days: i64) -> String {
+    const POSTGRES_EPOCH_JDATE: i64 = 2_451_545;
+    let mut julian = days + POSTGRES_EPOCH_JDATE + 32_044;
+    let mut quad = julian / 146_097;
+    let extra = (julian - quad * 146_097) * 4 + 3;
+    julian += 60 + quad * 3 + extra / 146_097;
+    quad = julian / 1461;
+    julian -= quad * 1461;
+    let mut year = julian * 4 / 1461;
+    julian = if year != 0 { (julian + 305) % 365 } else { (julian + 306) % 366 } + 123;
+    year += quad * 4;
+    let quad = julian * 2141 / 65_536;
+    let (day, month) = (julian - 7834 * quad / 256, (quad + 10) % 12 + 1);
+    format!("{:04}-{month:02}-{day:02}", year - 4800)
+}
+
+/// Seconds with their fraction, trailing zeros dropped: `6`, `6.5`, `6.000123`
+fn format_seconds(micros: u64) -> String {
+    let (seconds, fraction) = (micros / 1_000_000, micros % 1_000_000);
+    if fraction == 0 {
+        seconds.to_string()
+    } else {
+        format!("{seconds}.{fraction:06}").trim_end_matches('0').to_string()
+    }
+}
+
+fn format_time(micros: i64) -> String {
+    let micros = micros.max(0) as u64;
+    let (hours, minutes) = (micros / 3_600_000_000, micros / 60_000_000 % 60);
+    let seconds = format_seconds(micros % 60_000_000);
+    let pad = if seconds.find('.').unwrap_or(seconds.len()) < 2 { "0" } else { "" };
+    format!("{hours:02}:{minutes:02}:{pad}{seconds}")
+}
+
+fn format_offset(seconds_east: i32) -> String {
+    let sign = if seconds_east < 0 { '-' } else { '+' };
+    let seconds = seconds_east.unsigned_abs();
+    format!("{sign}{:02}:{:02}", seconds / 3600, seconds / 60 % 60)
+}
+
+/// Same shapes the API has always returned: RFC 3339 for timestamptz, `YYYY-MM-DD HH:MM:SS` otherwise
+fn format_timestamp(micros: i64, with_zone: bool) -> String {
+    match micros {
+        i64::MAX => return "infinity".to_string(),
+        i64::MIN => return "-infinity".to_string(),
+        _ => {}
+    }
+    let Some(local) = pg_epoch().checked_add_signed(Duration::microseconds(micros)) else {
+        let (days, time) = (micros.div_euclid(86_400_000_000), micros.rem_euclid(86_400_000_000));
+        let zone = if with_zone { "+00" } else { "" };
+        return format!("{} {}{zone}", pg_date_text(days), format_time(time));
+    };
+    if with_zone {
+        DateTime::<chrono::Utc>::from_naive_utc_and_offset(local, chrono::Utc).to_rfc3339()
+    } else {
+        local.to_string()
+    }
+}
+
+/// ISO 8601 duration, signed per component like Postgres' `iso_8601` interval style
+fn format_interval(months: i32, days: i32, micros: i64) -> String {
+    let mut out = String::from("P");
+    let (years, months) = (months / 12, months % 12);
+    for (value, unit) in [(years as i64, 'Y'), (months as i64, 'M'), (days as i64, 'D')] {
+        if value != 0 {
+            out.push_str(&format!("{value}{unit}"));
+        }
+    }
+    if micros != 0 {
+        out.push('T');
+        let sign = if micros < 0 { "-" } else { "" };
+        let abs = micros.unsigned_abs();
+        let (hours, minutes, rest) = (abs / 3_600_000_000, abs / 60_000_000 % 60, abs % 60_000_000);
+        if hours != 0 {
+            out.push_str(&format!("{sign}{hours}H"));
+        }
+        if minutes != 0 {
+            out.push_str(&format!("{sign}{minutes}M"));
+        }
+        if rest != 0 {
+            out.push_str(&format!("{sign}{}S", format_seconds(rest)));
+        }
+    }
+    if out == "P" {
+        out.push_str("T0S");
+    }
+    out
+}
+
+/// inet/cidr: address plus prefix, which inet leaves off when it covers the whole address
+fn decode_inet(bytes: &[u8]) -> Result<String, String> {
+    const AF_INET: u8 = 2;
+    let mut r = reader(bytes);
+    let family = r.u8()?;
+    let bits = r.u8()?;
+    let is_cidr = r.u8()? != 0;
+    let len = r.u8()? as usize;
+    let address = r.take(len)?;
+    let (address, full) = if family == AF_INET {
+        let octets: [u8; 4] = address.try_into().map_err(|_| "bad IPv4 address")?;
+        (Ipv4Addr::from(octets).to_string(), 32)
+    } else {
+        let octets: [u8; 16] = address.try_into().map_err(|_| "bad IPv6 address")?;
+        (Ipv6Addr::from(octets).to_string(), 128)
+    };
+    Ok(if is_cidr || bits != full { format!("{address}/{bits}") } else { address })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(weight: i16, sign: u16, dscale: u16, digits: &[i16]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend((digits.len() as i16).to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(sign.to_be_bytes());
+        bytes.extend(dscale.to_be_bytes());
+        for d in digits {
+            bytes.extend(d.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn numeric_keeps_every_digit() {
+        // 12345678901234567890.123456 as DECIMAL(26,6)
+        let big = numeric(4, 0, 6, &[1234, 5678, 9012, 3456, 7890, 1234, 5600]);
+        assert_eq!(decode_numeric(&big).unwrap(), "12345678901234567890.123456");
+        // -0.0000012345 as NUMERIC(20,10)
+        assert_eq!(decode_numeric(&numeric(-2, 0x4000, 10, &[123, 4500])).unwrap(), "-0.0000012345");
+        assert_eq!(decode_numeric(&numeric(0, 0, 2, &[])).unwrap(), "0.00");
+        assert_eq!(decode_numeric(&numeric(1, 0, 0, &[1])).unwrap(), "10000");
+        assert_eq!(decode_numeric(&numeric(0, 0xC000, 0, &[])).unwrap(), "NaN");
+    }
+
+    #[test]
+    fn arrays_nest_by_dimension() {
+        // '{{1,NULL},{3,4}}'::int4[]
+        let mut bytes = Vec::new();
+        for v in [2i32, 1, INT4 as i32, 2, 1, 2, 1] {
+            bytes.extend(v.to_be_bytes());
+        }
+        for v in [Some(1i32), None, Some(3), Some(4)] {
+            match v {
+                Some(v) => {
+                    bytes.extend(4i32.to_be_bytes());
+                    bytes.extend(v.to_be_bytes());
+                }
+                None => bytes.extend((-1i32).to_be_bytes()),
+            }
+        }
+        assert_eq!(decode_array(&bytes, None).unwrap(), json!([[1, null], [3, 4]]));
+        assert_eq!(decode_array(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 23], None).unwrap(), json!([]));
+    }
+
+    #[test]
+    fn formats_binary_scalars() {
+        let be = |parts: &[&[u8]]| parts.concat();
+        assert_eq!(decode_binary(BYTEA, b"hi!").unwrap(), json!("aGkh"));
+        assert_eq!(decode_binary(MONEY, &(-123456i64).to_be_bytes()).unwrap(), json!("-1234.56"));
+        assert_eq!(
+            decode_binary(INTERVAL, &be(&[&3_723_500_000i64.to_be_bytes(), &3i32.to_be_bytes(), &14i32.to_be_bytes()])).unwrap(),
+            json!("P1Y2M3DT1H2M3.5S")
+        );
+        assert_eq!(decode_binary(INTERVAL, &[0; 16]).unwrap(), json!("PT0S"));
+        assert_eq!(decode_binary(TIME, &45_296_000_001i64.to_be_bytes()).unwrap(), json!("12:34:56.000001"));
+        assert_eq!(
+            decode_binary(TIMETZ, &be(&[&3_600_000_000i64.to_be_bytes(), &(-19800i32).to_be_bytes()])).unwrap(),
+            json!("01:00:00+05:30")
+        );
+        assert_eq!(decode_binary(DATE, &(-1i32).to_be_bytes()).unwrap(), json!("1999-12-31"));
+        assert_eq!(decode_binary(TIMESTAMPTZ, &0i64.to_be_bytes()).unwrap(), json!("2000-01-01T00:00:00+00:00"));
+        // Past chrono's year 262143, as Postgres prints them
+        assert_eq!(decode_binary(DATE, &2_145_031_948i32.to_be_bytes()).unwrap(), json!("5874897-12-31"));
+        assert_eq!(decode_binary(DATE, &95_015_645i32.to_be_bytes()).unwrap(), json!("262144-01-01"));
+        assert_eq!(
+            decode_binary(TIMESTAMPTZ, &9_223_371_331_199_500_000i64.to_be_bytes()).unwrap(),
+            json!("294276-12-31 23:59:59.5+00")
+        );
+        assert_eq!(decode_binary(DATE, &i32::MAX.to_be_bytes()).unwrap(), json!("infinity"));
+        assert_eq!(decode_binary(INET, &[2, 32, 0, 4, 10, 0, 0, 1]).unwrap(), json!("10.0.0.1"));
+        assert_eq!(decode_binary(CIDR, &[2, 8, 1, 4, 10, 0, 0, 0]).unwrap(), json!("10.0.0.0/8"));
+        assert_eq!(decode_binary(MACADDR, &[8, 0, 43, 1, 2, 3]).unwrap(), json!("08:00:2b:01:02:03"));
+        assert_eq!(decode_binary(VARBIT, &[0, 0, 0, 5, 0b1011_0000]).unwrap(), json!("10110"));
+        assert_eq!(decode_binary(FLOAT8, &f64::NAN.to_be_bytes()).unwrap(), json!("NaN"));
+        assert_eq!(decode_text(Some(BYTEA), b"\\x686921"), json!("aGkh"));
+    }
+}
@@ -0,0 +1,248 @@
+// src/project_attributes.rs
+//! Structured project attributes (migration 0008) and `partner_tools backfill-project-attributes`.
+//!
+//! Imports used to append department, region, country, framework, NAICS sector and profile URL
+//! to a project's `description` as "Label: value" blocks. The backfill moves those blocks of
+//! existing records into the columns, one transaction per project with its history. A block is
+//! left in the description when the column already holds a different value or the value doesn't
+//! fit, so nothing is lost. Fiscal year, project number and committed amount were never in the
+//! description and can only be filled by importing the workbook again with `dedup.action: merge`.
+//! Run the command once before re-importing with `dedup.action: merge`, because that import
+//! matches on region and department and would not recognize records whose values are still in
+//! the description.
+use serde_json::{json, Map, Value};
+use sqlx::{Pool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::crm::{validate_fields, EntityInput, ProjectInput};
+use crate::history::{self, FieldChange};
+use crate::schema::quote_ident;
+
+/// `(column, label)` of the blocks imports appended to descriptions. DemocracyLab imports
+/// wrote their link as "Project URL".
+const LABELS: &[(&str, &str)] = &[
+    ("department", "Department"),
+    ("region", "Region"),
+    ("country", "Country"),
+    ("framework", "Framework"),
+    ("naics_sector", "NAICS Sector"),
+    ("profile_url", "Profile URL"),
+    ("profile_url", "Project URL"),
+];
+
+/// NAICS 2022 sectors as `(code, title)`
+const NAICS_SECTORS: &[(&str, &str)] = &[
+    ("11", "Agriculture, Forestry, Fishing and Hunting"),
+    ("21", "Mining, Quarrying, and Oil and Gas Extraction"),
+    ("22", "Utilities"),
+    ("23", "Construction"),
+    ("31-33", "Manufacturing"),
+    ("42", "Wholesale Trade"),
+    ("44-45", "Retail Trade"),
+    ("48-49", "Transportation and Warehousing"),
+    ("51", "Information"),
+    ("52", "Finance and Insurance"),
+    ("53", "Real Estate and Rental and Leasing"),
+    ("54", "Professional, Scientific, and Technical Services"),
+    ("55", "Management of Companies and Enterprises"),
+    ("56", "Administrative and Support and Waste Management and Remediation Services"),
+    ("61", "Educational Services"),
+    ("62", "Health Care and Social Assistance"),
+    ("71", "Arts, Entertainment, and Recreation"),
+    ("72", "Accommodation and Food Services"),
+    ("81", "Other Services (except Public Administration)"),
+    ("92", "Public Administration"),
+];
+
+/// Recorded as `created_by` on the history rows the backfill writes
+const SOURCE: &str = "attribute-backfill";
+
+/// Sector code for a NAICS sector title, or for a value that already is a sector code
+pub fn naics_code(sector: &str) -> Option<&'static str> {
+    let sector = sector.trim();
+    NAICS_SECTORS
+        .iter()
+        .find(|&&(code, title)| code == sector || title.eq_ignore_ascii_case(sector))
+        .map(|&(code, _)| code)
+}
+
+/// Year from a fiscal year cell such as "2021" or "FY2021"
+pub fn fiscal_year(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let year = value.strip_prefix("FY").or_else(|| value.strip_prefix("fy")).unwrap_or(value);
+    year.trim().parse().ok().filter(|y| (1900..=2200).contains(y))
+}
+
+/// A "Label: value" block split off a description
+#[derive(Debug, PartialEq)]
+pub struct Attribute {
+    pub column: &'static str,
+    pub label: &'static str,
+    pub value: String,
+}
+
+fn parse_block(block: &str) -> Option<Attribute> {
+    let block = block.trim();
+    LABELS.iter().find_map(|&(column, label)| {
+        let value = block.strip_prefix(label)?.strip_prefix(": ")?.trim();
+        (!value.is_empty() && !value.contains('\n')).then(|| Attribute { column, label, value: value.to_string() })
+    })
+}
+
+/// Split the attribute blocks off the end of `description`, returning them in order with what is
+/// left of the description
+pub fn split_description(description: &str) -> (Vec<Attribute>, Option<String>) {
+    let mut blocks: Vec<&str> = description.split("\n\n").collect();
+    let mut attributes = Vec::new();
+    while let Some(attribute) = blocks.last().and_then(|block| parse_block(block)) {
+        attributes.push(attribute);
+        blocks.pop();
+    }
+    attributes.reverse();
+    let rest = blocks.join("\n\n").trim().to_string();
+    (attributes, (!rest.is_empty()).then_some(rest))
+}
+
+/// What `backfill` did, or would do with `dry_run`
+#[derive(Debug, Default)]
+pub struct BackfillCount {
+    /// Projects whose description had attribute blocks moved into columns
+    pub projects: u64,
+    /// Column values filled, including NAICS codes derived from the sector
+    pub values: u64,
+    /// Blocks left in descriptions because the column held another value or the value didn't fit
+    pub kept: u64,
+}
+
+fn text(record: &Value, column: &str) -> Option<String> {
+    match record.get(column)? {
+        Value::Null => None,
+        Value::String(s) if s.trim().is_empty() => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// The columns and description to write for `existing`, or `None` when its description has no
+/// attribute blocks that can be moved
+fn plan_record(existing: &Value, count: &mut BackfillCount) -> Option<Map<String, Value>> {
+    let (attributes, rest) = split_description(&text(existing, "description")?);
+    if attributes.is_empty() {
+        return None;
+    }
+    let mut patch = Map::new();
+    let mut kept = Vec::new();
+    for attribute in attributes {
+        let current = text(existing, attribute.column).or_else(|| patch.get(attribute.column).and_then(Value::as_str).map(str::to_string));
+        let fits = || {
+            let input: Option<ProjectInput> = serde_json::from_value(json!({ attribute.column: attribute.value })).ok();
+            input.is_some_and(|input| validate_fields(&input.into_fields(), &[], false).is_empty())
+        };
+        match current {
+            Some(current) if current == attribute.value => {}
+            None if fits() => {
+                patch.insert(attribute.column.to_string(), json!(attribute.value));
+                count.values += 1;
+            }
+            _ => {
+                kept.push(format!("{}: {}", attribute.label, attribute.value));
+                count.kept += 1;
+            }
+        }
+    }
+    if text(existing, "naics_code").is_none() {
+        let sector = text(existing, "naics_sector").or_else(|| patch.get("naics_sector").and_then(Value::as_str).map(str::to_string));
+        if let Some(code) = sector.as_deref().and_then(naics_code) {
+            patch.insert("naics_code".to_string(), json!(code));
+            count.values += 1;
+        }
+    }
+    if patch.is_empty() {
+        return None;
+    }
+    let description: Vec<String> = rest.into_iter().chain(kept).collect();
+    patch.insert("description".to_string(), if description.is_empty() { Value::Null } else { json!(description.join("\n\n")) });
+    count.projects += 1;
+    Some(patch)
+}
+
+/// Move attribute blocks out of the descriptions of all projects, soft-deleted ones included.
+/// With `dry_run`, only count them.
+pub async fn backfill(pool: &Pool<Postgres>, dry_run: bool) -> anyhow::Result<BackfillCount> {
+    let labels = LABELS.iter().map(|&(_, label)| label).collect::<Vec<_>>().join("|");
+    let records: Vec<Value> = sqlx::query_scalar("SELECT to_jsonb(t) FROM projects t WHERE t.description ~ $1 ORDER BY t.date_entered, t.id")
+        .bind(format!("({labels}): "))
+        .fetch_all(pool)
+        .await?;
+
+    let mut count = BackfillCount::default();
+    for existing in records {
+        let Some(patch) = plan_record(&existing, &mut count) else { continue };
+        if dry_run {
+            continue;
+        }
+        let id: Uuid = existing["id"].as_str().and_then(|id| id.parse().ok()).ok_or_else(|| anyhow::anyhow!("project without an id"))?;
+        let fields = serde_json::from_value::<ProjectInput>(Value::Object(patch.clone()))?.into_fields();
+        let list = fields.iter().map(|f| quote_ident(f.column)).collect::<Vec<_>>().join(", ");
+
+        let mut tx = pool.begin().await?;
+        let mut qb = QueryBuilder::<Postgres>::new(format!(
+            "UPDATE projects t SET ({list}) = (SELECT {list} FROM jsonb_populate_record(NULL::projects, "
+        ));
+        qb.push_bind(Value::Object(patch)).push(")) WHERE t.id = ").push_bind(id).push(" RETURNING to_jsonb(t)");
+        let after: Value = qb.build_query_scalar().fetch_one(&mut *tx).await?;
+        let changes: Vec<FieldChange> = history::changes(
+            &fields.iter().map(|f| (f.column, f.data_type())).collect::<Vec<_>>(),
+            &existing,
+            &after,
+        );
+        history::write(&mut tx, "projects", id, Some(SOURCE), &changes).await?;
+        tx.commit().await?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_blocks_are_split_off_the_end() {
+        let description = "Wind farm\n\nRegion: Africa is not a block here\nsecond line\n\nDepartment: Finance\n\nRegion: Africa\n\nNAICS Sector: Utilities";
+        let (attributes, rest) = split_description(description);
+        let columns: Vec<(&str, &str)> = attributes.iter().map(|a| (a.column, a.value.as_str())).collect();
+        assert_eq!(columns, vec![("department", "Finance"), ("region", "Africa"), ("naics_sector", "Utilities")]);
+        assert_eq!(rest.as_deref(), Some("Wind farm\n\nRegion: Africa is not a block here\nsecond line"));
+
+        let (attributes, rest) = split_description("Project URL: https://example.org/p/1");
+        assert_eq!(attributes[0].column, "profile_url");
+        assert_eq!(rest, None);
+        assert!(split_description("Plain text").0.is_empty());
+    }
+
+    #[test]
+    fn backfill_keeps_blocks_that_conflict_with_columns() {
+        let existing = json!({
+            "description": "About\n\nRegion: Africa\n\nCountry: Kenya\n\nNAICS Sector: Finance and Insurance",
+            "region": "MENA",
+            "country": null
+        });
+        let mut count = BackfillCount::default();
+        let patch = plan_record(&existing, &mut count).unwrap();
+        assert_eq!(patch["country"], "Kenya");
+        assert_eq!(patch["naics_sector"], "Finance and Insurance");
+        assert_eq!(patch["naics_code"], "52");
+        assert_eq!(patch["description"], "About\n\nRegion: Africa");
+        assert_eq!((count.projects, count.values, count.kept), (1, 3, 1));
+    }
+
+    #[test]
+    fn naics_codes_and_fiscal_years() {
+        assert_eq!(naics_code("finance and insurance"), Some("52"));
+        assert_eq!(naics_code("31-33"), Some("31-33"));
+        assert_eq!(naics_code("Redacted"), None);
+        assert_eq!(fiscal_year("FY2021"), Some(2021));
+        assert_eq!(fiscal_year(" 1961 "), Some(1961));
+        assert_eq!(fiscal_year("n/a"), None);
+    }
+}
@@ -0,0 +1,72 @@
+// src/purge.rs
+//! `partner_tools purge`: permanently remove soft-deleted CRM records.
+//!
+//! Each record goes in its own transaction together with its join-table links; its
+//! `{entity}_audit` history cascades. A record that another live record still points at
+//! (e.g. an account with contacts) is kept and counted, and can be purged once that is fixed.
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+/// Result of purging one table
+#[derive(Debug)]
+pub struct PurgeCount {
+    pub table: &'static str,
+    pub purged: u64,
+    /// Records left in place because other rows still reference them
+    pub kept: u64,
+    pub links_removed: u64,
+}
+
+fn is_foreign_key_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23503"))
+}
+
+/// Purge records of `tables` deleted before `cutoff`. With `dry_run`, only count them.
+pub async fn purge(
+    pool: &Pool<Postgres>,
+    tables: &[&'static str],
+    cutoff: DateTime<Utc>,
+    dry_run: bool,
+) -> anyhow::Result<Vec<PurgeCount>> {
+    let mut counts = Vec::new();
+    for &table in tables {
+        let ids: Vec<Uuid> = sqlx::query_scalar(&format!(
+            "SELECT id FROM {table} WHERE deleted AND deleted_at <= $1 ORDER BY deleted_at"
+        ))
+        .bind(cutoff)
+        .fetch_all(pool)
+        .await?;
+
+        let mut count = PurgeCount { table, purged: 0, kept: 0, links_removed: 0 };
+        if dry_run {
+            count.purged = ids.len() as u64;
+            counts.push(count);
+            continue;
+        }
+        for id in ids {
+            let mut tx = pool.begin().await?;
+            let links = crate::relationships::unlink_all(&mut tx, table, id).await?;
+            match sqlx::query(&format!("DELETE FROM {table} WHERE id = $1 AND deleted"))
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+            {
+                Ok(done) if done.rows_affected() > 0 => {
+                    tx.commit().await?;
+                    count.purged += 1;
+                    count.links_removed += links;
+                }
+                // Restored since it was selected
+                Ok(_) => tx.rollback().await?,
+                Err(e) if is_foreign_key_violation(&e) => {
+                    tx.rollback().await?;
+                    count.kept += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        counts.push(count);
+    }
+    Ok(counts)
+}
@@ -0,0 +1,372 @@
+// src/relationships.rs
+//! Link, unlink and list records through the many-to-many join tables and the
+//! polymorphic `taggables` table.
+//!
+//! Routes hang off each entity scope as `/{id}/{related}` and `/{id}/{related}/{related_id}`,
+//! so `/api/accounts/{id}/contacts` and `/api/contacts/{id}/accounts` read the same rows.
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Result};
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::crm::{db_error_response, db_pool, not_found, soft_deletes, CrmTable};
+use crate::auth::{require, Permission};
+use crate::ApiState;
+
+/// `users` has no CRUD endpoints yet, only its `users_roles` relationship
+pub struct Users;
+
+impl CrmTable for Users {
+    const TABLE: &'static str = "users";
+    const LABEL: &'static str = "User";
+}
+
+/// A join table linking `left_table.id` to `right_table.id`
+struct JoinTable {
+    table: &'static str,
+    left_table: &'static str,
+    left_column: &'static str,
+    right_table: &'static str,
+    right_column: &'static str,
+}
+
+const JOIN_TABLES: &[JoinTable] = &[
+    JoinTable { table: "accounts_contacts", left_table: "accounts", left_column: "account_id", right_table: "contacts", right_column: "contact_id" },
+    JoinTable { table: "accounts_opportunities", left_table: "accounts", left_column: "account_id", right_table: "opportunities", right_column: "opportunity_id" },
+    JoinTable { table: "contacts_opportunities", left_table: "contacts", left_column: "contact_id", right_table: "opportunities", right_column: "opportunity_id" },
+    JoinTable { table: "campaigns_leads", left_table: "campaigns", left_column: "campaign_id", right_table: "leads", right_column: "lead_id" },
+    JoinTable { table: "projects_contacts", left_table: "projects", left_column: "project_id", right_table: "contacts", right_column: "contact_id" },
+    JoinTable { table: "projects_accounts", left_table: "projects", left_column: "project_id", right_table: "accounts", right_column: "account_id" },
+    JoinTable { table: "users_roles", left_table: "users", left_column: "user_id", right_table: "roles", right_column: "role_id" },
+];
+
+/// Tables whose records can be tagged; the table name is stored as `taggables.taggable_type`
+pub const TAGGABLE_TABLES: &[&str] = &[
+    "projects", "accounts", "contacts", "opportunities", "activities", "leads",
+    "campaigns", "documents", "events", "products", "roles", "calls",
+];
+
+/// A relationship as seen from one side
+#[derive(Debug, PartialEq)]
+struct Link {
+    join_table: &'static str,
+    /// Join column holding this record's id
+    this_column: &'static str,
+    /// Join column holding the related record's id
+    other_column: &'static str,
+    other_table: &'static str,
+    /// `taggable_type` value the join rows must carry
+    taggable_type: Option<&'static str>,
+}
+
+fn resolve_link(table: &str, related: &str) -> Option<Link> {
+    for j in JOIN_TABLES {
+        if j.left_table == table && j.right_table == related {
+            return Some(Link {
+                join_table: j.table,
+                this_column: j.left_column,
+                other_column: j.right_column,
+                other_table: j.right_table,
+                taggable_type: None,
+            });
+        }
+        if j.right_table == table && j.left_table == related {
+            return Some(Link {
+                join_table: j.table,
+                this_column: j.right_column,
+                other_column: j.left_column,
+                other_table: j.left_table,
+                taggable_type: None,
+            });
+        }
+    }
+
+    if related == "tags" {
+        let table = TAGGABLE_TABLES.iter().find(|t| **t == table)?;
+        return Some(Link {
+            join_table: "taggables",
+            this_column: "taggable_id",
+            other_column: "tag_id",
+            other_table: "tags",
+            taggable_type: Some(table),
+        });
+    }
+    if table == "tags" {
+        let related = TAGGABLE_TABLES.iter().find(|t| **t == related)?;
+        return Some(Link {
+            join_table: "taggables",
+            this_column: "tag_id",
+            other_column: "taggable_id",
+            other_table: related,
+            taggable_type: Some(related),
+        });
+    }
+    None
+}
+
+/// Relationship names available from a table, for error messages
+fn available_links(table: &str) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = JOIN_TABLES
+        .iter()
+        .filter_map(|j| {
+            if j.left_table == table {
+                Some(j.right_table)
+            } else if j.right_table == table {
+                Some(j.left_table)
+            } else {
+                None
+            }
+        })
+        .collect();
+    if TAGGABLE_TABLES.contains(&table) {
+        names.push("tags");
+    }
+    if table == "tags" {
+        names.extend_from_slice(TAGGABLE_TABLES);
+    }
+    names
+}
+
+/// Delete every join row that links to `table`/`id`, so the record itself can be removed
+pub async fn unlink_all(conn: &mut PgConnection, table: &str, id: Uuid) -> std::result::Result<u64, sqlx::Error> {
+    let mut removed = 0;
+    for related in available_links(table) {
+        let Some(link) = resolve_link(table, related) else { continue };
+        let mut sql = format!("DELETE FROM {} WHERE {} = $1", link.join_table, link.this_column);
+        if link.taggable_type.is_some() {
+            sql.push_str(" AND taggable_type = $2");
+        }
+        let mut query = sqlx::query(&sql).bind(id);
+        if let Some(taggable_type) = link.taggable_type {
+            query = query.bind(taggable_type);
+        }
+        removed += query.execute(&mut *conn).await?.rows_affected();
+    }
+    Ok(removed)
+}
+
+fn unknown_link<T: CrmTable>(related: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "success": false,
+        "error": format!("{} has no relationship '{related}'", T::TABLE),
+        "available": available_links(T::TABLE)
+    }))
+}
+
+/// Whether the record exists and, for soft-deleting tables, has not been deleted
+async fn record_exists(db: &Pool<Postgres>, table: &str, id: Uuid) -> std::result::Result<bool, sqlx::Error> {
+    let visible = if soft_deletes(table) { " AND NOT deleted" } else { "" };
+    sqlx::query_scalar(&format!("SELECT EXISTS (SELECT 1 FROM {table} WHERE id = $1{visible})"))
+        .bind(id)
+        .fetch_one(db)
+        .await
+}
+
+// GET /api/{entity}/{id}/{related}
+pub async fn list_related<T: CrmTable>(
+    data: web::Data<Arc<ApiState>>,
+    path: web::Path<(Uuid, String)>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let (id, related) = path.into_inner();
+    let Some(link) = resolve_link(T::TABLE, &related) else {
+        return Ok(unknown_link::<T>(&related));
+    };
+
+    match record_exists(db, T::TABLE, id).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(not_found::<T>(id)),
+        Err(e) => return Ok(db_error_response(e, &format!("look up {}", T::LABEL.to_lowercase()))),
+    }
+
+    let mut sql = format!(
+        "SELECT r.*, j.date_entered AS linked_at FROM {other} r JOIN {join} j ON j.{other_col} = r.id WHERE j.{this_col} = $1",
+        other = link.other_table,
+        join = link.join_table,
+        other_col = link.other_column,
+        this_col = link.this_column,
+    );
+    if link.taggable_type.is_some() {
+        sql.push_str(" AND j.taggable_type = $2");
+    }
+    if soft_deletes(link.other_table) {
+        sql.push_str(" AND NOT r.deleted");
+    }
+    sql.push_str(" ORDER BY j.date_entered");
+
+    let mut query = sqlx::query(&sql).bind(id);
+    if let Some(taggable_type) = link.taggable_type {
+        query = query.bind(taggable_type);
+    }
+    match query.fetch_all(db).await {
+        Ok(rows) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "data": rows.iter().map(crate::row_to_json).collect::<Vec<_>>(),
+            "total": rows.len(),
+        }))),
+        Err(e) => Ok(db_error_response(e, &format!("list {related}"))),
+    }
+}
+
+// POST /api/{entity}/{id}/{related}/{related_id}
+pub async fn link_records<T: CrmTable>(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    path: web::Path<(Uuid, String, Uuid)>,
+) -> Result<HttpResponse> {
+    let (id, related, related_id) = path.into_inner();
+    let response = insert_link::<T>(&data, id, &related, related_id).await?;
+    let target = format!("{}/{id}/{related}/{related_id}", T::TABLE);
+    crate::audit::record(&data, &session, &format!("{}.link", T::TABLE), &target, json!({}), &response).await;
+    Ok(response)
+}
+
+async fn insert_link<T: CrmTable>(data: &ApiState, id: Uuid, related: &str, related_id: Uuid) -> Result<HttpResponse> {
+    let db = match db_pool(data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let Some(link) = resolve_link(T::TABLE, related) else {
+        return Ok(unknown_link::<T>(related));
+    };
+
+    // taggables.taggable_id has no FK, so both sides are checked here for every join table
+    match record_exists(db, T::TABLE, id).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(not_found::<T>(id)),
+        Err(e) => return Ok(db_error_response(e, &format!("look up {}", T::LABEL.to_lowercase()))),
+    }
+    match record_exists(db, link.other_table, related_id).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(HttpResponse::NotFound().json(json!({
+            "success": false,
+            "error": format!("No {} record with id {related_id}", link.other_table)
+        }))),
+        Err(e) => return Ok(db_error_response(e, &format!("look up {related}"))),
+    }
+
+    let result = match link.taggable_type {
+        Some(taggable_type) => {
+            sqlx::query(&format!(
+                "INSERT INTO taggables ({this}, {other}, taggable_type) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                this = link.this_column,
+                other = link.other_column,
+            ))
+            .bind(id)
+            .bind(related_id)
+            .bind(taggable_type)
+            .execute(db)
+            .await
+        }
+        None => {
+            sqlx::query(&format!(
+                "INSERT INTO {join} ({this}, {other}) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                join = link.join_table,
+                this = link.this_column,
+                other = link.other_column,
+            ))
+            .bind(id)
+            .bind(related_id)
+            .execute(db)
+            .await
+        }
+    };
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "linked": false,
+            "message": format!("{} {id} is already linked to {related} {related_id}", T::LABEL)
+        }))),
+        Ok(_) => Ok(HttpResponse::Created().json(json!({
+            "success": true,
+            "linked": true,
+            "message": format!("Linked {} {id} to {related} {related_id}", T::LABEL)
+        }))),
+        Err(e) => Ok(db_error_response(e, &format!("link {related}"))),
+    }
+}
+
+// DELETE /api/{entity}/{id}/{related}/{related_id}
+pub async fn unlink_records<T: CrmTable>(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    path: web::Path<(Uuid, String, Uuid)>,
+) -> Result<HttpResponse> {
+    let (id, related, related_id) = path.into_inner();
+    let response = delete_link::<T>(&data, id, &related, related_id).await?;
+    let target = format!("{}/{id}/{related}/{related_id}", T::TABLE);
+    crate::audit::record(&data, &session, &format!("{}.unlink", T::TABLE), &target, json!({}), &response).await;
+    Ok(response)
+}
+
+async fn delete_link<T: CrmTable>(data: &ApiState, id: Uuid, related: &str, related_id: Uuid) -> Result<HttpResponse> {
+    let db = match db_pool(data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let Some(link) = resolve_link(T::TABLE, related) else {
+        return Ok(unknown_link::<T>(related));
+    };
+
+    let mut sql = format!(
+        "DELETE FROM {join} WHERE {this} = $1 AND {other} = $2",
+        join = link.join_table,
+        this = link.this_column,
+        other = link.other_column,
+    );
+    if link.taggable_type.is_some() {
+        sql.push_str(" AND taggable_type = $3");
+    }
+    let mut query = sqlx::query(&sql).bind(id).bind(related_id);
+    if let Some(taggable_type) = link.taggable_type {
+        query = query.bind(taggable_type);
+    }
+
+    match query.execute(db).await {
+        Ok(r) if r.rows_affected() == 0 => Ok(HttpResponse::NotFound().json(json!({
+            "success": false,
+            "error": format!("{} {id} is not linked to {related} {related_id}", T::LABEL)
+        }))),
+        Ok(_) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "message": format!("Unlinked {} {id} from {related} {related_id}", T::LABEL)
+        }))),
+        Err(e) => Ok(db_error_response(e, &format!("unlink {related}"))),
+    }
+}
+
+pub fn configure_routes<T: CrmTable>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/{id}/{related}", web::get().to(list_related::<T>))
+        .route("/{id}/{related}/{related_id}", web::post().to(link_records::<T>).wrap(require(Permission::Write)))
+        .route("/{id}/{related}/{related_id}", web::delete().to(unlink_records::<T>).wrap(require(Permission::Write)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_tables_resolve_from_both_sides() {
+        let forward = resolve_link("accounts", "contacts").unwrap();
+        assert_eq!((forward.join_table, forward.this_column, forward.other_column), ("accounts_contacts", "account_id", "contact_id"));
+        let reverse = resolve_link("contacts", "accounts").unwrap();
+        assert_eq!((reverse.this_column, reverse.other_column, reverse.other_table), ("contact_id", "account_id", "accounts"));
+        assert!(resolve_link("accounts", "leads").is_none());
+    }
+
+    #[test]
+    fn tags_resolve_polymorphically() {
+        let tags = resolve_link("projects", "tags").unwrap();
+        assert_eq!((tags.this_column, tags.taggable_type), ("taggable_id", Some("projects")));
+        let tagged = resolve_link("tags", "leads").unwrap();
+        assert_eq!((tagged.this_column, tagged.other_table, tagged.taggable_type), ("tag_id", "leads", Some("leads")));
+        assert!(resolve_link("users", "tags").is_none());
+        assert!(resolve_link("tags", "tags").is_none());
+    }
+}
@@ -0,0 +1,545 @@
+// src/saved_queries.rs
+//! Named, parameterized queries under `/api/db/saved`.
+//!
+//! A saved query is stored with its SQL, target connection and typed parameters; parameter n
+//! binds to `$n`. Running one goes through the same read-only sandbox as `/api/db/query`, with
+//! each `$n` cast to its declared type and the value bound as text. Queries with a
+//! `cache_ttl_seconds` keep their results in memory for that long, per set of parameter values.
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::types::Json;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::crm::{db_error_response, db_pool};
+use crate::sql_sandbox::{self, SandboxConfig};
+use crate::ApiState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    Text,
+    Integer,
+    Bigint,
+    Numeric,
+    Boolean,
+    Date,
+    Timestamp,
+    Timestamptz,
+    Uuid,
+    Jsonb,
+}
+
+impl ParamType {
+    fn sql(&self) -> &'static str {
+        match self {
+            ParamType::Text => "text",
+            ParamType::Integer => "integer",
+            ParamType::Bigint => "bigint",
+            ParamType::Numeric => "numeric",
+            ParamType::Boolean => "boolean",
+            ParamType::Date => "date",
+            ParamType::Timestamp => "timestamp",
+            ParamType::Timestamptz => "timestamptz",
+            ParamType::Uuid => "uuid",
+            ParamType::Jsonb => "jsonb",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub param_type: ParamType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SavedQuery {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub sql: String,
+    pub connection: Option<String>,
+    pub parameters: Json<Vec<QueryParameter>>,
+    pub cache_ttl_seconds: Option<i32>,
+    pub created_by: Option<String>,
+    pub date_entered: Option<chrono::DateTime<chrono::Utc>>,
+    pub date_modified: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Body of POST and PUT; PUT replaces the whole definition
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedQueryInput {
+    pub name: String,
+    pub description: Option<String>,
+    pub sql: String,
+    pub connection: Option<String>,
+    #[serde(default)]
+    pub parameters: Vec<QueryParameter>,
+    pub cache_ttl_seconds: Option<i32>,
+}
+
+impl SavedQueryInput {
+    /// Problems with the definition itself, before anything is stored
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push("name is required".to_string());
+        } else if self.name.chars().count() > 150 {
+            errors.push(format!("name exceeds 150 characters (got {})", self.name.chars().count()));
+        }
+        if self.cache_ttl_seconds.is_some_and(|ttl| ttl <= 0) {
+            errors.push("cache_ttl_seconds must be positive".to_string());
+        }
+        let mut names = HashSet::new();
+        for param in &self.parameters {
+            if param.name.trim().is_empty() {
+                errors.push("parameter names must not be empty".to_string());
+            } else if !names.insert(param.name.as_str()) {
+                errors.push(format!("parameter '{}' is declared twice", param.name));
+            }
+        }
+        if let Err(e) = sql_sandbox::cast_parameters(&self.sql, &param_types(&self.parameters)) {
+            errors.push(format!("sql: {e}"));
+        }
+        errors
+    }
+}
+
+fn param_types(parameters: &[QueryParameter]) -> Vec<&'static str> {
+    parameters.iter().map(|p| p.param_type.sql()).collect()
+}
+
+/// Parameter values by name (`{"since": "2024-01-01"}`) or by position (`["2024-01-01"]`)
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValues {
+    ByName(Map<String, Value>),
+    ByPosition(Vec<Value>),
+}
+
+impl Default for ParamValues {
+    fn default() -> Self {
+        ParamValues::ByName(Map::new())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RunRequest {
+    #[serde(default)]
+    pub params: ParamValues,
+    /// Lower the sandbox row cap for this run
+    pub max_rows: Option<usize>,
+    /// Skip the result cache and store a fresh result
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// Text to bind for one value; the SQL side casts it to the declared type
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(_) | Value::Number(_) | Value::Array(_) | Value::Object(_) => Some(value.to_string()),
+    }
+}
+
+/// Values for `$1..$n` in declaration order, after defaults and required checks
+pub fn bind_values(parameters: &[QueryParameter], values: &ParamValues) -> std::result::Result<Vec<Option<String>>, String> {
+    let provided: Vec<Option<&Value>> = match values {
+        ParamValues::ByName(map) => {
+            if let Some(unknown) = map.keys().find(|k| !parameters.iter().any(|p| &p.name == *k)) {
+                return Err(format!("Unknown parameter '{unknown}'"));
+            }
+            parameters.iter().map(|p| map.get(&p.name)).collect()
+        }
+        ParamValues::ByPosition(list) => {
+            if list.len() > parameters.len() {
+                return Err(format!("Expected at most {} parameters, got {}", parameters.len(), list.len()));
+            }
+            (0..parameters.len()).map(|i| list.get(i)).collect()
+        }
+    };
+
+    parameters
+        .iter()
+        .zip(provided)
+        .map(|(param, value)| {
+            let value = value.filter(|v| !v.is_null()).or(param.default.as_ref());
+            match value.and_then(value_text) {
+                None if param.required => Err(format!("Missing required parameter '{}'", param.name)),
+                text => Ok(text),
+            }
+        })
+        .collect()
+}
+
+struct CachedResult {
+    stored_at: Instant,
+    stored_at_utc: chrono::DateTime<chrono::Utc>,
+    ttl: Duration,
+    body: Value,
+}
+
+/// In-memory results of saved queries that have a cache TTL
+#[derive(Default)]
+pub struct ResultCache {
+    entries: Mutex<HashMap<(Uuid, String), CachedResult>>,
+}
+
+impl ResultCache {
+    fn get(&self, key: &(Uuid, String)) -> Option<(Value, chrono::DateTime<chrono::Utc>)> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, cached| cached.stored_at.elapsed() < cached.ttl);
+        entries.get(key).map(|cached| (cached.body.clone(), cached.stored_at_utc))
+    }
+
+    fn put(&self, key: (Uuid, String), ttl: Duration, body: Value) {
+        self.entries.lock().unwrap().insert(key, CachedResult {
+            stored_at: Instant::now(),
+            stored_at_utc: chrono::Utc::now(),
+            ttl,
+            body,
+        });
+    }
+
+    /// Drop every cached result of one query, after it changes or is deleted
+    fn invalidate(&self, id: Uuid) {
+        self.entries.lock().unwrap().retain(|(cached_id, _), _| *cached_id != id);
+    }
+}
+
+fn bad_request(error: impl Into<String>) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "success": false, "error": error.into() }))
+}
+
+fn not_found(id: Uuid) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "success": false,
+        "error": format!("Saved query {id} not found")
+    }))
+}
+
+const SELECT_SAVED: &str = "SELECT id, name, description, sql, connection, parameters, cache_ttl_seconds, \
+    created_by, date_entered, date_modified FROM saved_queries";
+
+// GET /api/db/saved
+pub async fn list_saved_queries(data: web::Data<Arc<ApiState>>) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    match sqlx::query_as::<_, SavedQuery>(&format!("{SELECT_SAVED} ORDER BY name")).fetch_all(db).await {
+        Ok(queries) => Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "data": queries,
+            "total": queries.len(),
+        }))),
+        Err(e) => Ok(db_error_response(e, "list saved queries")),
+    }
+}
+
+// GET /api/db/saved/{id}
+pub async fn get_saved_query(data: web::Data<Arc<ApiState>>, path: web::Path<Uuid>) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    match sqlx::query_as::<_, SavedQuery>(&format!("{SELECT_SAVED} WHERE id = $1")).bind(id).fetch_optional(db).await {
+        Ok(Some(query)) => Ok(HttpResponse::Ok().json(json!({ "success": true, "data": query }))),
+        Ok(None) => Ok(not_found(id)),
+        Err(e) => Ok(db_error_response(e, "fetch saved query")),
+    }
+}
+
+// POST /api/db/saved
+pub async fn create_saved_query(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: web::Json<SavedQueryInput>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let errors = req.validate();
+    if !errors.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "error": "Invalid saved query",
+            "errors": errors
+        })));
+    }
+
+    let created_by = crate::sessions::current_user(&session).map(|user| user.email);
+    let result = sqlx::query_as::<_, SavedQuery>(
+        r#"
+        INSERT INTO saved_queries (name, description, sql, connection, parameters, cache_ttl_seconds, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#
+    )
+    .bind(req.name.trim())
+    .bind(&req.description)
+    .bind(&req.sql)
+    .bind(&req.connection)
+    .bind(Json(&req.parameters))
+    .bind(req.cache_ttl_seconds)
+    .bind(created_by)
+    .fetch_one(db)
+    .await;
+
+    let (response, target) = match result {
+        Ok(query) => {
+            let target = format!("saved_queries/{}", query.id);
+            let response = HttpResponse::Created().json(json!({
+                "success": true,
+                "id": query.id,
+                "message": format!("Saved query '{}' created", query.name),
+                "data": query
+            }));
+            (response, target)
+        }
+        Err(e) => (db_error_response(e, "create saved query"), "saved_queries".to_string()),
+    };
+    let changes = json!({ "name": req.name.trim(), "sql": req.sql, "connection": req.connection });
+    crate::audit::record(&data, &session, "saved_query.create", &target, changes, &response).await;
+    Ok(response)
+}
+
+// PUT /api/db/saved/{id}
+pub async fn update_saved_query(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    path: web::Path<Uuid>,
+    req: web::Json<SavedQueryInput>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let errors = req.validate();
+    if !errors.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "error": "Invalid saved query",
+            "errors": errors
+        })));
+    }
+
+    let result = sqlx::query_as::<_, SavedQuery>(
+        r#"
+        UPDATE saved_queries
+        SET name = $2, description = $3, sql = $4, connection = $5, parameters = $6,
+            cache_ttl_seconds = $7, date_modified = CURRENT_TIMESTAMP
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(req.name.trim())
+    .bind(&req.description)
+    .bind(&req.sql)
+    .bind(&req.connection)
+    .bind(Json(&req.parameters))
+    .bind(req.cache_ttl_seconds)
+    .fetch_optional(db)
+    .await;
+
+    let response = match result {
+        Ok(Some(query)) => {
+            data.saved_query_cache.invalidate(id);
+            HttpResponse::Ok().json(json!({
+                "success": true,
+                "message": format!("Saved query '{}' updated", query.name),
+                "data": query
+            }))
+        }
+        Ok(None) => return Ok(not_found(id)),
+        Err(e) => db_error_response(e, "update saved query"),
+    };
+    let changes = json!({ "name": req.name.trim(), "sql": req.sql, "connection": req.connection });
+    crate::audit::record(&data, &session, "saved_query.update", &format!("saved_queries/{id}"), changes, &response).await;
+    Ok(response)
+}
+
+// DELETE /api/db/saved/{id}
+pub async fn delete_saved_query(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let response = match sqlx::query("DELETE FROM saved_queries WHERE id = $1").bind(id).execute(db).await {
+        Ok(result) if result.rows_affected() == 0 => return Ok(not_found(id)),
+        Ok(_) => {
+            data.saved_query_cache.invalidate(id);
+            HttpResponse::Ok().json(json!({
+                "success": true,
+                "message": format!("Saved query {id} deleted")
+            }))
+        }
+        Err(e) => db_error_response(e, "delete saved query"),
+    };
+    crate::audit::record(&data, &session, "saved_query.delete", &format!("saved_queries/{id}"), json!({}), &response).await;
+    Ok(response)
+}
+
+// POST /api/db/saved/{id}/run
+pub async fn run_saved_query(
+    data: web::Data<Arc<ApiState>>,
+    path: web::Path<Uuid>,
+    req: Option<web::Json<RunRequest>>,
+) -> Result<HttpResponse> {
+    let db = match db_pool(&data) {
+        Ok(db) => db,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let req = req.map(web::Json::into_inner).unwrap_or_default();
+
+    let saved = match sqlx::query_as::<_, SavedQuery>(&format!("{SELECT_SAVED} WHERE id = $1")).bind(id).fetch_optional(db).await {
+        Ok(Some(saved)) => saved,
+        Ok(None) => return Ok(not_found(id)),
+        Err(e) => return Ok(db_error_response(e, "fetch saved query")),
+    };
+
+    let params = match bind_values(&saved.parameters, &req.params) {
+        Ok(params) => params,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    let sql = match sql_sandbox::cast_parameters(&saved.sql, &param_types(&saved.parameters)) {
+        Ok(sql) => sql,
+        Err(e) => return Ok(bad_request(e.to_string())),
+    };
+
+    let ttl = saved.cache_ttl_seconds.map(|ttl| Duration::from_secs(ttl.max(0) as u64));
+    let cache_key = (id, serde_json::to_string(&(&params, req.max_rows)).unwrap_or_default());
+    if let (Some(_), false) = (ttl, req.refresh) {
+        if let Some((mut body, cached_at)) = data.saved_query_cache.get(&cache_key) {
+            body["cached"] = json!(true);
+            body["cached_at"] = json!(cached_at);
+            return Ok(HttpResponse::Ok().json(body));
+        }
+    }
+
+    let pool = match &saved.connection {
+        Some(connection_name) => match data.connections.get(connection_name).await {
+            Ok(pool) => pool,
+            Err(e) => return Ok(HttpResponse::build(e.status_code()).json(json!({
+                "success": false,
+                "error": e.to_string()
+            }))),
+        },
+        None => db.clone(),
+    };
+
+    let config = SandboxConfig::for_connection(saved.connection.as_deref());
+    match sql_sandbox::run_with_params(&pool, &sql, &params, &config, req.max_rows).await {
+        Ok(result) => {
+            let body = json!({
+                "success": true,
+                "message": if result.truncated {
+                    format!("Query executed successfully; showing the first {} rows", result.max_rows)
+                } else {
+                    "Query executed successfully".to_string()
+                },
+                "error": null,
+                "query": { "id": saved.id, "name": saved.name },
+                "data": result.rows,
+                "row_count": result.rows.len(),
+                "truncated": result.truncated,
+                "max_rows": result.max_rows,
+                "elapsed_ms": result.elapsed_ms,
+                "cached": false,
+            });
+            if let Some(ttl) = ttl {
+                data.saved_query_cache.put(cache_key, ttl, body.clone());
+            }
+            Ok(HttpResponse::Ok().json(body))
+        }
+        Err(e) => {
+            let status = if e.is_client_error() {
+                actix_web::http::StatusCode::BAD_REQUEST
+            } else {
+                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+            };
+            Ok(HttpResponse::build(status).json(json!({
+                "success": false,
+                "error": e.to_string()
+            })))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> Vec<QueryParameter> {
+        serde_json::from_value(json!([
+            { "name": "since", "type": "date", "required": true },
+            { "name": "limit", "type": "integer", "default": 10 },
+            { "name": "tag", "type": "text" }
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn binds_values_by_name_or_position_with_defaults() {
+        let by_name: ParamValues = serde_json::from_value(json!({ "since": "2024-01-01", "tag": "solar" })).unwrap();
+        assert_eq!(
+            bind_values(&params(), &by_name).unwrap(),
+            vec![Some("2024-01-01".to_string()), Some("10".to_string()), Some("solar".to_string())]
+        );
+        let by_position: ParamValues = serde_json::from_value(json!(["2024-01-01", 5])).unwrap();
+        assert_eq!(
+            bind_values(&params(), &by_position).unwrap(),
+            vec![Some("2024-01-01".to_string()), Some("5".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_parameters() {
+        let missing: ParamValues = serde_json::from_value(json!({ "limit": 3 })).unwrap();
+        assert!(bind_values(&params(), &missing).unwrap_err().contains("since"));
+        let unknown: ParamValues = serde_json::from_value(json!({ "since": "2024-01-01", "bogus": 1 })).unwrap();
+        assert!(bind_values(&params(), &unknown).unwrap_err().contains("bogus"));
+        let too_many: ParamValues = serde_json::from_value(json!([1, 2, 3, 4])).unwrap();
+        assert!(bind_values(&params(), &too_many).is_err());
+    }
+
+    #[test]
+    fn definitions_must_match_their_placeholders() {
+        let input = |sql: &str| SavedQueryInput {
+            name: "Recent".to_string(),
+            description: None,
+            sql: sql.to_string(),
+            connection: None,
+            parameters: params(),
+            cache_ttl_seconds: Some(60),
+        };
+        assert!(input("SELECT * FROM accounts WHERE date_entered >= $1 LIMIT $2").validate().is_empty());
+        assert_eq!(input("SELECT $4").validate().len(), 1);
+        assert_eq!(input("UPDATE accounts SET name = $3").validate().len(), 1);
+    }
+}
@@ -0,0 +1,510 @@
+// src/schema.rs
+//! Table introspection from pg_catalog: columns with comments, keys, indexes, constraints,
+//! and the foreign-key graph behind `/api/db/relationships`.
+//!
+//! Everything is looked up by the table's OID within one schema, so same-named tables in
+//! other schemas never leak into the answer. Tables are named by [`TableRef`], which accepts
+//! `table` (the connection's current schema) or `schema.table`.
+use serde::Serialize;
+use sqlx::postgres::types::Oid;
+use sqlx::{FromRow, Pool, Postgres};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A table named as `table` or `schema.table`. Either part may be double-quoted to keep dots
+/// or quotes in the name; names are otherwise taken exactly as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableRef {
+    pub schema: Option<String>,
+    pub name: String,
+}
+
+impl TableRef {
+    pub fn parse(identifier: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut part = String::new();
+        let mut quoted = false;
+        let mut chars = identifier.trim().chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    part.push('"');
+                }
+                '"' => quoted = !quoted,
+                '.' if !quoted => parts.push(std::mem::take(&mut part)),
+                _ => part.push(c),
+            }
+        }
+        parts.push(part);
+
+        if quoted {
+            return Err(format!("Unterminated quote in table name '{identifier}'"));
+        }
+        if parts.iter().any(String::is_empty) {
+            return Err(format!("Invalid table name '{identifier}'"));
+        }
+        match <[String; 2]>::try_from(parts) {
+            Ok([schema, name]) => Ok(Self { schema: Some(schema), name }),
+            Err(parts) if parts.len() == 1 => Ok(Self { schema: None, name: parts.into_iter().next().unwrap() }),
+            Err(_) => Err(format!("Invalid table name '{identifier}': expected table or schema.table")),
+        }
+    }
+
+    /// Parses `identifier`, falling back to `schema` when the identifier isn't qualified
+    pub fn resolve(identifier: &str, schema: Option<&str>) -> Result<Self, String> {
+        let mut table = Self::parse(identifier)?;
+        if table.schema.is_none() {
+            table.schema = schema.filter(|s| !s.is_empty()).map(str::to_string);
+        }
+        Ok(table)
+    }
+
+    pub fn schema(&self) -> Option<&str> {
+        self.schema.as_deref()
+    }
+
+    /// Quoted for use in SQL, e.g. `"exio"."trade"`
+    pub fn sql(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!("{}.{}", quote_ident(schema), quote_ident(&self.name)),
+            None => quote_ident(&self.name),
+        }
+    }
+}
+
+/// `schema.table` as a client would write it, quoting only parts that need it
+impl fmt::Display for TableRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let part = |name: &str| {
+            if name.contains(['.', '"']) { quote_ident(name) } else { name.to_string() }
+        };
+        match &self.schema {
+            Some(schema) => write!(f, "{}.{}", part(schema), part(&self.name)),
+            None => f.write_str(&part(&self.name)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SchemaInfo {
+    pub name: String,
+    pub owner: String,
+    pub tables: i64,
+    /// First schema on the search path; unqualified table names resolve here
+    pub current: bool,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TableSummary {
+    pub schema: String,
+    pub name: String,
+    /// What to pass back as `table`: bare in the current schema, `schema.table` elsewhere
+    pub identifier: String,
+    pub estimated_rows: Option<i64>,
+    pub description: Option<String>,
+}
+
+/// Schemas other than Postgres' own (`pg_*` names are reserved for the system)
+const USER_SCHEMAS: &str = "n.nspname <> 'information_schema' AND n.nspname !~ '^pg_'";
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ColumnDetails {
+    pub name: String,
+    /// information_schema spelling, e.g. `character varying`
+    #[serde(rename = "type")]
+    pub data_type: String,
+    /// With length/precision, e.g. `character varying(255)`
+    pub full_type: String,
+    /// `YES` / `NO`, as information_schema reports it
+    pub nullable: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numeric_precision: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numeric_scale: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConstraintDetails {
+    pub name: String,
+    pub columns: Vec<String>,
+    /// As Postgres would print it, e.g. `CHECK ((amount >= 0))`
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ForeignKey {
+    pub name: String,
+    pub schema: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct IndexDetails {
+    pub name: String,
+    /// Column names, or the expression for expression indexes
+    pub columns: Vec<String>,
+    pub unique: bool,
+    pub primary: bool,
+    pub method: String,
+    /// WHERE clause of a partial index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<String>,
+    pub definition: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableDetails {
+    pub table_name: String,
+    pub schema: String,
+    pub estimated_rows: Option<i64>,
+    pub column_count: usize,
+    pub description: Option<String>,
+    pub columns: Vec<ColumnDetails>,
+    pub primary_key: Option<ConstraintDetails>,
+    pub unique_constraints: Vec<ConstraintDetails>,
+    pub check_constraints: Vec<ConstraintDetails>,
+    pub foreign_keys: Vec<ForeignKey>,
+    /// Foreign keys in other tables that point at this one
+    pub referenced_by: Vec<ForeignKey>,
+    pub indexes: Vec<IndexDetails>,
+}
+
+/// `schema` or the connection's current schema
+const SCHEMA_PARAM: &str = "COALESCE($1, current_schema())";
+
+/// Names of a constraint's key columns, in key order
+const KEY_COLUMNS: &str = r#"ARRAY(
+    SELECT a.attname::text FROM unnest(con.conkey) WITH ORDINALITY k(attnum, ord)
+    JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+    ORDER BY k.ord)"#;
+
+const FOREIGN_KEY_SELECT: &str = r#"
+    SELECT
+        con.conname::text AS name,
+        n.nspname::text AS schema,
+        c.relname::text AS table,
+        ARRAY(
+            SELECT a.attname::text FROM unnest(con.conkey) WITH ORDINALITY k(attnum, ord)
+            JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+            ORDER BY k.ord) AS columns,
+        fn.nspname::text AS referenced_schema,
+        fc.relname::text AS referenced_table,
+        ARRAY(
+            SELECT a.attname::text FROM unnest(con.confkey) WITH ORDINALITY k(attnum, ord)
+            JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+            ORDER BY k.ord) AS referenced_columns,
+        CASE con.confupdtype WHEN 'c' THEN 'cascade' WHEN 'n' THEN 'set null' WHEN 'd' THEN 'set default'
+            WHEN 'r' THEN 'restrict' ELSE 'no action' END AS on_update,
+        CASE con.confdeltype WHEN 'c' THEN 'cascade' WHEN 'n' THEN 'set null' WHEN 'd' THEN 'set default'
+            WHEN 'r' THEN 'restrict' ELSE 'no action' END AS on_delete
+    FROM pg_constraint con
+    JOIN pg_class c ON c.oid = con.conrelid
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    JOIN pg_class fc ON fc.oid = con.confrelid
+    JOIN pg_namespace fn ON fn.oid = fc.relnamespace
+    WHERE con.contype = 'f'
+"#;
+
+/// Every user schema with its number of tables
+pub async fn schemas(pool: &Pool<Postgres>) -> Result<Vec<SchemaInfo>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        r#"
+        SELECT
+            n.nspname::text AS name,
+            pg_get_userbyid(n.nspowner)::text AS owner,
+            (SELECT COUNT(*) FROM pg_class c WHERE c.relnamespace = n.oid AND c.relkind IN ('r', 'p')) AS tables,
+            n.nspname = current_schema() AS current,
+            obj_description(n.oid, 'pg_namespace') AS description
+        FROM pg_namespace n
+        WHERE {USER_SCHEMAS}
+        ORDER BY n.nspname <> current_schema(), n.nspname
+        "#
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+/// Base tables in `schema`, or in every user schema (current schema first) when None
+pub async fn tables(pool: &Pool<Postgres>, schema: Option<&str>, limit: Option<i64>) -> Result<Vec<TableSummary>, sqlx::Error> {
+    #[derive(FromRow)]
+    struct Row {
+        schema: String,
+        name: String,
+        current: bool,
+        estimated_rows: Option<i64>,
+        description: Option<String>,
+    }
+
+    let rows: Vec<Row> = sqlx::query_as(&format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            c.relname::text AS name,
+            n.nspname = current_schema() AS current,
+            NULLIF(c.reltuples, -1)::int8 AS estimated_rows,
+            obj_description(c.oid, 'pg_class') AS description
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE c.relkind IN ('r', 'p') AND NOT c.relispartition AND {USER_SCHEMAS}
+            AND ($1::text IS NULL OR n.nspname = $1)
+        ORDER BY n.nspname <> current_schema(), n.nspname, c.relname
+        LIMIT $2
+        "#
+    ))
+    .bind(schema)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let table = TableRef { schema: (!row.current).then(|| row.schema.clone()), name: row.name.clone() };
+            TableSummary {
+                schema: row.schema,
+                name: row.name,
+                identifier: table.to_string(),
+                estimated_rows: row.estimated_rows,
+                description: row.description,
+            }
+        })
+        .collect())
+}
+
+/// OID of a table, view or materialized view, in the current schema unless `table` names one
+async fn relation_oid(pool: &Pool<Postgres>, table: &TableRef) -> Result<Option<(Oid, String)>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        r#"
+        SELECT c.oid, n.nspname::text
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = {SCHEMA_PARAM} AND c.relname = $2 AND c.relkind IN ('r', 'p', 'v', 'm', 'f')
+        "#
+    ))
+    .bind(table.schema())
+    .bind(&table.name)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Full description of one table; None if it doesn't exist in that schema
+pub async fn table_details(pool: &Pool<Postgres>, table: &TableRef) -> Result<Option<TableDetails>, sqlx::Error> {
+    let Some((oid, schema)) = relation_oid(pool, table).await? else {
+        return Ok(None);
+    };
+
+    let (estimated_rows, comment): (Option<i64>, Option<String>) = sqlx::query_as(
+        "SELECT NULLIF(reltuples, -1)::int8, obj_description(oid, 'pg_class') FROM pg_class WHERE oid = $1"
+    )
+    .bind(oid)
+    .fetch_one(pool)
+    .await?;
+
+    let columns: Vec<ColumnDetails> = sqlx::query_as(
+        r#"
+        SELECT
+            c.column_name::text AS name,
+            c.data_type::text AS data_type,
+            format_type(a.atttypid, a.atttypmod) AS full_type,
+            c.is_nullable::text AS nullable,
+            c.column_default::text AS default,
+            c.character_maximum_length::int4 AS max_length,
+            c.numeric_precision::int4 AS numeric_precision,
+            c.numeric_scale::int4 AS numeric_scale,
+            col_description($1, a.attnum) AS comment
+        FROM information_schema.columns c
+        JOIN pg_attribute a ON a.attrelid = $1 AND a.attname = c.column_name
+        WHERE c.table_schema = $2 AND c.table_name = $3
+        ORDER BY c.ordinal_position
+        "#
+    )
+    .bind(oid)
+    .bind(&schema)
+    .bind(&table.name)
+    .fetch_all(pool)
+    .await?;
+
+    let constraints: Vec<(String, ConstraintDetails)> = sqlx::query_as::<_, (String, String, Vec<String>, String)>(&format!(
+        r#"
+        SELECT con.contype::text, con.conname::text, {KEY_COLUMNS}, pg_get_constraintdef(con.oid)
+        FROM pg_constraint con
+        WHERE con.conrelid = $1 AND con.contype IN ('p', 'u', 'c')
+        ORDER BY con.conname
+        "#
+    ))
+    .bind(oid)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(kind, name, columns, definition)| (kind, ConstraintDetails { name, columns, definition }))
+    .collect();
+
+    let mut primary_key = None;
+    let mut unique_constraints = Vec::new();
+    let mut check_constraints = Vec::new();
+    for (kind, constraint) in constraints {
+        match kind.as_str() {
+            "p" => primary_key = Some(constraint),
+            "u" => unique_constraints.push(constraint),
+            _ => check_constraints.push(constraint),
+        }
+    }
+
+    let foreign_keys: Vec<ForeignKey> = sqlx::query_as(&format!("{FOREIGN_KEY_SELECT} AND con.conrelid = $1 ORDER BY con.conname"))
+        .bind(oid)
+        .fetch_all(pool)
+        .await?;
+    let referenced_by: Vec<ForeignKey> = sqlx::query_as(&format!("{FOREIGN_KEY_SELECT} AND con.confrelid = $1 ORDER BY c.relname, con.conname"))
+        .bind(oid)
+        .fetch_all(pool)
+        .await?;
+
+    let indexes: Vec<IndexDetails> = sqlx::query_as(
+        r#"
+        SELECT
+            ic.relname::text AS name,
+            ARRAY(SELECT pg_get_indexdef(i.indexrelid, k, true) FROM generate_series(1, i.indnkeyatts) k) AS columns,
+            i.indisunique AS unique,
+            i.indisprimary AS primary,
+            am.amname::text AS method,
+            pg_get_expr(i.indpred, i.indrelid) AS predicate,
+            pg_get_indexdef(i.indexrelid) AS definition
+        FROM pg_index i
+        JOIN pg_class ic ON ic.oid = i.indexrelid
+        JOIN pg_am am ON am.oid = ic.relam
+        WHERE i.indrelid = $1
+        ORDER BY i.indisprimary DESC, ic.relname
+        "#
+    )
+    .bind(oid)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(TableDetails {
+        table_name: table.name.clone(),
+        schema,
+        estimated_rows,
+        column_count: columns.len(),
+        description: comment,
+        columns,
+        primary_key,
+        unique_constraints,
+        check_constraints,
+        foreign_keys,
+        referenced_by,
+        indexes,
+    }))
+}
+
+/// Every foreign key in `schema` (default: the current schema), including ones that point elsewhere
+pub async fn foreign_keys(pool: &Pool<Postgres>, schema: Option<&str>) -> Result<Vec<ForeignKey>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "{FOREIGN_KEY_SELECT} AND n.nspname = {SCHEMA_PARAM} ORDER BY c.relname, con.conname"
+    ))
+    .bind(schema)
+    .fetch_all(pool)
+    .await
+}
+
+/// The foreign-key graph in the shape of admin/sql/table_relationships.json:
+/// one entry per referencing table, one relationship per column pair
+pub fn relationship_graph(foreign_keys: &[ForeignKey]) -> serde_json::Value {
+    let mut tables: BTreeMap<&str, Vec<serde_json::Value>> = BTreeMap::new();
+    for fk in foreign_keys {
+        let relationships = tables.entry(fk.table.as_str()).or_default();
+        for (column, referenced_column) in fk.columns.iter().zip(&fk.referenced_columns) {
+            relationships.push(serde_json::json!({
+                "column": column,
+                "referenced_table": fk.referenced_table,
+                "referenced_column": referenced_column,
+                "constraint_type": "foreign_key",
+                "constraint_name": fk.name,
+                "referenced_schema": fk.referenced_schema,
+                "on_update": fk.on_update,
+                "on_delete": fk.on_delete,
+            }));
+        }
+    }
+    serde_json::json!({
+        "tables": tables
+            .into_iter()
+            .map(|(table_name, relationships)| serde_json::json!({
+                "table_name": table_name,
+                "relationships": relationships,
+            }))
+            .collect::<Vec<_>>()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fk(table: &str, columns: &[&str], referenced_table: &str, referenced_columns: &[&str]) -> ForeignKey {
+        ForeignKey {
+            name: format!("{table}_fk"),
+            schema: "public".into(),
+            table: table.into(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            referenced_schema: "public".into(),
+            referenced_table: referenced_table.into(),
+            referenced_columns: referenced_columns.iter().map(|c| c.to_string()).collect(),
+            on_update: "no action".into(),
+            on_delete: "cascade".into(),
+        }
+    }
+
+    #[test]
+    fn parses_qualified_and_quoted_table_names() {
+        assert_eq!(TableRef::parse("trade").unwrap(), TableRef { schema: None, name: "trade".into() });
+        let table = TableRef::parse("exio.trade").unwrap();
+        assert_eq!(table.schema(), Some("exio"));
+        assert_eq!(table.sql(), r#""exio"."trade""#);
+
+        let odd = TableRef::parse(r#""My Schema"."odd.""name""#).unwrap();
+        assert_eq!(odd.schema(), Some("My Schema"));
+        assert_eq!(odd.name, r#"odd."name"#);
+        assert_eq!(odd.to_string(), r#"My Schema."odd.""name""#);
+        assert_eq!(TableRef::parse(&odd.to_string()).unwrap(), odd);
+
+        assert_eq!(TableRef::resolve("trade", Some("exio")).unwrap().sql(), r#""exio"."trade""#);
+        assert_eq!(TableRef::resolve("public.trade", Some("exio")).unwrap().schema(), Some("public"));
+        for bad in ["", "a.", "a.b.c", r#""open"#] {
+            assert!(TableRef::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn graph_matches_the_relationships_file_shape() {
+        let graph = relationship_graph(&[
+            fk("contacts", &["account_id"], "accounts", &["id"]),
+            fk("accounts_contacts", &["account_id", "contact_id"], "pairs", &["a", "b"]),
+        ]);
+        let tables = graph["tables"].as_array().unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0]["table_name"], "accounts_contacts");
+        assert_eq!(tables[0]["relationships"].as_array().unwrap().len(), 2);
+        assert_eq!(tables[0]["relationships"][1]["referenced_column"], "b");
+        assert_eq!(tables[1]["relationships"][0]["column"], "account_id");
+        assert_eq!(tables[1]["relationships"][0]["referenced_table"], "accounts");
+        assert_eq!(tables[1]["relationships"][0]["constraint_type"], "foreign_key");
+    }
+}
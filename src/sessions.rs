@@ -0,0 +1,193 @@
+// src/sessions.rs
+//! Cookie sessions backed by the Postgres `sessions` table.
+//!
+//! The browser holds only a signed cookie carrying a random session id; the signed-in
+//! `UserSession` lives server-side, so logging out (or deleting the row) revokes it at once.
+use actix_session::config::{CookieContentSecurity, PersistentSession};
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_session::{Session, SessionMiddleware};
+use actix_web::cookie::{time::Duration, Key};
+use oauth2::CsrfToken;
+use sqlx::types::Json;
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+
+use crate::oauth::UserSession;
+
+/// Session entry holding the signed-in user
+pub const USER_KEY: &str = "user";
+/// Session entry holding the `oauth::PendingAuthorization` of a sign-in in progress
+pub const OAUTH_KEY: &str = "oauth_pending";
+pub const COOKIE_NAME: &str = "team_session";
+
+type SessionState = HashMap<String, String>;
+
+/// `SessionStore` over the `sessions` table. Without a database connection every
+/// load comes back empty and saves fail, so requests simply stay anonymous.
+#[derive(Clone)]
+pub struct PgSessionStore {
+    db: Option<Pool<Postgres>>,
+}
+
+impl PgSessionStore {
+    pub fn new(db: Option<Pool<Postgres>>) -> Self {
+        Self { db }
+    }
+
+    fn pool(&self) -> anyhow::Result<&Pool<Postgres>> {
+        self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Session store has no database connection"))
+    }
+}
+
+/// (user_id, provider) of the signed-in user, copied into their own columns for lookups
+fn user_columns(state: &SessionState) -> (Option<String>, Option<String>) {
+    match state.get(USER_KEY).and_then(|u| serde_json::from_str::<UserSession>(u).ok()) {
+        Some(user) => (Some(user.user_id), Some(user.provider)),
+        None => (None, None),
+    }
+}
+
+async fn upsert(db: &Pool<Postgres>, id: &str, state: &SessionState, ttl: &Duration) -> Result<u64, sqlx::Error> {
+    let (user_id, provider) = user_columns(state);
+    let result = sqlx::query(
+        r#"
+        INSERT INTO sessions (id, state, user_id, provider, expires_at)
+        VALUES ($1, $2, $3, $4, NOW() + $5 * INTERVAL '1 second')
+        ON CONFLICT (id) DO UPDATE SET
+            state = EXCLUDED.state,
+            user_id = EXCLUDED.user_id,
+            provider = EXCLUDED.provider,
+            expires_at = EXCLUDED.expires_at,
+            date_modified = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(id)
+    .bind(Json(state))
+    .bind(user_id)
+    .bind(provider)
+    .bind(ttl.whole_seconds() as f64)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+impl SessionStore for PgSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return Ok(None),
+        };
+        let state: Option<Json<SessionState>> =
+            sqlx::query_scalar("SELECT state FROM sessions WHERE id = $1 AND expires_at > NOW()")
+                .bind(session_key.as_ref())
+                .fetch_optional(db)
+                .await
+                .map_err(|e| LoadError::Other(e.into()))?;
+        Ok(state.map(|Json(state)| state))
+    }
+
+    async fn save(&self, session_state: SessionState, ttl: &Duration) -> Result<SessionKey, SaveError> {
+        let db = self.pool().map_err(SaveError::Other)?;
+
+        // Opportunistic cleanup so abandoned sessions don't pile up
+        sqlx::query("DELETE FROM sessions WHERE expires_at < NOW()")
+            .execute(db)
+            .await
+            .map_err(|e| SaveError::Other(e.into()))?;
+
+        let id = CsrfToken::new_random_len(32).secret().clone();
+        upsert(db, &id, &session_state, ttl)
+            .await
+            .map_err(|e| SaveError::Other(e.into()))?;
+        SessionKey::try_from(id).map_err(|e| SaveError::Other(e.into()))
+    }
+
+    async fn update(&self, session_key: SessionKey, session_state: SessionState, ttl: &Duration) -> Result<SessionKey, UpdateError> {
+        let db = self.pool().map_err(UpdateError::Other)?;
+        upsert(db, session_key.as_ref(), &session_state, ttl)
+            .await
+            .map_err(|e| UpdateError::Other(e.into()))?;
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        sqlx::query("UPDATE sessions SET expires_at = NOW() + $2 * INTERVAL '1 second' WHERE id = $1")
+            .bind(session_key.as_ref())
+            .bind(ttl.whole_seconds() as f64)
+            .execute(self.pool()?)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM sessions WHERE id = $1")
+            .bind(session_key.as_ref())
+            .execute(self.pool()?)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Cookie signing key from SESSION_SECRET (at least 64 bytes).
+/// Falls back to a random key, which signs everyone out on restart.
+pub fn signing_key() -> Key {
+    match std::env::var("SESSION_SECRET") {
+        Ok(secret) => Key::try_from(secret.as_bytes()).unwrap_or_else(|_| {
+            log::warn!("SESSION_SECRET must be at least 64 bytes; using a random session key");
+            Key::generate()
+        }),
+        Err(_) => {
+            log::warn!("SESSION_SECRET not set; using a random session key");
+            Key::generate()
+        }
+    }
+}
+
+pub fn middleware(db: Option<Pool<Postgres>>, key: Key, timeout_hours: u32) -> SessionMiddleware<PgSessionStore> {
+    // Browsers drop Secure cookies on plain-http origins, so local development opts out
+    let secure = std::env::var("SESSION_COOKIE_SECURE")
+        .map(|v| v != "false")
+        .unwrap_or(true);
+
+    SessionMiddleware::builder(PgSessionStore::new(db), key)
+        .cookie_name(COOKIE_NAME.to_string())
+        .cookie_content_security(CookieContentSecurity::Signed)
+        .cookie_secure(secure)
+        .session_lifecycle(PersistentSession::default().session_ttl(Duration::hours(i64::from(timeout_hours))))
+        .build()
+}
+
+/// Start a fresh session for a user who just signed in
+pub fn sign_in(session: &Session, user: &UserSession) -> anyhow::Result<()> {
+    // New id on every login so a pre-login cookie can't be fixated onto the account
+    session.renew();
+    session.insert(USER_KEY, user)?;
+    Ok(())
+}
+
+/// The signed-in user, if the session holds one that hasn't expired
+pub fn current_user(session: &Session) -> Option<UserSession> {
+    let user: UserSession = session.get(USER_KEY).ok().flatten()?;
+    if user.is_expired() {
+        session.purge();
+        return None;
+    }
+    Some(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_columns_come_from_the_stored_user() {
+        let user = UserSession::new("42".into(), "ada@example.com".into(), "Ada".into(), None, "github".into());
+        let mut state = SessionState::new();
+        state.insert(USER_KEY.to_string(), serde_json::to_string(&user).unwrap());
+        assert_eq!(user_columns(&state), (Some("42".to_string()), Some("github".to_string())));
+
+        state.insert(USER_KEY.to_string(), "not json".to_string());
+        assert_eq!(user_columns(&state), (None, None));
+        assert_eq!(user_columns(&SessionState::new()), (None, None));
+    }
+}
@@ -0,0 +1,649 @@
+// src/spreadsheet.rs
+//! Generic spreadsheet import: read one sheet of an xlsx/xls/ods workbook or a csv/tsv file,
+//! map its headers onto the columns of a CRM table, coerce every cell to the column's type and
+//! insert the rows that pass.
+//!
+//! Serves `POST /api/import/spreadsheet`, and `POST /api/import/excel` when the request carries
+//! `column_mappings`. Each bad cell is reported with its sheet row number; rows with errors are
+//! skipped and the others are inserted. With `dry_run` the whole import is rolled back and a
+//! per-row report is returned instead.
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Result};
+use calamine::{open_workbook_auto, Data, Reader};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::import::{run_rows, ImportRequest, Plan, PlanError, PreparedRow, RowReport, Target};
+use crate::schema::{quote_ident, TableRef};
+use crate::ApiState;
+
+/// Tables a spreadsheet can be imported into: the CRM entities, except roles, which grant permissions
+pub fn importable(table: &str) -> bool {
+    table != "roles" && crate::crm::ENTITY_TABLES.contains(&table)
+}
+
+/// A cell as read from the file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    DateTime(NaiveDateTime),
+    /// Formula error such as `#DIV/0!`
+    Error(String),
+}
+
+impl Cell {
+    fn from_data(data: &Data) -> Cell {
+        match data {
+            Data::Empty => Cell::Empty,
+            Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => Cell::Text(s.clone()),
+            Data::Int(i) => Cell::Int(*i),
+            Data::Float(f) => Cell::Float(*f),
+            Data::Bool(b) => Cell::Bool(*b),
+            Data::DateTime(dt) => dt.as_datetime().map_or(Cell::Float(dt.as_f64()), Cell::DateTime),
+            Data::Error(e) => Cell::Error(e.to_string()),
+        }
+    }
+
+    pub(crate) fn is_blank(&self) -> bool {
+        match self {
+            Cell::Empty => true,
+            Cell::Text(s) => s.trim().is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The cell as a user would read it
+    pub(crate) fn text(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            Cell::Text(s) | Cell::Error(s) => s.trim().to_string(),
+            Cell::Int(i) => i.to_string(),
+            // Whole numbers come back from Excel as floats; don't show them as "42.0"
+            Cell::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+            Cell::Float(f) => f.to_string(),
+            Cell::Bool(b) => b.to_string(),
+            Cell::DateTime(dt) if dt.time() == chrono::NaiveTime::MIN => dt.format("%Y-%m-%d").to_string(),
+            Cell::DateTime(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+/// Header row and data rows of one sheet
+#[derive(Debug)]
+pub struct Sheet {
+    pub name: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// Delimiter for csv/tsv files; None for workbooks
+fn text_delimiter(path: &Path) -> Option<u8> {
+    match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "csv" => Some(b','),
+        "tsv" | "tab" => Some(b'\t'),
+        _ => None,
+    }
+}
+
+/// Sheets in a workbook; a csv/tsv file has one, named after the file. `path` comes from
+/// `uploads::resolve`.
+pub fn sheet_names(path: &Path) -> std::result::Result<Vec<String>, String> {
+    if text_delimiter(path).is_some() {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Sheet1");
+        return Ok(vec![stem.to_string()]);
+    }
+    let workbook = open_workbook_auto(path).map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+    Ok(workbook.sheet_names().to_vec())
+}
+
+/// Read one sheet (the first if `sheet` is None). The first row holds the headers.
+pub fn read_sheet(path: &Path, sheet: Option<&str>) -> std::result::Result<Sheet, String> {
+    if let Some(delimiter) = text_delimiter(path) {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+        let headers = reader
+            .headers()
+            .map_err(|e| format!("Cannot read headers: {e}"))?
+            .iter()
+            .map(|h| h.trim().to_string())
+            .collect();
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+            rows.push(record.iter().map(|v| if v.is_empty() { Cell::Empty } else { Cell::Text(v.to_string()) }).collect());
+        }
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Sheet1").to_string();
+        return Ok(Sheet { name, headers, rows });
+    }
+
+    let mut workbook = open_workbook_auto(path).map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+    let name = match sheet {
+        Some(name) => name.to_string(),
+        None => workbook.sheet_names().first().cloned().ok_or("The workbook has no sheets")?,
+    };
+    let range = workbook
+        .worksheet_range(&name)
+        .map_err(|e| format!("Cannot read sheet '{name}': {e}"))?;
+    let mut rows = range.rows();
+    let headers = rows
+        .next()
+        .map(|row| row.iter().map(|cell| Cell::from_data(cell).text()).collect())
+        .unwrap_or_default();
+    let rows = rows.map(|row| row.iter().map(Cell::from_data).collect()).collect();
+    Ok(Sheet { name, headers, rows })
+}
+
+/// A column of the target table, with what coercion needs to know about it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TargetColumn {
+    pub name: String,
+    /// information_schema spelling, e.g. `character varying`
+    pub data_type: String,
+    pub udt_schema: String,
+    pub udt_name: String,
+    /// NOT NULL without a default, or required by the CRM API
+    pub required: bool,
+    pub max_length: Option<i32>,
+    pub numeric_precision: Option<i32>,
+    pub numeric_scale: Option<i32>,
+}
+
+impl TargetColumn {
+    pub(crate) fn cast_type(&self) -> String {
+        format!("{}.{}", quote_ident(&self.udt_schema), quote_ident(&self.udt_name))
+    }
+}
+
+pub async fn target_columns(pool: &Pool<Postgres>, table: &TableRef) -> std::result::Result<Vec<TargetColumn>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT column_name::text AS name, data_type::text, udt_schema::text, udt_name::text,
+               is_nullable = 'NO' AND column_default IS NULL AS required,
+               character_maximum_length::int AS max_length,
+               numeric_precision::int, numeric_scale::int
+        FROM information_schema.columns
+        WHERE table_schema = COALESCE($1, current_schema()) AND table_name = $2
+        ORDER BY ordinal_position
+        "#
+    )
+    .bind(table.schema())
+    .bind(&table.name)
+    .fetch_all(pool)
+    .await
+    .map(|columns: Vec<TargetColumn>| {
+        let api_required = crate::crm::required_columns(&table.name);
+        columns
+            .into_iter()
+            .map(|c| TargetColumn { required: c.required || api_required.contains(&c.name.as_str()), ..c })
+            .collect()
+    })
+}
+
+/// Header names compare ignoring case, surrounding space and space/dash vs underscore
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// `(header index, column index)` pairs to import. Explicit mappings (header → column, an empty
+/// column skips the header) must name real headers and columns; without them, headers match
+/// columns by name.
+pub fn resolve_mapping(
+    headers: &[String],
+    columns: &[TargetColumn],
+    explicit: Option<&HashMap<String, String>>,
+) -> std::result::Result<Vec<(usize, usize)>, Vec<String>> {
+    let column_index = |name: &str| columns.iter().position(|c| c.name == name || normalize(&c.name) == normalize(name));
+    let mut pairs = Vec::new();
+    let mut errors = Vec::new();
+    match explicit {
+        Some(mappings) => {
+            let mut mappings: Vec<_> = mappings.iter().filter(|(_, column)| !column.trim().is_empty()).collect();
+            mappings.sort();
+            for (header, column) in mappings {
+                let Some(h) = headers.iter().position(|h| h == header || normalize(h) == normalize(header)) else {
+                    errors.push(format!("Header '{header}' is not in the sheet"));
+                    continue;
+                };
+                match column_index(column) {
+                    Some(c) => pairs.push((h, c)),
+                    None => errors.push(format!("Column '{column}' does not exist")),
+                }
+            }
+        }
+        None => {
+            for (h, header) in headers.iter().enumerate() {
+                if let Some(c) = column_index(header) {
+                    pairs.push((h, c));
+                }
+            }
+            if pairs.is_empty() {
+                errors.push(format!("No header matches a column name; send column_mappings for: {}", headers.join(", ")));
+            }
+        }
+    }
+    for (i, &(_, c)) in pairs.iter().enumerate() {
+        if pairs[..i].iter().any(|&(_, other)| other == c) {
+            errors.push(format!("More than one header maps to column '{}'", columns[c].name));
+        }
+    }
+    if errors.is_empty() { Ok(pairs) } else { Err(errors) }
+}
+
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%d.%m.%Y", "%d %b %Y", "%b %d, %Y", "%B %d, %Y"];
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M",
+    "%m/%d/%Y %H:%M:%S", "%m/%d/%Y %H:%M",
+];
+
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(text, f).ok())
+        .or_else(|| parse_datetime(text).map(|dt| dt.date()))
+}
+
+fn parse_datetime(text: &str) -> Option<NaiveDateTime> {
+    DATETIME_FORMATS.iter().find_map(|f| NaiveDateTime::parse_from_str(text, f).ok())
+}
+
+/// Number text without grouping separators or a leading currency sign, e.g. "$1,200.50" → "1200.50"
+fn clean_number(text: &str) -> String {
+    let text: String = text.chars().filter(|c| !matches!(c, ',' | '_' | ' ')).collect();
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text.as_str()),
+    };
+    format!("{sign}{}", rest.trim_start_matches(['$', '€', '£']))
+}
+
+/// The cell as the text Postgres casts into `column`, or None to leave the column out
+pub fn coerce(cell: &Cell, column: &TargetColumn) -> std::result::Result<Option<String>, String> {
+    let name = &column.name;
+    if cell.is_blank() {
+        if column.required {
+            return Err(format!("{name} is required"));
+        }
+        return Ok(None);
+    }
+    if let Cell::Error(e) = cell {
+        return Err(format!("{name} holds a formula error ({e})"));
+    }
+    let text = cell.text();
+    let expected = |what: &str| {
+        let shown: String = text.chars().take(60).collect();
+        let ellipsis = if shown.len() < text.len() { "…" } else { "" };
+        format!("{name}: expected {what}, got '{shown}{ellipsis}'")
+    };
+
+    let value = match column.data_type.as_str() {
+        "smallint" | "integer" | "bigint" => {
+            let n: i64 = match cell {
+                Cell::Int(i) => *i,
+                Cell::Float(f) if f.fract() == 0.0 => *f as i64,
+                Cell::Text(_) => {
+                    let cleaned = clean_number(&text);
+                    cleaned
+                        .parse::<i64>()
+                        .ok()
+                        .or_else(|| cleaned.parse::<f64>().ok().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+                        .ok_or_else(|| expected("a whole number"))?
+                }
+                _ => return Err(expected("a whole number")),
+            };
+            let max = match column.data_type.as_str() {
+                "smallint" => i16::MAX as i64,
+                "integer" => i32::MAX as i64,
+                _ => i64::MAX,
+            };
+            if n > max || n < -max - 1 {
+                return Err(format!("{name} is out of range for {}", column.data_type));
+            }
+            n.to_string()
+        }
+        "numeric" | "real" | "double precision" | "money" => {
+            let cleaned = match cell {
+                Cell::Int(_) | Cell::Float(_) | Cell::Text(_) => clean_number(&text),
+                _ => return Err(expected("a number")),
+            };
+            let v = cleaned.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(|| expected("a number"))?;
+            if let (Some(precision), Some(scale)) = (column.numeric_precision, column.numeric_scale) {
+                if column.data_type == "numeric" && v.abs() >= 10f64.powi(precision - scale) {
+                    return Err(format!("{name} is out of range for NUMERIC({precision},{scale})"));
+                }
+            }
+            cleaned
+        }
+        "boolean" => match cell {
+            Cell::Bool(b) => b.to_string(),
+            _ => match text.to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "1" => "true".to_string(),
+                "false" | "f" | "no" | "n" | "0" => "false".to_string(),
+                _ => return Err(expected("yes/no or true/false")),
+            },
+        },
+        "date" => match cell {
+            Cell::DateTime(dt) => dt.date().to_string(),
+            Cell::Text(_) => parse_date(&text).ok_or_else(|| expected("a date"))?.to_string(),
+            _ => return Err(expected("a date")),
+        },
+        "timestamp with time zone" | "timestamp without time zone" => match cell {
+            Cell::DateTime(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+            Cell::Text(_) => match DateTime::parse_from_rfc3339(&text) {
+                Ok(dt) => dt.to_rfc3339(),
+                Err(_) => parse_datetime(&text)
+                    .or_else(|| parse_date(&text).map(|d| d.and_time(chrono::NaiveTime::MIN)))
+                    .ok_or_else(|| expected("a date and time"))?
+                    .format("%Y-%m-%d %H:%M:%S%.f")
+                    .to_string(),
+            },
+            _ => return Err(expected("a date and time")),
+        },
+        "uuid" => Uuid::parse_str(&text).map_err(|_| expected("a UUID"))?.to_string(),
+        "json" | "jsonb" => match cell {
+            Cell::Text(_) => {
+                serde_json::from_str::<Value>(&text).map_err(|_| expected("JSON"))?;
+                text
+            }
+            Cell::DateTime(_) => Value::String(text).to_string(),
+            _ => text,
+        },
+        // Text columns, and types Postgres checks itself when casting
+        _ => {
+            let text = match cell {
+                Cell::Text(s) => s.trim().to_string(),
+                _ => text,
+            };
+            if let Some(max) = column.max_length {
+                let len = text.chars().count();
+                if len > max as usize {
+                    return Err(format!("{name} exceeds {max} characters (got {len})"));
+                }
+            }
+            text
+        }
+    };
+    Ok(Some(value))
+}
+
+/// A problem with one sheet row; `row` is the row number as shown in the spreadsheet
+#[derive(Debug, Clone, Serialize)]
+pub struct RowError {
+    pub row: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpreadsheetImportResponse {
+    pub success: bool,
+    pub message: String,
+    pub table: String,
+    pub sheet: String,
+    pub records_processed: usize,
+    pub records_inserted: usize,
+    pub records_updated: usize,
+    pub records_skipped: usize,
+    pub records_failed: usize,
+    /// The dedup policy applied, if the request gave one
+    pub duplicate_check_columns: Option<String>,
+    /// Header → column actually imported
+    pub mapping: BTreeMap<String, String>,
+    pub unmapped_headers: Vec<String>,
+    pub errors: Vec<String>,
+    pub row_errors: Vec<RowError>,
+    pub dry_run: bool,
+    /// Per-row report, returned for dry runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<Vec<RowReport>>,
+}
+
+fn bad_request(message: String, errors: Vec<String>) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "success": false,
+        "message": message,
+        "errors": errors
+    }))
+}
+
+/// Database error text for a row report, without the driver's prefix
+pub(crate) fn db_message(e: &sqlx::Error) -> String {
+    match e {
+        sqlx::Error::Database(db_err) => db_err.message().to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// What a spreadsheet import reports about the sheet besides its rows
+pub struct SheetSummary {
+    pub sheet: String,
+    /// Header → column actually imported
+    pub mapping: BTreeMap<String, String>,
+    pub unmapped_headers: Vec<String>,
+}
+
+/// Read `req.file_path`, map it onto `req.table_name` and coerce every non-blank row
+pub(crate) async fn plan(db: &Pool<Postgres>, req: &ImportRequest) -> std::result::Result<(Plan, SheetSummary), PlanError> {
+    if !importable(&req.table_name) {
+        return Err(PlanError::Invalid(
+            format!("Cannot import into '{}'", req.table_name),
+            vec![format!("Importable tables: {}", crate::crm::ENTITY_TABLES.iter().filter(|t| importable(t)).copied().collect::<Vec<_>>().join(", "))],
+        ));
+    }
+    let table = TableRef { schema: None, name: req.table_name.clone() };
+
+    let sheet = req
+        .source()
+        .and_then(|path| read_sheet(&path, req.sheet_name.as_deref()))
+        .map_err(|e| PlanError::Invalid(format!("Failed to read {}", req.source_name()), vec![e]))?;
+    let columns = target_columns(db, &table).await?;
+    let pairs = resolve_mapping(&sheet.headers, &columns, req.column_mappings.as_ref())
+        .map_err(|errors| PlanError::Invalid("Invalid column mapping".to_string(), errors))?;
+    let unmapped_required: Vec<String> = columns
+        .iter()
+        .enumerate()
+        .filter(|(i, c)| c.required && !pairs.iter().any(|&(_, mapped)| mapped == *i))
+        .map(|(_, c)| format!("Required column '{}' is not mapped", c.name))
+        .collect();
+    if !unmapped_required.is_empty() {
+        return Err(PlanError::Invalid("Invalid column mapping".to_string(), unmapped_required));
+    }
+    // Spreadsheet imports only check for duplicates when asked to
+    if let Some(policy) = &req.dedup {
+        let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+        policy.validate(&names).map_err(|errors| PlanError::Invalid("Invalid dedup policy".to_string(), errors))?;
+    }
+
+    let mut rows = Vec::new();
+    for (i, cells) in sheet.rows.iter().enumerate() {
+        if cells.iter().all(Cell::is_blank) {
+            continue;
+        }
+        // Data starts on the second sheet row, under the headers
+        let mut row = PreparedRow { row: i + 2, ..Default::default() };
+        for &(h, c) in &pairs {
+            let column = &columns[c];
+            match coerce(cells.get(h).unwrap_or(&Cell::Empty), column) {
+                Ok(Some(value)) => {
+                    row.values.insert(column.name.clone(), Value::String(value));
+                }
+                Ok(None) => {}
+                Err(message) => row.errors.push(RowError { row: row.row, column: Some(column.name.clone()), message }),
+            }
+        }
+        if row.values.is_empty() && row.errors.is_empty() {
+            row.errors.push(RowError { row: row.row, column: None, message: "No mapped values".to_string() });
+        }
+        rows.push(row);
+    }
+
+    let summary = SheetSummary {
+        mapping: pairs.iter().map(|&(h, c)| (sheet.headers[h].clone(), columns[c].name.clone())).collect(),
+        unmapped_headers: sheet
+            .headers
+            .iter()
+            .enumerate()
+            .filter(|(h, header)| !header.is_empty() && !pairs.iter().any(|&(mapped, _)| mapped == *h))
+            .map(|(_, header)| header.clone())
+            .collect(),
+        sheet: sheet.name,
+    };
+    let target = Target { table: req.table_name.clone(), columns, policy: req.dedup.clone(), source: None };
+    Ok((Plan { target, rows }, summary))
+}
+
+/// Import `req.file_path` into `req.table_name`
+pub async fn import(data: &ApiState, req: &ImportRequest) -> HttpResponse {
+    let db = match crate::crm::db_pool(data) {
+        Ok(db) => db,
+        Err(resp) => return resp,
+    };
+    let (plan, summary) = match plan(db, req).await {
+        Ok(planned) => planned,
+        Err(PlanError::Invalid(message, errors)) => return bad_request(message, errors),
+        Err(PlanError::Database(e)) => return crate::crm::db_error_response(e, "read table columns"),
+    };
+
+    // One transaction with a savepoint per row; a dry run rolls it all back
+    let tally = match run_rows(db, &plan.target, &plan.rows, req.dry_run).await {
+        Ok(tally) => tally,
+        Err(e) => return crate::crm::db_error_response(e, "import spreadsheet"),
+    };
+    let processed = plan.rows.len();
+    let (inserted, updated, skipped, failed) = (tally.inserted, tally.updated, tally.skipped, tally.errors.len());
+
+    let mut message = if req.dry_run {
+        format!("Dry run: would import {inserted} of {processed} records into {} and reject {failed}", req.table_name)
+    } else if failed == 0 {
+        format!("Imported {inserted} records into {}", req.table_name)
+    } else {
+        format!("Imported {inserted} of {processed} records into {}; {failed} rows had errors", req.table_name)
+    };
+    if updated + skipped > 0 {
+        message.push_str(&if req.dry_run {
+            format!("; would update {updated} existing and skip {skipped} duplicates")
+        } else {
+            format!("; updated {updated} existing and skipped {skipped} duplicates")
+        });
+    }
+    HttpResponse::Ok().json(SpreadsheetImportResponse {
+        success: failed == 0 || inserted + updated > 0,
+        message,
+        table: req.table_name.clone(),
+        sheet: summary.sheet,
+        records_processed: processed,
+        records_inserted: inserted,
+        records_updated: updated,
+        records_skipped: skipped,
+        records_failed: failed,
+        duplicate_check_columns: req.dedup.as_ref().map(|p| p.describe()),
+        mapping: summary.mapping,
+        unmapped_headers: summary.unmapped_headers,
+        errors: tally.row_errors.iter().map(|e| format!("Row {}: {}", e.row, e.message)).collect(),
+        row_errors: tally.row_errors,
+        dry_run: req.dry_run,
+        rows: req.dry_run.then_some(tally.rows),
+    })
+}
+
+// POST /api/import/spreadsheet
+pub async fn import_spreadsheet(
+    data: web::Data<Arc<ApiState>>,
+    session: Session,
+    req: web::Json<ImportRequest>,
+) -> Result<HttpResponse> {
+    let response = import(&data, &req).await;
+    if !req.dry_run {
+        let changes = json!({
+            "file_path": req.file_path,
+            "upload_id": req.upload_id,
+            "sheet_name": req.sheet_name,
+            "column_mappings": req.column_mappings
+        });
+        crate::audit::record(&data, &session, "import.spreadsheet", &req.table_name, changes, &response).await;
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str, required: bool) -> TargetColumn {
+        TargetColumn {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            udt_schema: "pg_catalog".to_string(),
+            udt_name: String::new(),
+            required,
+            max_length: (data_type == "character varying").then_some(10),
+            numeric_precision: (data_type == "numeric").then_some(8),
+            numeric_scale: (data_type == "numeric").then_some(2),
+        }
+    }
+
+    fn text(s: &str) -> Cell {
+        Cell::Text(s.to_string())
+    }
+
+    #[test]
+    fn cells_are_coerced_to_column_types() {
+        let int = column("n", "integer", false);
+        assert_eq!(coerce(&Cell::Float(42.0), &int).unwrap().as_deref(), Some("42"));
+        assert_eq!(coerce(&text("1,200"), &int).unwrap().as_deref(), Some("1200"));
+        assert!(coerce(&text("12.5"), &int).is_err());
+        assert!(coerce(&text("3000000000"), &int).is_err());
+
+        let amount = column("amount", "numeric", false);
+        assert_eq!(coerce(&text("$1,200.50"), &amount).unwrap().as_deref(), Some("1200.50"));
+        assert_eq!(coerce(&text("1000000"), &amount).unwrap_err(), "amount is out of range for NUMERIC(8,2)");
+
+        let flag = column("converted", "boolean", false);
+        assert_eq!(coerce(&text("Yes"), &flag).unwrap().as_deref(), Some("true"));
+        assert!(coerce(&text("maybe"), &flag).is_err());
+
+        let day = column("date_closed", "date", false);
+        assert_eq!(coerce(&text("3/14/2025"), &day).unwrap().as_deref(), Some("2025-03-14"));
+        let excel = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap().and_hms_opt(9, 30, 0).unwrap();
+        assert_eq!(coerce(&Cell::DateTime(excel), &day).unwrap().as_deref(), Some("2025-03-14"));
+        let at = column("date_start", "timestamp with time zone", false);
+        assert_eq!(coerce(&Cell::DateTime(excel), &at).unwrap().as_deref(), Some("2025-03-14 09:30:00"));
+
+        let name = column("name", "character varying", true);
+        assert_eq!(coerce(&text("  "), &name).unwrap_err(), "name is required");
+        assert_eq!(coerce(&text("abcdefghijk"), &name).unwrap_err(), "name exceeds 10 characters (got 11)");
+        assert_eq!(coerce(&Cell::Float(7.0), &name).unwrap().as_deref(), Some("7"));
+        assert_eq!(coerce(&Cell::Empty, &int).unwrap(), None);
+    }
+
+    #[test]
+    fn headers_map_by_name_or_explicitly() {
+        let columns = vec![column("last_name", "character varying", true), column("email", "character varying", false)];
+        let headers = vec!["Last Name".to_string(), "E-mail".to_string(), "Notes".to_string()];
+        assert_eq!(resolve_mapping(&headers, &columns, None).unwrap(), vec![(0, 0)]);
+
+        let explicit = HashMap::from([("E-mail".to_string(), "email".to_string()), ("Last Name".to_string(), "last_name".to_string())]);
+        assert_eq!(resolve_mapping(&headers, &columns, Some(&explicit)).unwrap(), vec![(1, 1), (0, 0)]);
+
+        let bad = HashMap::from([("Notes".to_string(), "description".to_string()), ("Phone".to_string(), "email".to_string())]);
+        assert_eq!(resolve_mapping(&headers, &columns, Some(&bad)).unwrap_err(), vec![
+            "Column 'description' does not exist",
+            "Header 'Phone' is not in the sheet",
+        ]);
+        let twice = HashMap::from([("Notes".to_string(), "email".to_string()), ("E-mail".to_string(), "email".to_string())]);
+        assert!(resolve_mapping(&headers, &columns, Some(&twice)).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+// src/sql_sandbox.rs
+//! Read-only sandbox for ad-hoc queries from `/api/db/query`.
+//!
+//! A query must be a single SELECT / WITH / VALUES / TABLE statement and may not call
+//! functions that sleep, touch the filesystem or change session settings. It then runs in a
+//! `READ ONLY` transaction with a `statement_timeout`, optionally as a dedicated role, and is
+//! wrapped in a LIMIT so at most `max_rows` rows come back.
+//!
+//! The keyword and function checks only catch mistakes and the obvious attempts. The security
+//! boundary is the dedicated role: give `SQL_SANDBOX_ROLE` read access to the tables users may
+//! query and nothing else, since a denylist can't anticipate every way to reach a function.
+//!
+//! Settings come from `SQL_SANDBOX_TIMEOUT_MS` (default 5000), `SQL_SANDBOX_MAX_ROWS`
+//! (default 1000) and `SQL_SANDBOX_ROLE`; `{CONNECTION}_SANDBOX_ROLE` overrides the role
+//! for one named connection.
+use serde_json::Value;
+use sqlx::{Executor, Pool, Postgres, Transaction};
+use std::fmt;
+use std::time::Instant;
+
+const ALLOWED_LEADING_KEYWORDS: &[&str] = &["select", "with", "values", "table"];
+
+/// Functions that could stall the server, read files, reach other servers, or undo the sandbox settings
+const DENIED_FUNCTIONS: &[&str] = &[
+    "pg_sleep", "pg_sleep_for", "pg_sleep_until",
+    "set_config",
+    "pg_read_file", "pg_read_binary_file", "pg_ls_dir", "pg_stat_file",
+    "pg_ls_logdir", "pg_ls_waldir", "pg_ls_tmpdir", "pg_ls_archive_statusdir",
+    "lo_import", "lo_export", "lo_get", "lo_open", "lo_creat", "lo_create",
+    "dblink", "dblink_exec", "dblink_connect", "dblink_send_query",
+    "pg_terminate_backend", "pg_cancel_backend", "pg_reload_conf", "pg_rotate_logfile",
+    "pg_advisory_lock", "pg_advisory_xact_lock", "pg_advisory_lock_shared", "pg_advisory_xact_lock_shared",
+    "query_to_xml", "query_to_xml_and_xmlschema", "query_to_xmlschema", "cursor_to_xml",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxConfig {
+    pub timeout_ms: u64,
+    pub max_rows: usize,
+    pub role: Option<String>,
+}
+
+impl SandboxConfig {
+    pub fn for_connection(connection: Option<&str>) -> Self {
+        let env_parse = |key: &str, default| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+        let role = connection
+            .and_then(|name| std::env::var(format!("{name}_SANDBOX_ROLE")).ok())
+            .or_else(|| std::env::var("SQL_SANDBOX_ROLE").ok())
+            .filter(|r| !r.trim().is_empty());
+
+        Self {
+            timeout_ms: env_parse("SQL_SANDBOX_TIMEOUT_MS", 5000) as u64,
+            max_rows: env_parse("SQL_SANDBOX_MAX_ROWS", 1000),
+            role,
+        }
+    }
+
+    /// Exports stream for as long as the client keeps reading, so they get their own,
+    /// longer timeout (SQL_EXPORT_TIMEOUT_MS, default 5 minutes)
+    pub fn for_export(connection: Option<&str>) -> Self {
+        let timeout_ms = std::env::var("SQL_EXPORT_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(300_000);
+        Self { timeout_ms, ..Self::for_connection(connection) }
+    }
+}
+
+#[derive(Debug)]
+pub enum SandboxError {
+    /// The query text was refused before reaching the database
+    Rejected(String),
+    Timeout(u64),
+    Database(sqlx::Error),
+}
+
+impl SandboxError {
+    /// Errors caused by the query itself, as opposed to connection or server trouble
+    pub fn is_client_error(&self) -> bool {
+        match self {
+            SandboxError::Database(e) => matches!(e, sqlx::Error::Database(_)),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxError::Rejected(reason) => write!(f, "{reason}"),
+            SandboxError::Timeout(ms) => write!(f, "Query exceeded the {ms} ms statement timeout"),
+            SandboxError::Database(e) => write!(f, "Query failed: {e}"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SandboxError {
+    fn from(e: sqlx::Error) -> Self {
+        SandboxError::Database(e)
+    }
+}
+
+/// 57014 query_canceled is what statement_timeout raises
+fn is_timeout(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.code().as_deref() == Some("57014"))
+}
+
+#[derive(Debug)]
+pub struct SandboxResult {
+    pub rows: Vec<Value>,
+    /// More rows matched than were returned
+    pub truncated: bool,
+    pub max_rows: usize,
+    pub elapsed_ms: u128,
+}
+
+/// Words outside comments and literals, plus the byte offset of every top-level `;`
+struct Scan {
+    words: Vec<String>,
+    semicolons: Vec<usize>,
+    /// `$n` placeholders as (byte range, n)
+    placeholders: Vec<(std::ops::Range<usize>, usize)>,
+    /// End of the last code character that isn't whitespace, a comment or `;`
+    code_end: usize,
+}
+
+fn scan(sql: &str) -> Result<Scan, String> {
+    let bytes = sql.as_bytes();
+    let mut words = Vec::new();
+    let mut semicolons = Vec::new();
+    let mut placeholders = Vec::new();
+    let mut code_end = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                // Postgres block comments nest
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                if depth != 0 {
+                    return Err("Unterminated block comment".to_string());
+                }
+            }
+            b'\'' => {
+                let escapes = i > 0 && matches!(bytes[i - 1], b'e' | b'E') && !is_word_byte(*bytes.get(i.wrapping_sub(2)).unwrap_or(&b' '));
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => return Err("Unterminated string literal".to_string()),
+                        Some(b'\\') if escapes => i += 2,
+                        Some(b'\'') if bytes.get(i + 1) == Some(&b'\'') => i += 2,
+                        Some(b'\'') => break,
+                        Some(_) => i += 1,
+                    }
+                }
+                i += 1;
+                code_end = i;
+            }
+            b'"' => {
+                // U&"pg\0073leep" spells an identifier with escapes the denylist would not see
+                if i >= 2 && bytes[i - 1] == b'&' && matches!(bytes[i - 2], b'u' | b'U') && !(i >= 3 && is_word_byte(bytes[i - 3])) {
+                    return Err("Unicode-escaped identifiers (U&\"...\") are not allowed".to_string());
+                }
+                // Quoted identifiers still name functions, so keep them as words
+                let start = i + 1;
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => return Err("Unterminated quoted identifier".to_string()),
+                        Some(b'"') if bytes.get(i + 1) == Some(&b'"') => i += 2,
+                        Some(b'"') => break,
+                        Some(_) => i += 1,
+                    }
+                }
+                words.push(sql[start..i].replace("\"\"", "\"").to_lowercase());
+                i += 1;
+                code_end = i;
+            }
+            b'$' if dollar_tag(bytes, i).is_some() => {
+                let tag_len = dollar_tag(bytes, i).unwrap();
+                let tag = &sql[i..i + tag_len];
+                let body_start = i + tag_len;
+                let close = sql[body_start..]
+                    .find(tag)
+                    .ok_or_else(|| "Unterminated dollar-quoted string".to_string())?;
+                i = body_start + close + tag_len;
+                code_end = i;
+            }
+            b'$' if bytes.get(i + 1).is_some_and(u8::is_ascii_digit) && !(i > 0 && is_word_byte(bytes[i - 1])) => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let n = sql[start + 1..i].parse().map_err(|_| format!("Invalid parameter {}", &sql[start..i]))?;
+                placeholders.push((start..i, n));
+                code_end = i;
+            }
+            b';' => {
+                semicolons.push(i);
+                i += 1;
+            }
+            _ if is_word_byte(c) => {
+                let start = i;
+                while i < bytes.len() && is_word_byte(bytes[i]) {
+                    i += 1;
+                }
+                words.push(sql[start..i].to_lowercase());
+                code_end = i;
+            }
+            _ if c.is_ascii_whitespace() => i += 1,
+            _ => {
+                i += 1;
+                code_end = i;
+            }
+        }
+    }
+
+    Ok(Scan { words, semicolons, placeholders, code_end })
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+/// Length of a `$tag$` opener at `i` (including both `$`), if there is one
+fn dollar_tag(bytes: &[u8], i: usize) -> Option<usize> {
+    // `$1` is a parameter, and `a$b` is part of an identifier
+    if i > 0 && is_word_byte(bytes[i - 1]) {
+        return None;
+    }
+    let mut j = i + 1;
+    if bytes.get(j).is_some_and(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    while j < bytes.len() && (bytes[j].is_ascii_alphanumeric() || bytes[j] == b'_') {
+        j += 1;
+    }
+    (bytes.get(j) == Some(&b'$')).then_some(j + 1 - i)
+}
+
+/// Check the query and return it without any trailing `;`
+pub fn validate(sql: &str) -> Result<&str, SandboxError> {
+    let scan = scan(sql).map_err(SandboxError::Rejected)?;
+
+    if scan.semicolons.iter().any(|&pos| pos < scan.code_end) {
+        return Err(SandboxError::Rejected("Only a single statement is allowed".to_string()));
+    }
+    match scan.words.first() {
+        Some(first) if ALLOWED_LEADING_KEYWORDS.contains(&first.as_str()) => {}
+        _ => return Err(SandboxError::Rejected("Only SELECT queries are allowed".to_string())),
+    }
+    if let Some(denied) = scan.words.iter().find(|w| DENIED_FUNCTIONS.contains(&w.as_str())) {
+        return Err(SandboxError::Rejected(format!("{denied} is not allowed in sandboxed queries")));
+    }
+    Ok(&sql[..scan.code_end])
+}
+
+/// Check the query and wrap each `$n` in `CAST($n AS type)`, with `types[n - 1]` as the type,
+/// so parameters can be bound as text. `types` must come from a fixed list, never from input.
+pub fn cast_parameters(sql: &str, types: &[&str]) -> Result<String, SandboxError> {
+    let statement = validate(sql)?;
+    let scan = scan(statement).map_err(SandboxError::Rejected)?;
+
+    let mut cast = String::with_capacity(statement.len());
+    let mut last = 0;
+    for (range, n) in scan.placeholders {
+        let Some(ty) = n.checked_sub(1).and_then(|i| types.get(i)) else {
+            return Err(SandboxError::Rejected(format!(
+                "${n} has no matching parameter ({} declared)",
+                types.len()
+            )));
+        };
+        cast.push_str(&statement[last..range.start]);
+        cast.push_str(&format!("CAST(${n} AS {ty})"));
+        last = range.end;
+    }
+    cast.push_str(&statement[last..]);
+    Ok(cast)
+}
+
+fn is_valid_role(role: &str) -> bool {
+    !role.is_empty() && role.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Open a read-only transaction with the sandbox's statement timeout and role applied.
+/// Roll it back when done; there is nothing to keep.
+pub async fn begin(pool: &Pool<Postgres>, config: &SandboxConfig) -> Result<Transaction<'static, Postgres>, SandboxError> {
+    let mut tx = pool.begin().await?;
+    (&mut *tx).execute("SET TRANSACTION READ ONLY").await?;
+    (&mut *tx)
+        .execute(format!("SET LOCAL statement_timeout = {}", config.timeout_ms).as_str())
+        .await?;
+    if let Some(role) = &config.role {
+        if !is_valid_role(role) {
+            return Err(SandboxError::Rejected(format!("Invalid sandbox role name '{role}'")));
+        }
+        (&mut *tx).execute(format!("SET LOCAL ROLE \"{role}\"").as_str()).await?;
+    }
+    Ok(tx)
+}
+
+/// Run a query inside the sandbox. `max_rows` can lower (never raise) the configured cap.
+pub async fn run(
+    pool: &Pool<Postgres>,
+    sql: &str,
+    config: &SandboxConfig,
+    max_rows: Option<usize>,
+) -> Result<SandboxResult, SandboxError> {
+    run_with_params(pool, sql, &[], config, max_rows).await
+}
+
+/// Like [`run`], binding `params` as text to `$1..$n` (see [`cast_parameters`])
+pub async fn run_with_params(
+    pool: &Pool<Postgres>,
+    sql: &str,
+    params: &[Option<String>],
+    config: &SandboxConfig,
+    max_rows: Option<usize>,
+) -> Result<SandboxResult, SandboxError> {
+    let statement = validate(sql)?;
+    let max_rows = max_rows.map_or(config.max_rows, |n| n.clamp(1, config.max_rows));
+    let started = Instant::now();
+
+    let mut tx = begin(pool, config).await?;
+    // Newlines keep a trailing `--` comment from swallowing the closing parenthesis
+    let limited = format!("SELECT * FROM (\n{statement}\n) AS sandboxed LIMIT {}", max_rows + 1);
+    let mut query = sqlx::query(&limited);
+    for param in params {
+        query = query.bind(param);
+    }
+    let result = query.fetch_all(&mut *tx).await;
+    // Nothing to keep from a read-only transaction
+    tx.rollback().await.ok();
+
+    let mut rows = result.map_err(|e| {
+        if is_timeout(&e) {
+            SandboxError::Timeout(config.timeout_ms)
+        } else {
+            SandboxError::Database(e)
+        }
+    })?;
+    let truncated = rows.len() > max_rows;
+    rows.truncate(max_rows);
+
+    Ok(SandboxResult {
+        rows: rows.iter().map(crate::row_to_json).collect(),
+        truncated,
+        max_rows,
+        elapsed_ms: started.elapsed().as_millis(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(sql: &str) -> bool {
+        matches!(validate(sql), Err(SandboxError::Rejected(_)))
+    }
+
+    #[test]
+    fn accepts_single_read_statements() {
+        assert_eq!(validate("SELECT 1;").unwrap(), "SELECT 1");
+        assert_eq!(validate("  select 1 ; -- done\n").unwrap(), "  select 1");
+        assert!(validate("WITH t AS (SELECT 1) SELECT * FROM t").is_ok());
+        assert!(validate("SELECT ';' AS semi, 'it''s' AS quote, $$;$$ AS dollar").is_ok());
+        assert!(validate("SELECT 1 /* ; nested /* ; */ */").is_ok());
+        assert!(validate("SELECT * FROM t WHERE a = $1").is_ok());
+        // E'' strings use backslash escapes, so this is one literal, not two statements
+        assert!(validate("SELECT E'\\'; DROP TABLE users; --'").is_ok());
+    }
+
+    #[test]
+    fn rejects_multiple_statements_and_writes() {
+        assert!(rejected("SELECT 1; DROP TABLE users"));
+        assert!(rejected("SELECT 1;;SELECT 2"));
+        assert!(rejected("DELETE FROM users"));
+        assert!(rejected("-- SELECT\nUPDATE users SET email = NULL"));
+        assert!(rejected("SELECT 'unterminated"));
+    }
+
+    #[test]
+    fn rejects_denied_functions_however_spelled() {
+        assert!(rejected("SELECT pg_sleep(10)"));
+        assert!(rejected("SELECT PG_CATALOG.PG_SLEEP(10)"));
+        assert!(rejected("SELECT \"pg_sleep\"(10)"));
+        assert!(rejected("SELECT set_config('statement_timeout', '0', true)"));
+        assert!(rejected(r#"SELECT U&"pg_read_fil\0065"('/etc/passwd')"#));
+        assert!(rejected(r#"SELECT u&"pg\0073leep"(60)"#));
+        // A column named like the prefix is still fine
+        assert!(validate(r#"SELECT menu&"x" FROM t"#).is_ok());
+        // Only literal text mentions it here, so this is fine
+        assert!(validate("SELECT 'pg_sleep' AS name").is_ok());
+    }
+
+    #[test]
+    fn casts_placeholders_outside_literals() {
+        assert_eq!(
+            cast_parameters("SELECT * FROM t WHERE a = $1 AND b <= $2 AND c = '$1' -- $2\n;", &["integer", "date"]).unwrap(),
+            "SELECT * FROM t WHERE a = CAST($1 AS integer) AND b <= CAST($2 AS date) AND c = '$1'"
+        );
+        assert_eq!(cast_parameters("SELECT $1, $1", &["text"]).unwrap(), "SELECT CAST($1 AS text), CAST($1 AS text)");
+        assert!(cast_parameters("SELECT $2", &["text"]).is_err());
+        assert!(cast_parameters("SELECT $0", &["text"]).is_err());
+        assert!(cast_parameters("DELETE FROM t WHERE id = $1", &["uuid"]).is_err());
+    }
+}
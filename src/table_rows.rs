@@ -0,0 +1,771 @@
+// src/table_rows.rs
+//! Paged rows for the data grid (`POST /api/db/table-rows`) with server-side filtering,
+//! multi-column sort and full-text search.
+//!
+//! `table` may be `schema.table` (or pass `schema`); table and column names are checked against
+//! `information_schema.columns` before they are quoted into SQL; filter values are always bound as text and cast to the column's own type.
+//!
+//! Large tables can page by cursor instead of OFFSET: each response carries `next_cursor`, the
+//! last row's position in the sort order extended by the primary key.
+use actix_web::{web, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres, QueryBuilder, Row};
+use std::sync::Arc;
+
+use crate::schema::TableRef;
+use crate::ApiState;
+
+pub use crate::schema::quote_ident;
+
+/// Guards against runaway filter trees from the client
+const MAX_FILTER_DEPTH: usize = 8;
+const MAX_CONDITIONS: usize = 100;
+const MAX_IN_VALUES: usize = 1000;
+
+/// Extra column carrying each row's keyset values; stripped before rows are returned
+const CURSOR_COLUMN: &str = "__cursor";
+
+#[derive(Deserialize)]
+pub struct TableRowsRequest {
+    /// `table` or `schema.table`
+    pub table: String,
+    /// Schema for an unqualified `table`; defaults to the connection's current schema
+    pub schema: Option<String>,
+    pub connection: Option<String>,
+    pub page: Option<i64>,
+    pub size: Option<i64>,
+    /// Single-column sort, kept for older clients; `sort` wins when both are given
+    pub sort_field: Option<String>,
+    pub sort_dir: Option<String>,
+    #[serde(default)]
+    pub sort: Vec<SortSpec>,
+    pub filter: Option<Filter>,
+    /// Full-text search across the table's text columns
+    pub q: Option<String>,
+    /// `next_cursor` from the previous response; replaces `page` for deep paging
+    pub cursor: Option<String>,
+    pub count: Option<CountMode>,
+    /// Also return soft-deleted rows of tables with a `deleted` flag
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SortSpec {
+    pub field: String,
+    pub dir: Option<String>,
+}
+
+/// A filter tree. A bare array means AND, which is also what Tabulator sends as `filter`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Filter {
+    All(Vec<Filter>),
+    And { and: Vec<Filter> },
+    Or { or: Vec<Filter> },
+    Condition(Condition),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Condition {
+    pub field: String,
+    /// Tabulator calls this `type`
+    #[serde(alias = "type")]
+    pub op: Op,
+    #[serde(default)]
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    #[serde(alias = "=")]
+    Eq,
+    #[serde(alias = "!=")]
+    Ne,
+    #[serde(alias = "<")]
+    Lt,
+    #[serde(alias = "<=")]
+    Lte,
+    #[serde(alias = ">")]
+    Gt,
+    #[serde(alias = ">=")]
+    Gte,
+    /// `value` is `[low, high]`, inclusive
+    Between,
+    /// Case-insensitive match of `value` anywhere in the column; Tabulator's "like"
+    #[serde(alias = "like")]
+    Contains,
+    /// Case-insensitive pattern with the caller's own `%` / `_` wildcards
+    Ilike,
+    In,
+    /// `value: true` for IS NULL, `false` for IS NOT NULL
+    IsNull,
+}
+
+impl Op {
+    fn sql(&self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "<>",
+            Op::Lt => "<",
+            Op::Lte => "<=",
+            Op::Gt => ">",
+            Op::Gte => ">=",
+            _ => unreachable!("operator has no single SQL symbol"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub udt_schema: String,
+    pub udt_name: String,
+}
+
+impl ColumnInfo {
+    fn is_text(&self) -> bool {
+        matches!(self.data_type.as_str(), "text" | "character varying" | "character")
+    }
+
+    /// Cast target for bound text values, e.g. `"pg_catalog"."int4"`
+    fn cast_type(&self) -> String {
+        format!("{}.{}", quote_ident(&self.udt_schema), quote_ident(&self.udt_name))
+    }
+}
+
+impl TableRowsRequest {
+    pub fn table_ref(&self) -> Result<TableRef, String> {
+        TableRef::resolve(&self.table, self.schema.as_deref())
+    }
+}
+
+/// Columns of a table, in table order. Empty if the table doesn't exist.
+pub async fn table_columns(pool: &Pool<Postgres>, table: &TableRef) -> Result<Vec<ColumnInfo>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT column_name::text, data_type::text, udt_schema::text, udt_name::text
+        FROM information_schema.columns
+        WHERE table_schema = COALESCE($1, current_schema()) AND table_name = $2
+        ORDER BY ordinal_position
+        "#
+    )
+    .bind(table.schema())
+    .bind(&table.name)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ColumnInfo {
+            name: row.get(0),
+            data_type: row.get(1),
+            udt_schema: row.get(2),
+            udt_name: row.get(3),
+        })
+        .collect())
+}
+
+fn find_column<'a>(columns: &'a [ColumnInfo], field: &str) -> Result<&'a ColumnInfo, String> {
+    columns
+        .iter()
+        .find(|c| c.name == field)
+        .ok_or_else(|| format!("Unknown column '{field}'"))
+}
+
+/// JSON filter value as the text Postgres will cast from
+fn value_text(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(format!("Unsupported filter value {other}")),
+    }
+}
+
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+fn count_conditions(filter: &Filter, depth: usize) -> Result<usize, String> {
+    if depth > MAX_FILTER_DEPTH {
+        return Err(format!("Filters may nest at most {MAX_FILTER_DEPTH} levels"));
+    }
+    match filter {
+        Filter::All(items) | Filter::And { and: items } | Filter::Or { or: items } => items
+            .iter()
+            .map(|f| count_conditions(f, depth + 1))
+            .sum(),
+        Filter::Condition(_) => Ok(1),
+    }
+}
+
+fn push_filter(qb: &mut QueryBuilder<'_, Postgres>, filter: &Filter, columns: &[ColumnInfo]) -> Result<(), String> {
+    let (items, joiner) = match filter {
+        Filter::All(items) | Filter::And { and: items } => (items, " AND "),
+        Filter::Or { or: items } => (items, " OR "),
+        Filter::Condition(condition) => return push_condition(qb, condition, columns),
+    };
+    if items.is_empty() {
+        // Empty AND matches everything, empty OR matches nothing
+        qb.push(if joiner == " AND " { "TRUE" } else { "FALSE" });
+        return Ok(());
+    }
+    qb.push("(");
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            qb.push(joiner);
+        }
+        push_filter(qb, item, columns)?;
+    }
+    qb.push(")");
+    Ok(())
+}
+
+fn push_condition(qb: &mut QueryBuilder<'_, Postgres>, condition: &Condition, columns: &[ColumnInfo]) -> Result<(), String> {
+    let column = find_column(columns, &condition.field)?;
+    let ident = quote_ident(&column.name);
+    let cast = column.cast_type();
+
+    match condition.op {
+        Op::IsNull => {
+            let is_null = condition.value.as_bool().unwrap_or(true);
+            qb.push(format!("{ident} IS {}NULL", if is_null { "" } else { "NOT " }));
+        }
+        Op::Eq | Op::Ne if condition.value.is_null() => {
+            qb.push(format!("{ident} IS {}NULL", if condition.op == Op::Ne { "NOT " } else { "" }));
+        }
+        Op::Eq | Op::Ne | Op::Lt | Op::Lte | Op::Gt | Op::Gte => {
+            qb.push(format!("{ident} {} CAST(", condition.op.sql()));
+            qb.push_bind(value_text(&condition.value)?);
+            qb.push(format!(" AS {cast})"));
+        }
+        Op::Between => {
+            let bounds = condition.value.as_array().filter(|b| b.len() == 2).ok_or_else(|| {
+                format!("between on '{}' needs a [low, high] value", column.name)
+            })?;
+            qb.push(format!("{ident} BETWEEN CAST("));
+            qb.push_bind(value_text(&bounds[0])?);
+            qb.push(format!(" AS {cast}) AND CAST("));
+            qb.push_bind(value_text(&bounds[1])?);
+            qb.push(format!(" AS {cast})"));
+        }
+        Op::Contains | Op::Ilike => {
+            let text = value_text(&condition.value)?;
+            let pattern = if condition.op == Op::Contains { format!("%{}%", escape_like(&text)) } else { text };
+            qb.push(format!("{ident}::text ILIKE "));
+            qb.push_bind(pattern);
+        }
+        Op::In => {
+            let values = condition
+                .value
+                .as_array()
+                .ok_or_else(|| format!("in on '{}' needs an array value", column.name))?;
+            if values.is_empty() {
+                qb.push("FALSE");
+                return Ok(());
+            }
+            if values.len() > MAX_IN_VALUES {
+                return Err(format!("in accepts at most {MAX_IN_VALUES} values"));
+            }
+            qb.push(format!("{ident} IN ("));
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    qb.push(", ");
+                }
+                qb.push("CAST(");
+                qb.push_bind(value_text(value)?);
+                qb.push(format!(" AS {cast})"));
+            }
+            qb.push(")");
+        }
+    }
+    Ok(())
+}
+
+/// Append a WHERE clause for `filter` and `q`; further conditions can follow with " AND ..."
+pub fn push_where(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    filter: Option<&Filter>,
+    search: Option<&str>,
+    columns: &[ColumnInfo],
+) -> Result<(), String> {
+    let search = search.map(str::trim).filter(|q| !q.is_empty());
+    qb.push(" WHERE TRUE");
+    if let Some(filter) = filter {
+        if count_conditions(filter, 0)? > MAX_CONDITIONS {
+            return Err(format!("At most {MAX_CONDITIONS} filter conditions are allowed"));
+        }
+        qb.push(" AND ");
+        push_filter(qb, filter, columns)?;
+    }
+    if let Some(q) = search {
+        let text_columns: Vec<String> = columns.iter().filter(|c| c.is_text()).map(|c| quote_ident(&c.name)).collect();
+        if text_columns.is_empty() {
+            return Err("This table has no text columns to search".to_string());
+        }
+        qb.push(format!(
+            " AND to_tsvector('simple', concat_ws(' ', {})) @@ websearch_to_tsquery('simple', ",
+            text_columns.join(", ")
+        ));
+        qb.push_bind(q.to_string());
+        qb.push(")");
+    }
+    Ok(())
+}
+
+/// Columns to order by, with `true` for descending: `sort` (or the legacy single field)
+pub fn sort_keys<'a>(req: &TableRowsRequest, columns: &'a [ColumnInfo]) -> Result<Vec<(&'a ColumnInfo, bool)>, String> {
+    let legacy;
+    let specs: &[SortSpec] = if req.sort.is_empty() {
+        match &req.sort_field {
+            Some(field) => {
+                legacy = [SortSpec { field: field.clone(), dir: req.sort_dir.clone() }];
+                &legacy
+            }
+            None => &[],
+        }
+    } else {
+        &req.sort
+    };
+
+    specs
+        .iter()
+        .map(|spec| {
+            let column = find_column(columns, &spec.field)?;
+            let desc = spec.dir.as_deref().is_some_and(|d| d.eq_ignore_ascii_case("desc"));
+            Ok((column, desc))
+        })
+        .collect()
+}
+
+/// ORDER BY clause for `keys`, empty when unsorted
+pub fn order_by(keys: &[(&ColumnInfo, bool)]) -> String {
+    if keys.is_empty() {
+        return String::new();
+    }
+    let terms: Vec<String> = keys
+        .iter()
+        .map(|(column, desc)| format!("{} {}", quote_ident(&column.name), if *desc { "DESC" } else { "ASC" }))
+        .collect();
+    format!(" ORDER BY {}", terms.join(", "))
+}
+
+/// Primary key columns of a table, in key order
+pub async fn primary_key(pool: &Pool<Postgres>, table: &TableRef) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT a.attname::text
+        FROM pg_index i
+        JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+        WHERE i.indrelid = to_regclass(format('%I.%I', COALESCE($1, current_schema()), $2)) AND i.indisprimary
+        ORDER BY array_position(i.indkey::int2[], a.attnum)
+        "#
+    )
+    .bind(table.schema())
+    .bind(&table.name)
+    .fetch_all(pool)
+    .await
+}
+
+/// Sort keys extended with the primary key so every row has a unique position.
+/// None when the table has no primary key, in which case cursors aren't available.
+fn keyset<'a>(
+    mut keys: Vec<(&'a ColumnInfo, bool)>,
+    primary_key: &[String],
+    columns: &'a [ColumnInfo],
+) -> Option<Vec<(&'a ColumnInfo, bool)>> {
+    if primary_key.is_empty() {
+        return None;
+    }
+    for name in primary_key {
+        if !keys.iter().any(|(c, _)| &c.name == name) {
+            keys.push((find_column(columns, name).ok()?, false));
+        }
+    }
+    Some(keys)
+}
+
+/// Position of the last row on a page: the keyset columns and that row's values as text
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    #[serde(rename = "k")]
+    pub keys: Vec<String>,
+    #[serde(rename = "v")]
+    pub values: Vec<Option<String>>,
+}
+
+impl Cursor {
+    /// Opaque token handed to the client as `next_cursor`
+    pub fn encode(&self) -> String {
+        urlencoding::encode(&serde_json::to_string(self).unwrap_or_default()).into_owned()
+    }
+
+    pub fn decode(token: &str) -> Result<Self, String> {
+        urlencoding::decode(token)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .ok_or_else(|| "Invalid cursor".to_string())
+    }
+}
+
+/// `column <op> value` as seen from the ORDER BY: ascending sorts put NULLs last, descending first
+fn push_key_compare(qb: &mut QueryBuilder<'_, Postgres>, column: &ColumnInfo, desc: bool, value: &Option<String>, after: bool) {
+    let ident = quote_ident(&column.name);
+    match (value, after) {
+        (None, false) => {
+            qb.push(format!("{ident} IS NULL"));
+        }
+        (None, true) => {
+            qb.push(if desc { format!("{ident} IS NOT NULL") } else { "FALSE".to_string() });
+        }
+        (Some(value), _) => {
+            let op = if !after { "=" } else if desc { "<" } else { ">" };
+            qb.push(format!("({ident} {op} CAST("));
+            qb.push_bind(value.clone());
+            qb.push(format!(" AS {})", column.cast_type()));
+            if after && !desc {
+                qb.push(format!(" OR {ident} IS NULL"));
+            }
+            qb.push(")");
+        }
+    }
+}
+
+/// Append " AND <rows after the cursor>" for the given keyset
+pub fn push_after(qb: &mut QueryBuilder<'_, Postgres>, keys: &[(&ColumnInfo, bool)], cursor: &Cursor) -> Result<(), String> {
+    let names: Vec<&str> = keys.iter().map(|(c, _)| c.name.as_str()).collect();
+    if cursor.keys != names || cursor.values.len() != keys.len() {
+        return Err("Cursor doesn't match this table and sort order; start again from the first page".to_string());
+    }
+
+    // (k1 > v1) OR (k1 = v1 AND k2 > v2) OR ...
+    qb.push(" AND (");
+    for i in 0..keys.len() {
+        if i > 0 {
+            qb.push(" OR ");
+        }
+        qb.push("(");
+        for ((column, desc), value) in keys.iter().zip(&cursor.values).take(i) {
+            push_key_compare(qb, column, *desc, value, false);
+            qb.push(" AND ");
+        }
+        push_key_compare(qb, keys[i].0, keys[i].1, &cursor.values[i], true);
+        qb.push(")");
+    }
+    qb.push(")");
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CountMode {
+    /// `COUNT(*)` over the filtered rows
+    #[default]
+    Exact,
+    /// `pg_class.reltuples`, or the planner's row estimate when filtering
+    Estimate,
+    /// Skip counting; page with `next_cursor` / `has_more`
+    None,
+}
+
+fn bad_request(error: impl Into<String>) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "error": error.into() }))
+}
+
+/// Errors Postgres raises for filter values that don't fit the column type
+fn is_bad_value(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db)
+        if db.code().is_some_and(|c| c.starts_with("22")))
+}
+
+/// Condition that hides soft-deleted rows, for tables with a SuiteCRM-style `deleted` flag
+/// (boolean here, 0/1 in tables imported from SuiteCRM)
+pub fn not_deleted(columns: &[ColumnInfo]) -> Option<&'static str> {
+    match columns.iter().find(|c| c.name == "deleted")?.data_type.as_str() {
+        "boolean" => Some("\"deleted\" IS NOT TRUE"),
+        "smallint" | "integer" | "bigint" => Some("COALESCE(\"deleted\", 0) = 0"),
+        _ => None,
+    }
+}
+
+/// Builder for `{select} FROM table WHERE <filter and q>`, leaving out soft-deleted rows
+/// unless the request includes them
+pub fn filtered<'a>(select: &str, table: &TableRef, req: &TableRowsRequest, columns: &[ColumnInfo]) -> Result<QueryBuilder<'a, Postgres>, String> {
+    let mut qb = QueryBuilder::new(format!("{select} FROM {}", table.sql()));
+    push_where(&mut qb, req.filter.as_ref(), req.q.as_deref(), columns)?;
+    if let Some(condition) = not_deleted(columns).filter(|_| !req.include_deleted) {
+        qb.push(" AND ").push(condition);
+    }
+    Ok(qb)
+}
+
+fn is_filtered(req: &TableRowsRequest) -> bool {
+    req.filter.is_some() || req.q.as_deref().is_some_and(|q| !q.trim().is_empty())
+}
+
+/// Row count in the requested mode; None for `CountMode::None`
+async fn count_rows(pool: &Pool<Postgres>, table: &TableRef, req: &TableRowsRequest, columns: &[ColumnInfo], mode: CountMode) -> Result<Option<i64>, sqlx::Error> {
+    match mode {
+        CountMode::None => Ok(None),
+        CountMode::Exact => {
+            let mut qb = filtered("SELECT COUNT(*)", table, req, columns).map_err(sqlx::Error::Protocol)?;
+            let total: i64 = qb.build().fetch_one(pool).await?.get(0);
+            Ok(Some(total))
+        }
+        CountMode::Estimate => {
+            if !is_filtered(req) {
+                let reltuples: Option<f32> = sqlx::query_scalar(
+                    "SELECT reltuples FROM pg_class WHERE oid = to_regclass(format('%I.%I', COALESCE($1, current_schema()), $2))"
+                )
+                .bind(table.schema())
+                .bind(&table.name)
+                .fetch_optional(pool)
+                .await?;
+                // -1 means the table has never been analyzed; ask the planner instead
+                if let Some(reltuples) = reltuples.filter(|r| *r >= 0.0) {
+                    return Ok(Some(reltuples as i64));
+                }
+            }
+            let mut qb = filtered("EXPLAIN (FORMAT JSON) SELECT *", table, req, columns).map_err(sqlx::Error::Protocol)?;
+            let plan: Value = qb.build().fetch_one(pool).await?.try_get(0)?;
+            Ok(plan[0]["Plan"]["Plan Rows"].as_f64().map(|rows| rows as i64))
+        }
+    }
+}
+
+// POST /api/db/table-rows
+pub async fn get_table_rows(
+    data: web::Data<Arc<ApiState>>,
+    req: web::Json<TableRowsRequest>,
+) -> Result<HttpResponse> {
+    let pool = if let Some(connection_name) = &req.connection {
+        match data.connections.get(connection_name).await {
+            Ok(pool) => pool,
+            Err(e) => return Ok(HttpResponse::build(e.status_code()).json(json!({
+                "error": e.to_string()
+            }))),
+        }
+    } else {
+        match &data.db {
+            Some(db) => db.clone(),
+            None => return Ok(HttpResponse::ServiceUnavailable().json(json!({
+                "error": "Database not available"
+            }))),
+        }
+    };
+
+    let table = match req.table_ref() {
+        Ok(table) => table,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    let columns = match table_columns(&pool, &table).await {
+        Ok(columns) if columns.is_empty() => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "error": format!("Table '{table}' not found")
+            })));
+        }
+        Ok(columns) => columns,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to read columns of {table}: {e}")
+        }))),
+    };
+    let primary_key = match primary_key(&pool, &table).await {
+        Ok(primary_key) => primary_key,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to read the primary key of {table}: {e}")
+        }))),
+    };
+
+    let page = req.page.unwrap_or(1).max(1);
+    let size = req.size.unwrap_or(200).clamp(1, 1000);
+    let count_mode = req.count.unwrap_or_default();
+
+    let sort = match sort_keys(&req, &columns) {
+        Ok(keys) => keys,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    let keyset = keyset(sort.clone(), &primary_key, &columns);
+    let order_keys = keyset.as_deref().unwrap_or(&sort);
+
+    // The keyset values ride along as text so the next cursor round-trips exactly
+    let select = match &keyset {
+        Some(keys) => {
+            let values: Vec<String> = keys.iter().map(|(c, _)| format!("{}::text", quote_ident(&c.name))).collect();
+            format!("SELECT *, ARRAY[{}] AS {}", values.join(", "), quote_ident(CURSOR_COLUMN))
+        }
+        None => "SELECT *".to_string(),
+    };
+    let mut data_qb = match filtered(&select, &table, &req, &columns) {
+        Ok(qb) => qb,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    match (&req.cursor, &keyset) {
+        (Some(token), Some(keys)) => {
+            if let Err(e) = Cursor::decode(token).and_then(|cursor| push_after(&mut data_qb, keys, &cursor)) {
+                return Ok(bad_request(e));
+            }
+            data_qb.push(format!("{} LIMIT {}", order_by(order_keys), size + 1));
+        }
+        (Some(_), None) => {
+            return Ok(bad_request(format!("Table '{table}' has no primary key, so it can only be paged by page number")));
+        }
+        (None, _) => {
+            data_qb.push(format!("{} LIMIT {} OFFSET {}", order_by(order_keys), size + 1, (page - 1) * size));
+        }
+    }
+
+    let mut rows = match data_qb.build().fetch_all(&pool).await {
+        Ok(rows) => rows,
+        Err(e) if is_bad_value(&e) => return Ok(bad_request(format!("Invalid filter value: {e}"))),
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to fetch rows from {table}: {e}")
+        }))),
+    };
+    let has_more = rows.len() as i64 > size;
+    rows.truncate(size as usize);
+
+    let next_cursor = match (&keyset, rows.last()) {
+        (Some(keys), Some(last)) if has_more => last.try_get::<Vec<Option<String>>, _>(CURSOR_COLUMN).ok().map(|values| {
+            Cursor { keys: keys.iter().map(|(c, _)| c.name.clone()).collect(), values }.encode()
+        }),
+        _ => None,
+    };
+    let rows: Vec<Value> = rows
+        .iter()
+        .map(|row| {
+            let mut value = crate::row_to_json(row);
+            if let Some(object) = value.as_object_mut() {
+                object.remove(CURSOR_COLUMN);
+            }
+            value
+        })
+        .collect();
+
+    let total = match count_rows(&pool, &table, &req, &columns, count_mode).await {
+        Ok(total) => total,
+        Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to count rows in {table}: {e}")
+        }))),
+    };
+    let last_page = total.map(|total| (((total as f64) / (size as f64)).ceil() as i64).max(1));
+
+    Ok(HttpResponse::Ok().json(json!({
+        "last_page": last_page,
+        "data": rows,
+        "total": total,
+        "count_mode": count_mode,
+        "page": page,
+        "size": size,
+        "has_more": has_more,
+        "next_cursor": next_cursor,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<ColumnInfo> {
+        [("year", "smallint", "int2"), ("country", "character varying", "varchar"), ("amount", "numeric", "numeric")]
+            .into_iter()
+            .map(|(name, data_type, udt)| ColumnInfo {
+                name: name.to_string(),
+                data_type: data_type.to_string(),
+                udt_schema: "pg_catalog".to_string(),
+                udt_name: udt.to_string(),
+            })
+            .collect()
+    }
+
+    fn where_sql(filter: Value, q: Option<&str>) -> Result<String, String> {
+        let filter: Filter = serde_json::from_value(filter).unwrap();
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT * FROM t");
+        push_where(&mut qb, Some(&filter), q, &columns())?;
+        Ok(qb.sql().to_string())
+    }
+
+    #[test]
+    fn builds_nested_and_or_filters_with_casts() {
+        let sql = where_sql(json!({
+            "and": [
+                { "field": "year", "op": "between", "value": [2015, 2019] },
+                { "or": [
+                    { "field": "country", "op": "in", "value": ["US", "CA"] },
+                    { "field": "amount", "op": ">=", "value": 10.5 }
+                ]}
+            ]
+        }), None)
+        .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM t WHERE TRUE AND (\"year\" BETWEEN CAST($1 AS \"pg_catalog\".\"int2\") AND CAST($2 AS \"pg_catalog\".\"int2\") AND \
+             (\"country\" IN (CAST($3 AS \"pg_catalog\".\"varchar\"), CAST($4 AS \"pg_catalog\".\"varchar\")) OR \"amount\" >= CAST($5 AS \"pg_catalog\".\"numeric\")))"
+        );
+    }
+
+    #[test]
+    fn accepts_tabulator_filter_arrays_and_search() {
+        let sql = where_sql(json!([{ "field": "country", "type": "like", "value": "U" }]), Some("steel mill")).unwrap();
+        assert!(sql.contains("(\"country\"::text ILIKE $1)"));
+        assert!(sql.ends_with("to_tsvector('simple', concat_ws(' ', \"country\")) @@ websearch_to_tsquery('simple', $2)"));
+    }
+
+    #[test]
+    fn rejects_unknown_columns_and_bad_values() {
+        assert_eq!(where_sql(json!({ "field": "nope", "op": "eq", "value": 1 }), None).unwrap_err(), "Unknown column 'nope'");
+        assert!(where_sql(json!({ "field": "year", "op": "between", "value": 2019 }), None).is_err());
+        assert!(where_sql(json!({ "field": "year", "op": "eq", "value": { "x": 1 } }), None).is_err());
+
+        let req: TableRowsRequest = serde_json::from_value(json!({
+            "table": "t", "sort": [{ "field": "year", "dir": "desc" }, { "field": "country" }]
+        }))
+        .unwrap();
+        assert_eq!(order_by(&sort_keys(&req, &columns()).unwrap()), " ORDER BY \"year\" DESC, \"country\" ASC");
+        let req: TableRowsRequest = serde_json::from_value(json!({ "table": "t", "sort_field": "bogus\" --" })).unwrap();
+        assert!(sort_keys(&req, &columns()).is_err());
+    }
+
+    #[test]
+    fn cursor_resumes_after_the_last_row_in_sort_order() {
+        let columns = columns();
+        let req: TableRowsRequest = serde_json::from_value(json!({
+            "table": "t", "sort": [{ "field": "amount", "dir": "desc" }]
+        }))
+        .unwrap();
+        let keys = keyset(sort_keys(&req, &columns).unwrap(), &["year".to_string()], &columns).unwrap();
+        assert_eq!(order_by(&keys), " ORDER BY \"amount\" DESC, \"year\" ASC");
+
+        let cursor = Cursor { keys: vec!["amount".into(), "year".into()], values: vec![None, Some("2019".into())] };
+        let cursor = Cursor::decode(&cursor.encode()).unwrap();
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT * FROM t WHERE TRUE");
+        push_after(&mut qb, &keys, &cursor).unwrap();
+        assert_eq!(
+            qb.sql(),
+            "SELECT * FROM t WHERE TRUE AND ((\"amount\" IS NOT NULL) OR (\"amount\" IS NULL AND \
+             (\"year\" > CAST($1 AS \"pg_catalog\".\"int2\") OR \"year\" IS NULL)))"
+        );
+
+        let stale = Cursor { keys: vec!["year".into()], values: vec![Some("2019".into())] };
+        assert!(push_after(&mut qb, &keys, &stale).is_err());
+        assert!(keyset(Vec::new(), &[], &columns).is_none());
+    }
+
+    #[test]
+    fn deleted_rows_are_hidden_by_column_type() {
+        let mut columns = columns();
+        assert_eq!(not_deleted(&columns), None);
+        columns.push(ColumnInfo {
+            name: "deleted".into(),
+            data_type: "boolean".into(),
+            udt_schema: "pg_catalog".into(),
+            udt_name: "bool".into(),
+        });
+        assert_eq!(not_deleted(&columns), Some("\"deleted\" IS NOT TRUE"));
+        columns.last_mut().unwrap().data_type = "smallint".into();
+        assert_eq!(not_deleted(&columns), Some("COALESCE(\"deleted\", 0) = 0"));
+    }
+}
@@ -0,0 +1,390 @@
+// src/uploads.rs
+//! Spreadsheet uploads for the import endpoints, under `/api/import/uploads`.
+//!
+//! `POST /api/import/uploads` takes one xlsx, xls, ods or csv file as multipart form data and
+//! stores it in a quarantine directory outside the project (IMPORT_UPLOAD_DIR) under a generated
+//! name, after checking its size and that its first bytes match its extension. The returned
+//! upload id can stand in for `file_path` in the preview, sheets and import requests. Uploads are
+//! never served back, and are deleted once they expire unless an unfinished import job still
+//! reads them.
+use actix_multipart::Multipart;
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Duration, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::ApiState;
+
+/// Where uploads go and how long and large they may be
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    pub dir: PathBuf,
+    pub max_bytes: u64,
+    pub ttl_hours: i64,
+}
+
+impl UploadConfig {
+    /// IMPORT_UPLOAD_DIR (default: `partner_tools_uploads` in the system temp directory),
+    /// IMPORT_UPLOAD_MAX_BYTES (default 25 MiB) and IMPORT_UPLOAD_TTL_HOURS (default 24)
+    pub fn from_env() -> Self {
+        let env_parse = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+        Self {
+            dir: std::env::var("IMPORT_UPLOAD_DIR")
+                .ok()
+                .filter(|d| !d.trim().is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| std::env::temp_dir().join("partner_tools_uploads")),
+            max_bytes: env_parse("IMPORT_UPLOAD_MAX_BYTES", 25 * 1024 * 1024),
+            ttl_hours: env_parse("IMPORT_UPLOAD_TTL_HOURS", 24) as i64,
+        }
+    }
+
+    fn file(&self, id: Uuid, kind: UploadKind) -> PathBuf {
+        self.dir.join(format!("{id}.{}", kind.extension()))
+    }
+
+    fn metadata(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+/// File formats the importers read
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadKind {
+    Xlsx,
+    Xls,
+    Ods,
+    Csv,
+}
+
+impl UploadKind {
+    fn from_file_name(name: &str) -> Option<Self> {
+        match Path::new(name).extension()?.to_str()?.to_lowercase().as_str() {
+            "xlsx" => Some(UploadKind::Xlsx),
+            "xls" => Some(UploadKind::Xls),
+            "ods" => Some(UploadKind::Ods),
+            "csv" => Some(UploadKind::Csv),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            UploadKind::Xlsx => "xlsx",
+            UploadKind::Xls => "xls",
+            UploadKind::Ods => "ods",
+            UploadKind::Csv => "csv",
+        }
+    }
+
+    fn content_type(&self) -> String {
+        mime_guess::from_ext(self.extension()).first_or_octet_stream().to_string()
+    }
+
+    /// Whether the first bytes of a file look like this format: xlsx and ods are zip archives
+    /// (an ods names its MIME type in the first entry), xls is an OLE2 compound file and csv is
+    /// UTF-8 text
+    fn sniff(&self, head: &[u8]) -> bool {
+        const ZIP: &[u8] = b"PK\x03\x04";
+        const OLE2: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+        const ODS: &[u8] = b"mimetypeapplication/vnd.oasis.opendocument.spreadsheet";
+        let is_ods = head.starts_with(ZIP) && head.get(30..).is_some_and(|rest| rest.starts_with(ODS));
+        match self {
+            UploadKind::Xlsx => head.starts_with(ZIP) && !is_ods,
+            UploadKind::Ods => is_ods,
+            UploadKind::Xls => head.starts_with(OLE2),
+            UploadKind::Csv => {
+                // The sample may end partway through a character
+                let text = match std::str::from_utf8(head) {
+                    Ok(text) => text,
+                    Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+                    Err(_) => return false,
+                };
+                !text.contains('\0')
+            }
+        }
+    }
+}
+
+/// An uploaded file, as stored next to it in `{id}.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Upload {
+    pub id: Uuid,
+    /// The name it was uploaded under; only shown, never used as a path
+    pub file_name: String,
+    pub kind: UploadKind,
+    pub content_type: String,
+    pub size: u64,
+    pub sheets: Vec<String>,
+    pub uploaded_by: Option<String>,
+    pub uploaded_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Files named by path are read relative to the server's working directory and must stay inside it
+fn checked_path(path: &str) -> std::result::Result<&Path, String> {
+    let path = Path::new(path);
+    if path.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("File path must be relative and stay inside the project: {}", path.display()));
+    }
+    Ok(path)
+}
+
+/// An upload stays usable until the sweep deletes it, which may be after `expires_at`
+fn find(config: &UploadConfig, id: Uuid) -> std::result::Result<Upload, String> {
+    let missing = || format!("Upload {id} not found; it may have expired");
+    let stored = std::fs::read(config.metadata(id)).map_err(|_| missing())?;
+    serde_json::from_slice(&stored).map_err(|_| missing())
+}
+
+/// The file an import reads: an upload, or a path under the server's working directory
+pub fn resolve(file_path: Option<&str>, upload_id: Option<Uuid>) -> std::result::Result<PathBuf, String> {
+    match (file_path.filter(|p| !p.trim().is_empty()), upload_id) {
+        (Some(_), Some(_)) => Err("Send either file_path or upload_id, not both".to_string()),
+        (None, Some(id)) => {
+            let config = UploadConfig::from_env();
+            find(&config, id).map(|upload| config.file(id, upload.kind))
+        }
+        (Some(path), None) => checked_path(path).map(Path::to_path_buf),
+        (None, None) => Err("file_path or upload_id is required".to_string()),
+    }
+}
+
+/// Delete expired uploads, and uploads that never finished arriving, except those an import
+/// job that may still be resumed refers to
+fn sweep(config: &UploadConfig, keep: &[String]) {
+    let Ok(entries) = std::fs::read_dir(&config.dir) else { return };
+    let now = Utc::now();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| Uuid::parse_str(s).ok()) else { continue };
+        if keep.contains(&id.to_string()) {
+            continue;
+        }
+        let expired = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => std::fs::read(&path)
+                .ok()
+                .and_then(|stored| serde_json::from_slice::<Upload>(&stored).ok())
+                .is_none_or(|upload| upload.expires_at < now),
+            Some("part") => entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| DateTime::<Utc>::from(modified) + Duration::hours(config.ttl_hours) < now),
+            _ => continue,
+        };
+        if expired {
+            for ext in ["json", "xlsx", "xls", "ods", "csv", "part"] {
+                std::fs::remove_file(config.dir.join(format!("{id}.{ext}"))).ok();
+            }
+        }
+    }
+}
+
+/// Upload ids in the requests of import jobs that haven't completed
+async fn uploads_in_use(db: &Pool<Postgres>) -> std::result::Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT request->>'upload_id' FROM import_jobs WHERE status <> 'completed' AND request ? 'upload_id'")
+        .fetch_all(db)
+        .await
+}
+
+fn error(status: actix_web::http::StatusCode, message: impl Into<String>) -> HttpResponse {
+    HttpResponse::build(status).json(json!({ "success": false, "message": message.into() }))
+}
+
+/// Stream the multipart `file` field into the quarantine directory
+async fn receive(config: &UploadConfig, mut payload: Multipart, uploaded_by: Option<String>) -> HttpResponse {
+    use actix_web::http::StatusCode;
+
+    while let Some(field) = payload.next().await {
+        let mut field = match field {
+            Ok(field) => field,
+            Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid multipart body: {e}")),
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+        let file_name: String = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .map(|name| name.chars().take(255).collect())
+            .unwrap_or_default();
+        let Some(kind) = UploadKind::from_file_name(&file_name) else {
+            return error(StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("'{file_name}' is not an .xlsx, .xls, .ods or .csv file"));
+        };
+
+        if let Err(e) = std::fs::create_dir_all(&config.dir) {
+            return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot create the upload directory: {e}"));
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&config.dir, std::fs::Permissions::from_mode(0o700)).ok();
+        }
+        let id = Uuid::new_v4();
+        let partial = config.dir.join(format!("{id}.part"));
+        let mut file = match std::fs::File::create(&partial) {
+            Ok(file) => file,
+            Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot store the upload: {e}")),
+        };
+        let mut size = 0u64;
+        let mut head = Vec::new();
+        let failure = loop {
+            let chunk = match field.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => break Some(error(StatusCode::BAD_REQUEST, format!("Upload interrupted: {e}"))),
+                None => break None,
+            };
+            size += chunk.len() as u64;
+            if size > config.max_bytes {
+                break Some(error(StatusCode::PAYLOAD_TOO_LARGE, format!("Uploads are limited to {} bytes", config.max_bytes)));
+            }
+            if head.len() < 4096 {
+                head.extend_from_slice(&chunk[..chunk.len().min(4096 - head.len())]);
+            }
+            if let Err(e) = file.write_all(&chunk) {
+                break Some(error(StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot store the upload: {e}")));
+            }
+        };
+        drop(file);
+        let failure = failure
+            .or_else(|| (size == 0).then(|| error(StatusCode::BAD_REQUEST, format!("'{file_name}' is empty"))))
+            .or_else(|| {
+                (!kind.sniff(&head)).then(|| {
+                    error(StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("'{file_name}' does not contain {} data", kind.extension()))
+                })
+            });
+        if let Some(response) = failure {
+            std::fs::remove_file(&partial).ok();
+            return response;
+        }
+
+        let stored = config.file(id, kind);
+        if let Err(e) = std::fs::rename(&partial, &stored) {
+            std::fs::remove_file(&partial).ok();
+            return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot store the upload: {e}"));
+        }
+        // Opening it as the importers will is the final check that it's a readable spreadsheet
+        let sheets = match crate::spreadsheet::sheet_names(&stored) {
+            // A csv file's one sheet is named after the file, which should be the one uploaded
+            Ok(_) if kind == UploadKind::Csv => {
+                vec![Path::new(&file_name).file_stem().and_then(|s| s.to_str()).unwrap_or("Sheet1").to_string()]
+            }
+            Ok(sheets) => sheets,
+            Err(e) => {
+                std::fs::remove_file(&stored).ok();
+                return error(StatusCode::UNPROCESSABLE_ENTITY, format!("Cannot read '{file_name}': {e}"));
+            }
+        };
+        let now = Utc::now();
+        let upload = Upload {
+            id,
+            file_name,
+            kind,
+            content_type: kind.content_type(),
+            size,
+            sheets,
+            uploaded_by,
+            uploaded_at: now,
+            expires_at: now + Duration::hours(config.ttl_hours),
+        };
+        let written = serde_json::to_vec_pretty(&upload)
+            .map_err(std::io::Error::other)
+            .and_then(|bytes| std::fs::write(config.metadata(id), bytes));
+        if let Err(e) = written {
+            std::fs::remove_file(&stored).ok();
+            return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot store the upload: {e}"));
+        }
+        return HttpResponse::Created().json(json!({
+            "success": true,
+            "message": format!("Uploaded '{}' ({} bytes); it expires at {}", upload.file_name, upload.size, upload.expires_at),
+            "data": upload
+        }));
+    }
+    error(StatusCode::BAD_REQUEST, "Send the spreadsheet as the multipart field 'file'")
+}
+
+// POST /api/import/uploads
+pub async fn create_upload(data: web::Data<Arc<ApiState>>, session: Session, payload: Multipart) -> Result<HttpResponse> {
+    let config = UploadConfig::from_env();
+    if let Some(db) = &data.db {
+        // Without the job list, nothing is swept rather than risk an upload a job needs
+        if let Ok(keep) = uploads_in_use(db).await {
+            sweep(&config, &keep);
+        }
+    }
+    let uploaded_by = crate::sessions::current_user(&session).map(|user| user.email);
+    let response = receive(&config, payload, uploaded_by).await;
+    crate::audit::record(&data, &session, "import.upload", "uploads", json!({}), &response).await;
+    Ok(response)
+}
+
+// GET /api/import/uploads/{id}
+pub async fn get_upload(path: web::Path<Uuid>) -> Result<HttpResponse> {
+    match find(&UploadConfig::from_env(), path.into_inner()) {
+        Ok(upload) => Ok(HttpResponse::Ok().json(json!({ "success": true, "data": upload }))),
+        Err(message) => Ok(error(actix_web::http::StatusCode::NOT_FOUND, message)),
+    }
+}
+
+// DELETE /api/import/uploads/{id}
+pub async fn delete_upload(data: web::Data<Arc<ApiState>>, session: Session, path: web::Path<Uuid>) -> Result<HttpResponse> {
+    let config = UploadConfig::from_env();
+    let id = path.into_inner();
+    let response = match find(&config, id) {
+        Ok(upload) => {
+            std::fs::remove_file(config.file(id, upload.kind)).ok();
+            std::fs::remove_file(config.metadata(id)).ok();
+            HttpResponse::Ok().json(json!({ "success": true, "message": format!("Upload {id} deleted") }))
+        }
+        Err(message) => error(actix_web::http::StatusCode::NOT_FOUND, message),
+    };
+    crate::audit::record(&data, &session, "import.upload.delete", &format!("uploads/{id}"), json!({}), &response).await;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_stay_inside_the_project() {
+        assert!(checked_path("preferences/projects/list.xlsx").is_ok());
+        assert!(checked_path("../secrets.csv").is_err());
+        assert!(checked_path("/etc/passwd").is_err());
+        assert!(resolve(Some("a.csv"), Some(Uuid::nil())).is_err());
+        assert!(resolve(None, None).is_err());
+    }
+
+    #[test]
+    fn contents_must_match_the_extension() {
+        let zip = b"PK\x03\x04\x14\x00\x00\x00\x08\x00rest of an xlsx";
+        assert!(UploadKind::Xlsx.sniff(zip));
+        assert!(!UploadKind::Xls.sniff(zip));
+        assert!(UploadKind::Xls.sniff(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, 0]));
+        let mut ods = b"PK\x03\x04".to_vec();
+        ods.resize(30, 0);
+        ods.extend_from_slice(b"mimetypeapplication/vnd.oasis.opendocument.spreadsheetPK");
+        assert!(UploadKind::Ods.sniff(&ods) && !UploadKind::Xlsx.sniff(&ods));
+        assert!(UploadKind::Csv.sniff("name,city\nZoë,Zürich\n".as_bytes()));
+        // Cut in the middle of "ë"
+        assert!(UploadKind::Csv.sniff(&"Zoë".as_bytes()[..3]));
+        assert!(!UploadKind::Csv.sniff(zip));
+        assert!(!UploadKind::Csv.sniff(&[0xFF, 0xFE, 0x00, 0x41]));
+        assert_eq!(UploadKind::from_file_name("Report.XLSX"), Some(UploadKind::Xlsx));
+        assert_eq!(UploadKind::from_file_name("notes.txt"), None);
+    }
+}